@@ -25,7 +25,7 @@ fn main() {
     sub.grade_against(&mut rubric);
 
     match sub.submit("http://localhost:8080/submit") {
-        Ok(_) => println!("Submitted successfully!"),
+        Ok(receipt) => receipt.print(),
         Err(e) => println!("Error, couldn't submit. {}", e),
     }
 }
@@ -0,0 +1,38 @@
+// Checks a dropbox's submissions for shared machines,
+// shared repositories, and bursts of submissions.
+//
+//   cargo run --example integrity_report -- submissions.csv [report.csv|report.json]
+
+use std::env;
+
+use rubric::dropbox::{IntegrityOptions, IntegrityReport};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let input = args.get(1).map(String::as_str).unwrap_or("submissions.csv");
+
+    let options = IntegrityOptions::default()
+        .compare("repo_url")
+        .bursts(5, 10);
+
+    let report = match IntegrityReport::from_file(input, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Couldn't read {}. {}", input, e);
+            return;
+        }
+    };
+
+    let result = match args.get(2) {
+        Some(out) if out.ends_with(".json") => report.write_json(out),
+        Some(out) => report.write_csv(out),
+        None => {
+            println!("{}", report.to_csv());
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Couldn't write the report. {}", e);
+    }
+}
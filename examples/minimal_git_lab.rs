@@ -65,9 +65,8 @@ fn main() {
     report::long(&mut rubric);
 
     let url = format!("http://localhost:8080/submit");
-    if sub.submit(&url).is_ok() {
-        println!("Submitted!");
-    } else {
-        println!("Error! Couldn't submit");
+    match sub.submit(&url) {
+        Ok(receipt) => receipt.print(),
+        Err(e) => println!("Error! Couldn't submit. {}", e),
     }
 }
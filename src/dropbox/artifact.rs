@@ -0,0 +1,368 @@
+//! Files attached to a submission for manual review
+//!
+//! A grader can attach files, like a student's `Dockerfile` or some log output,
+//! with [`Submission::attach_file`](crate::dropbox::submission::Submission::attach_file).
+//! When a submission has artifacts, it's sent to the dropbox as a multipart form instead
+//! of plain JSON. The submission's JSON lists each artifact's name, size, and SHA-256 hash,
+//! so the dropbox can make sure the files it received are the ones that were attached.
+//!
+//! The dropbox stores artifacts in a directory per submission, named with the SHA-256 of
+//! the submission's id, `<artifact dir>/<hash>/`. Ids are up to the grader, so two of
+//! them could clean up to the same directory name, but their hashes won't match.
+
+// std uses
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// internal uses
+use crate::Result;
+
+
+/// The largest file that can be attached by default, 1 MiB
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// The most that can be attached to one submission by default, 10 MiB
+pub const DEFAULT_MAX_TOTAL_SIZE: u64 = 10 * 1024 * 1024;
+/// Where the dropbox stores artifacts, relative to where it's run
+pub const DEFAULT_ARTIFACT_DIR: &str = "artifacts";
+
+/// The form field holding the submission's JSON
+pub const SUBMISSION_FIELD: &str = "submission";
+/// The form field for each artifact
+pub const ARTIFACT_FIELD: &str = "artifact";
+
+
+/// How much can be attached to a submission
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtifactLimits {
+    /// The largest single file, in bytes
+    pub max_file_size: u64,
+    /// The most all files together can be, in bytes
+    pub max_total_size: u64,
+}
+
+impl Default for ArtifactLimits {
+    fn default() -> Self {
+        ArtifactLimits {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_total_size: DEFAULT_MAX_TOTAL_SIZE,
+        }
+    }
+}
+
+
+/// A file attached to a submission
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The file's name. This is the path it was attached from, relative to
+    /// the current directory, with the directory separators replaced so it's unique.
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    /// SHA-256 of the contents, in hex
+    pub sha256: String,
+    /// Where the file is. On the grader's side this is the file that was attached,
+    /// and on the dropbox's side it's where the file was stored.
+    ///
+    /// This is never sent to the dropbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl Artifact {
+    /// Reads a file to attach.
+    ///
+    /// This returns an error if the file doesn't exist, isn't a file,
+    /// or is larger than `max_size` bytes.
+    pub fn from_path<P: AsRef<Path>>(path: P, max_size: u64) -> Result<Artifact> {
+        let path = path.as_ref();
+        let meta = fs::metadata(path).context(format!("Couldn't attach {}", path.display()))?;
+        if !meta.is_file() {
+            bail!("Couldn't attach {}, it isn't a file", path.display());
+        }
+        if meta.len() > max_size {
+            bail!("Couldn't attach {}, it's {} bytes and the limit is {}", path.display(), meta.len(), max_size);
+        }
+
+        let contents = fs::read(path).context(format!("Couldn't read {}", path.display()))?;
+
+        // Absolute paths would tell the dropbox where the student keeps their work,
+        // so name the file from the current directory, or just use its name
+        let relative = env::current_dir().ok()
+            .and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or(path);
+        let name = match relative.is_absolute() {
+            true => artifact_name(path.file_name().unwrap_or_default()),
+            false => artifact_name(relative),
+        };
+
+        Ok(Artifact {
+            name,
+            size: contents.len() as u64,
+            sha256: digest_bytes(&contents),
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    /// Reads the file's contents
+    pub fn read(&self) -> Result<Vec<u8>> {
+        match &self.path {
+            Some(path) => Ok(fs::read(path).context(format!("Couldn't read artifact {}", path.display()))?),
+            None => bail!("Artifact {} doesn't have a path", self.name),
+        }
+    }
+
+    /// Returns true if `contents` are what was attached
+    pub fn matches(&self, contents: &[u8]) -> bool {
+        contents.len() as u64 == self.size && digest_bytes(contents) == self.sha256
+    }
+}
+
+
+/// SHA-256 of some bytes, in hex
+pub fn digest_bytes(contents: &[u8]) -> String {
+    Sha256::digest(contents).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A name for an artifact that's safe to use as a filename.
+///
+/// Anything other than letters, numbers, `.`, `-`, and `_` becomes `_`,
+/// so `src/main.rs` becomes `src_main.rs`.
+pub fn artifact_name<P: AsRef<Path>>(path: P) -> String {
+    let name: String = path.as_ref().to_string_lossy()
+        .trim_start_matches("./")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    // Don't allow hidden files, or names like ".."
+    name.trim_start_matches('.').to_string()
+}
+
+/// The directory a submission's artifacts are stored in, inside the artifact directory
+pub fn submission_dir(submission_id: &str) -> String {
+    digest_bytes(submission_id.as_bytes())
+}
+
+/// Makes sure a submission's artifacts will each be stored in their own file.
+///
+/// Names like `src/main.rs` and `src_main.rs` are different, but [`artifact_name`]
+/// cleans them up to the same file name, so one would overwrite the other.
+pub fn check_names(artifacts: &[Artifact]) -> Result<()> {
+    for (i, a) in artifacts.iter().enumerate() {
+        let file_name = artifact_name(&a.name);
+        if file_name.is_empty() {
+            bail!("Artifact '{}' can't be a file name", a.name);
+        }
+        if let Some(other) = artifacts[..i].iter().find(|o| artifact_name(&o.name) == file_name) {
+            bail!("Artifacts '{}' and '{}' would both be stored as '{}'", other.name, a.name, file_name);
+        }
+    }
+    Ok(())
+}
+
+/// Stores an artifact the dropbox received, returning where it was written.
+///
+/// The artifact's name has to have something left after [`artifact_name`] cleans it,
+/// or it'd be written somewhere shared. Use [`check_names`] first, so a submission's
+/// artifacts don't overwrite each other.
+pub fn store<P: AsRef<Path>>(dir: P, submission_id: &str, name: &str, contents: &[u8]) -> Result<PathBuf> {
+    let file_name = artifact_name(name);
+    if file_name.is_empty() {
+        bail!("Couldn't store artifact '{}', it can't be a file name", name);
+    }
+    let dir = dir.as_ref().join(submission_dir(submission_id));
+    fs::create_dir_all(&dir).context(format!("Couldn't create artifact directory {}", dir.display()))?;
+    let path = dir.join(file_name);
+    fs::write(&path, contents).context(format!("Couldn't write artifact {}", path.display()))?;
+    Ok(path)
+}
+
+
+/// One part of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+// Finds a byte sequence, starting at `from`
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+    haystack[from..].windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+// Pulls a parameter like `name="value"` out of a Content-Disposition header
+fn disposition_param(header: &str, param: &str) -> Option<String> {
+    header.split(';')
+        .map(|p| p.trim())
+        .find_map(|p| {
+            let (key, value) = p.split_at(p.find('=')?);
+            if key.trim().eq_ignore_ascii_case(param) {
+                Some(value[1..].trim().trim_matches('"').to_string())
+            } else {
+                None
+            }
+        })
+}
+
+/// Splits a `multipart/form-data` body into its parts.
+///
+/// This only handles what [`post_multipart`](crate::helpers::web::post_multipart)
+/// sends, so it isn't a complete implementation of the format.
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Result<Vec<FormPart>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+
+    let mut pos = match find(body, &delimiter, 0) {
+        Some(p) => p + delimiter.len(),
+        None => bail!("Multipart body doesn't contain the boundary"),
+    };
+
+    loop {
+        // The last boundary ends with "--"
+        if body[pos..].starts_with(b"--") {
+            break;
+        }
+        let headers_start = pos + 2;
+        let headers_end = find(body, b"\r\n\r\n", headers_start)
+            .context("Multipart part doesn't have headers")?;
+        let headers = String::from_utf8_lossy(&body[headers_start..headers_end]);
+
+        let data_start = headers_end + 4;
+        let mut next = b"\r\n".to_vec();
+        next.extend_from_slice(&delimiter);
+        let data_end = find(body, &next, data_start)
+            .context("Multipart body ended early")?;
+
+        let disposition = headers.lines()
+            .find(|l| l.to_ascii_lowercase().starts_with("content-disposition:"))
+            .context("Multipart part doesn't have a Content-Disposition")?;
+
+        parts.push(FormPart {
+            name: disposition_param(disposition, "name").unwrap_or_default(),
+            filename: disposition_param(disposition, "filename"),
+            data: body[data_start..data_end].to_vec(),
+        });
+
+        pos = data_end + next.len();
+    }
+
+    Ok(parts)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_artifact_name() {
+        assert_eq!(artifact_name("Dockerfile"), "Dockerfile");
+        assert_eq!(artifact_name("./src/main.rs"), "src_main.rs");
+        assert_eq!(artifact_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(artifact_name(".env"), "env");
+    }
+
+    #[test]
+    fn test_from_path() {
+        let dir = TempDir::new("artifact").unwrap();
+        let path = dir.path().join("log.txt");
+        fs::write(&path, "some output").unwrap();
+
+        let artifact = Artifact::from_path(&path, DEFAULT_MAX_FILE_SIZE).unwrap();
+        assert_eq!(artifact.size, 11);
+        assert_eq!(artifact.name, "log.txt");
+        assert!(artifact.matches(b"some output"));
+        assert!(!artifact.matches(b"other output"));
+        assert_eq!(artifact.read().unwrap(), b"some output");
+
+        // Too big
+        assert!(Artifact::from_path(&path, 5).is_err());
+        // Not a file
+        assert!(Artifact::from_path(dir.path(), DEFAULT_MAX_FILE_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_path_not_serialized_when_missing() {
+        let artifact = Artifact {
+            name: String::from("a"),
+            size: 0,
+            sha256: digest_bytes(b""),
+            path: None,
+        };
+        assert!(!serde_json::to_string(&artifact).unwrap().contains("path"));
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = TempDir::new("artifact").unwrap();
+        let path = store(dir.path(), "abc123", "Dockerfile", b"FROM rust").unwrap();
+        assert_eq!(path, dir.path().join(submission_dir("abc123")).join("Dockerfile"));
+        assert_eq!(fs::read(path).unwrap(), b"FROM rust");
+
+        // Ids that clean up to the same name still get their own directory
+        let a = store(dir.path(), "a/b", "Dockerfile", b"a").unwrap();
+        let b = store(dir.path(), "a_b", "Dockerfile", b"b").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(fs::read(a).unwrap(), b"a");
+
+        // Any id works
+        assert!(store(dir.path(), "", "Dockerfile", b"FROM rust").is_ok());
+        assert!(store(dir.path(), "../..", "Dockerfile", b"FROM rust").is_ok());
+    }
+
+    #[test]
+    fn test_store_needs_names() {
+        let dir = TempDir::new("artifact").unwrap();
+        assert!(store(dir.path(), "abc123", "..", b"FROM rust").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_check_names() {
+        let artifact = |name: &str| Artifact {
+            name: String::from(name),
+            size: 0,
+            sha256: digest_bytes(b""),
+            path: None,
+        };
+        assert!(check_names(&[artifact("src/main.rs"), artifact("Dockerfile")]).is_ok());
+        assert!(check_names(&[artifact("src/main.rs"), artifact("src_main.rs")]).is_err());
+        assert!(check_names(&[artifact("..")]).is_err());
+    }
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = b"--XYZ\r\n\
+            Content-Disposition: form-data; name=\"submission\"\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"id\":\"1\"}\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"artifact\"; filename=\"log.txt\"\r\n\r\n\
+            line one\r\nline two\r\n\
+            --XYZ--\r\n";
+
+        let parts = parse_multipart(body, "XYZ").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "submission");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"{\"id\":\"1\"}");
+        assert_eq!(parts[1].name, "artifact");
+        assert_eq!(parts[1].filename.as_deref(), Some("log.txt"));
+        assert_eq!(parts[1].data, b"line one\r\nline two");
+
+        assert!(parse_multipart(b"nothing here", "XYZ").is_err());
+    }
+}
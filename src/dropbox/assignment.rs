@@ -0,0 +1,211 @@
+//! Serving more than one assignment from the same dropbox
+//!
+//! Each assignment gets its own route, `/submit/<assignment>`, and its own results
+//! and receipts, in a directory named after it next to the usual results file.
+//! Each can have its own deadline, attempt limit, rubric versions, and score policy.
+//!
+//! ```rust
+//! use rubric::dropbox::{Assignment, DropboxConfig};
+//!
+//! let config = DropboxConfig::new(8080)
+//!     .assignment(Assignment::new("lab1").max_attempts(3))
+//!     .assignment(Assignment::new("lab2").rubric_versions(&["2", "3"]));
+//! assert!(config.accepts_assignment(Some("lab2")));
+//! ```
+
+// external uses
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Local};
+use reqwest::Url;
+
+// internal uses
+use crate::Result;
+use crate::rubric::{Rubric, LatePolicy, AttemptPolicy, ScorePolicy};
+use crate::dropbox::artifact::artifact_name;
+
+
+/// One assignment the dropbox accepts submissions for, and its settings.
+///
+/// Anything that isn't set here uses the dropbox's setting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    /// The name in the URL, like `lab1` in `/submit/lab1`
+    pub name: String,
+    /// Deadlines and penalties, decided by the dropbox's clock
+    pub late_policy: Option<LatePolicy>,
+    /// How many attempts each student gets
+    pub attempt_policy: Option<AttemptPolicy>,
+    /// Which attempt counts in the gradebook
+    pub score_policy: Option<ScorePolicy>,
+    /// If not empty, only submissions graded against these versions
+    /// of the rubric are accepted
+    pub rubric_versions: Vec<String>,
+}
+
+impl Assignment {
+    /// An assignment with the dropbox's settings
+    pub fn new(name: &str) -> Self {
+        Assignment {
+            name: String::from(name),
+            late_policy: None,
+            attempt_policy: None,
+            score_policy: None,
+            rubric_versions: Vec::new(),
+        }
+    }
+
+    /// An assignment named after the rubric, with its deadlines, attempt limit,
+    /// score policy, and version. Only the rubric's current version is accepted.
+    pub fn from_rubric(rubric: &Rubric) -> Self {
+        let mut assignment = Assignment::new(&rubric.name);
+        if rubric.deadline.is_some() || rubric.final_deadline.is_some() {
+            assignment.late_policy = Some(rubric.late_policy());
+        }
+        if rubric.max_attempts.is_some() {
+            assignment.attempt_policy = Some(rubric.attempt_policy());
+        }
+        assignment.score_policy = Some(rubric.score_policy);
+        assignment.rubric_versions = rubric.version.iter().cloned().collect();
+        assignment
+    }
+
+    /// Decide lateness and penalties with this policy
+    pub fn late_policy(mut self, policy: LatePolicy) -> Self {
+        self.late_policy = Some(policy);
+        self
+    }
+
+    /// Mark submissions received after this as late. Any other
+    /// parts of the late policy are kept.
+    pub fn deadline(mut self, deadline: DateTime<Local>) -> Self {
+        let mut policy = self.late_policy.unwrap_or_default();
+        policy.deadline = Some(deadline);
+        self.late_policy = Some(policy);
+        self
+    }
+
+    /// Count attempts with this policy
+    pub fn attempt_policy(mut self, policy: AttemptPolicy) -> Self {
+        self.attempt_policy = Some(policy);
+        self
+    }
+
+    /// Give each student this many attempts. Any other parts
+    /// of the attempt policy are kept.
+    pub fn max_attempts(mut self, max: u32) -> Self {
+        let mut policy = self.attempt_policy.unwrap_or_default();
+        policy.max_attempts = Some(max);
+        self.attempt_policy = Some(policy);
+        self
+    }
+
+    /// Take one grade from each student's attempts this way
+    pub fn score_policy(mut self, policy: ScorePolicy) -> Self {
+        self.score_policy = Some(policy);
+        self
+    }
+
+    /// Only accept submissions graded against these versions of the rubric.
+    /// See [`Rubric::version`](crate::rubric::Rubric::version).
+    pub fn rubric_versions<S: AsRef<str>>(mut self, versions: &[S]) -> Self {
+        self.rubric_versions = versions.iter().map(|v| String::from(v.as_ref())).collect();
+        self
+    }
+
+    /// Returns true if a submission graded against this rubric version is accepted.
+    /// Any version is, unless versions were given.
+    ///
+    /// ```rust
+    /// use rubric::dropbox::Assignment;
+    ///
+    /// let lab = Assignment::new("lab1").rubric_versions(&["2"]);
+    /// assert!(lab.accepts_version(Some("2")));
+    /// assert!(!lab.accepts_version(Some("1")));
+    /// assert!(!lab.accepts_version(None));
+    /// ```
+    pub fn accepts_version(&self, version: Option<&str>) -> bool {
+        if self.rubric_versions.is_empty() {
+            return true;
+        }
+        version.map_or(false, |v| self.rubric_versions.iter().any(|accepted| accepted == v))
+    }
+
+    /// The name of the directory the assignment's results are kept in.
+    /// It's the name, made safe for the filesystem. A dropbox won't open
+    /// with two assignments that have the same one.
+    pub fn dir_name(&self) -> String {
+        artifact_name(&self.name)
+    }
+}
+
+
+/// Adds an assignment to the dropbox's submit URL, so
+/// `http://localhost:8080/submit` becomes `http://localhost:8080/submit/lab1`.
+///
+/// ```rust
+/// use rubric::dropbox::assignment::submit_url;
+///
+/// let url = submit_url("http://localhost:8080/submit/", "lab 1").unwrap();
+/// assert_eq!(url, "http://localhost:8080/submit/lab%201");
+/// ```
+pub fn submit_url(url: &str, assignment: &str) -> Result<String> {
+    let mut parsed = Url::parse(url).context(format!("Invalid dropbox URL {}", url))?;
+    parsed.path_segments_mut()
+        .map_err(|_| anyhow!("Can't add an assignment to {}", url))?
+        .pop_if_empty()
+        .push(assignment);
+    Ok(parsed.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml;
+
+    #[test]
+    fn test_settings() {
+        let deadline = Local::now();
+        let lab = Assignment::new("lab1")
+            .deadline(deadline)
+            .max_attempts(2)
+            .score_policy(ScorePolicy::Best);
+        assert_eq!(lab.score_policy, Some(ScorePolicy::Best));
+        assert_eq!(lab.late_policy.as_ref().unwrap().deadline, Some(deadline));
+        let attempts = lab.attempt_policy.as_ref().unwrap();
+        assert_eq!(attempts.max_attempts, Some(2));
+        assert!(attempts.reject_extra);
+        assert!(lab.rubric_versions.is_empty());
+    }
+
+    #[test]
+    fn test_from_rubric() {
+        let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        let lab = Assignment::from_rubric(&rubric);
+        assert_eq!(lab.name, "Test Rubric");
+        assert_eq!(lab.late_policy.as_ref().unwrap().deadline, rubric.deadline);
+        assert!(lab.attempt_policy.is_none());
+        assert_eq!(lab.score_policy, Some(ScorePolicy::Latest));
+        assert!(lab.accepts_version(None));
+
+        rubric.max_attempts = Some(3);
+        rubric.version = Some(String::from("2"));
+        let lab = Assignment::from_rubric(&rubric);
+        assert_eq!(lab.attempt_policy.unwrap().max_attempts, Some(3));
+        assert_eq!(lab.rubric_versions, vec!["2"]);
+    }
+
+    #[test]
+    fn test_dir_name() {
+        assert_eq!(Assignment::new("Lab 1: Docker").dir_name(), "Lab_1__Docker");
+        assert_eq!(Assignment::new("../lab").dir_name(), "_lab");
+    }
+
+    #[test]
+    fn test_submit_url() {
+        assert_eq!(submit_url("http://localhost:8080/submit", "lab1").unwrap(), "http://localhost:8080/submit/lab1");
+        assert_eq!(submit_url("http://localhost:8080/submit", "a/b").unwrap(), "http://localhost:8080/submit/a%2Fb");
+        assert!(submit_url("not a url", "lab1").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Tokens for submitting to the dropbox, and for reading from it
+//!
+//! If the dropbox is given API tokens, only requests with one of them in the
+//! `Authorization` header can submit. The token is usually embedded in the grader,
+//! see [`Submission::set_api_token`](crate::dropbox::submission::Submission::set_api_token).
+//! The dropbox accepts every token it's given, so to rotate a token, add the new one,
+//! hand out graders with it, then remove the old one.
+//!
+//! The instructor token is for reading submissions and other instructor routes. It
+//! can also submit. If there isn't one, those routes are closed to everyone.
+//!
+//! Requests without a token are refused with `401 Unauthorized`, and requests with
+//! a token that isn't accepted with `403 Forbidden`.
+//!
+//! Browsers can't send the header, so the token can also be in a cookie. The
+//! [`dashboard`](crate::dropbox::dashboard) sets it when an instructor logs in.
+//!
+//! ```rust
+//! use rubric::dropbox::DropboxConfig;
+//! use rubric::dropbox::auth::{self, Access};
+//!
+//! let config = DropboxConfig::new(8080)
+//!     .api_token("old token")
+//!     .api_token("new token")
+//!     .instructor_token("instructor token");
+//!
+//! assert_eq!(auth::submit_access(&config, Some("old token")), Access::Granted);
+//! assert_eq!(auth::submit_access(&config, None), Access::Missing);
+//! assert_eq!(auth::instructor_access(&config, Some("new token")), Access::Denied);
+//! ```
+
+// external uses
+use rand::Rng;
+use rocket::{Request, State, Outcome};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+
+// internal uses
+use crate::dropbox::DropboxConfig;
+
+
+/// The header the token is sent in
+pub const AUTHORIZATION_HEADER: &str = "Authorization";
+/// The cookie the token is kept in, for browsers
+pub const TOKEN_COOKIE: &str = "rubric_token";
+
+
+/// What a token lets a request do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// The token is accepted, or none is needed
+    Granted,
+    /// A token is needed, and there wasn't one. This is `401 Unauthorized`.
+    Missing,
+    /// The token isn't accepted for this. This is `403 Forbidden`.
+    Denied,
+}
+
+/// A new random token, 64 hex characters
+pub fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The token in an `Authorization` header, like `Bearer abc123`.
+/// `None` if it isn't a bearer token.
+///
+/// ```rust
+/// use rubric::dropbox::auth::bearer_token;
+///
+/// assert_eq!(bearer_token("Bearer abc123"), Some("abc123"));
+/// assert_eq!(bearer_token("bearer  abc123 "), Some("abc123"));
+/// assert_eq!(bearer_token("Basic abc123"), None);
+/// ```
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let header = header.trim();
+    let (scheme, token) = header.split_at(header.find(' ')?);
+    let token = token.trim();
+    if !scheme.eq_ignore_ascii_case("bearer") || token.is_empty() {
+        return None;
+    }
+    Some(token)
+}
+
+/// The value of the `Authorization` header for a token
+pub fn bearer_header(token: &str) -> String {
+    format!("Bearer {}", token)
+}
+
+// Compares tokens without stopping at the first difference, so
+// how long it takes doesn't give away how much of a guess was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    if given.len() != expected.len() {
+        return false;
+    }
+    given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Checks a token against the accepted ones
+fn access(token: Option<&str>, accepted: &[&str]) -> Access {
+    match token {
+        None => Access::Missing,
+        Some(token) if accepted.iter().any(|a| tokens_match(token, a)) => Access::Granted,
+        Some(_) => Access::Denied,
+    }
+}
+
+/// What a token lets a request do on the submit routes. No token is
+/// needed unless the dropbox has API tokens.
+pub fn submit_access(config: &DropboxConfig, token: Option<&str>) -> Access {
+    if config.api_tokens.is_empty() {
+        return Access::Granted;
+    }
+    let mut accepted: Vec<&str> = config.api_tokens.iter().map(|t| t.as_str()).collect();
+    accepted.extend(config.instructor_token.as_deref());
+    access(token, &accepted)
+}
+
+/// What a token lets a request do on the instructor routes. If the dropbox
+/// doesn't have an instructor token, they're closed.
+pub fn instructor_access(config: &DropboxConfig, token: Option<&str>) -> Access {
+    match &config.instructor_token {
+        Some(instructor) => access(token, &[instructor.as_str()]),
+        None if token.is_none() => Access::Missing,
+        None => Access::Denied,
+    }
+}
+
+
+// Decides a request guard's outcome from the request's token, in
+// the header or the cookie
+fn guard<'a, 'r, T>(req: &'a Request<'r>, check: fn(&DropboxConfig, Option<&str>) -> Access, granted: T) -> request::Outcome<T, ()> {
+    let config = match req.guard::<State<DropboxConfig>>() {
+        Outcome::Success(config) => config,
+        _ => return Outcome::Failure((Status::InternalServerError, ())),
+    };
+    let cookie = req.cookies().get(TOKEN_COOKIE).map(|c| c.value().to_string());
+    let token = req.headers().get_one(AUTHORIZATION_HEADER)
+        .and_then(bearer_token)
+        .or_else(|| cookie.as_deref());
+    match check(config.inner(), token) {
+        Access::Granted => Outcome::Success(granted),
+        Access::Missing => Outcome::Failure((Status::Unauthorized, ())),
+        Access::Denied => Outcome::Failure((Status::Forbidden, ())),
+    }
+}
+
+/// A request guard for routes that accept submissions.
+/// See [`submit_access`](crate::dropbox::auth::submit_access).
+pub struct SubmitToken;
+
+impl<'a, 'r> FromRequest<'a, 'r> for SubmitToken {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        guard(req, submit_access, SubmitToken)
+    }
+}
+
+/// A request guard for instructor routes.
+/// See [`instructor_access`](crate::dropbox::auth::instructor_access).
+pub struct InstructorToken;
+
+impl<'a, 'r> FromRequest<'a, 'r> for InstructorToken {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        guard(req, instructor_access, InstructorToken)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token(&bearer_header("abc")), Some("abc"));
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abc", "abd"));
+        assert!(!tokens_match("abc", "abcd"));
+        assert!(!tokens_match("", "abc"));
+    }
+
+    #[test]
+    fn test_submit_access() {
+        // Open to everyone without API tokens
+        let config = DropboxConfig::new(8080).instructor_token("teacher");
+        assert_eq!(submit_access(&config, None), Access::Granted);
+        assert_eq!(submit_access(&config, Some("anything")), Access::Granted);
+
+        let config = config.api_tokens(&["fall", "spring"]);
+        assert_eq!(submit_access(&config, None), Access::Missing);
+        assert_eq!(submit_access(&config, Some("fall")), Access::Granted);
+        assert_eq!(submit_access(&config, Some("spring")), Access::Granted);
+        assert_eq!(submit_access(&config, Some("teacher")), Access::Granted);
+        assert_eq!(submit_access(&config, Some("summer")), Access::Denied);
+    }
+
+    #[test]
+    fn test_instructor_access() {
+        // Closed without an instructor token
+        let config = DropboxConfig::new(8080).api_token("fall");
+        assert_eq!(instructor_access(&config, None), Access::Missing);
+        assert_eq!(instructor_access(&config, Some("fall")), Access::Denied);
+
+        let config = config.instructor_token("teacher");
+        assert_eq!(instructor_access(&config, Some("teacher")), Access::Granted);
+        assert_eq!(instructor_access(&config, Some("fall")), Access::Denied);
+        assert_eq!(instructor_access(&config, None), Access::Missing);
+    }
+}
@@ -0,0 +1,1028 @@
+//! Settings for the dropbox
+//!
+//! Settings can be built in code, or loaded from a YAML or TOML file and
+//! environment variables. Every key is optional.
+//!
+//! ```yaml
+//! address: 127.0.0.1
+//! port: 8080
+//! # Puts submissions.csv, receipts.jsonl, and artifacts/ in this directory
+//! results_dir: /var/lib/dropbox
+//! store: jsonl
+//! delimiter: ";"
+//! log_level: critical
+//! max_upload_size: 1048576
+//! # Each assignment is a name, or its settings
+//! assignments:
+//!   - lab1
+//!   - name: lab2
+//!     deadline: 2020-05-24 23:59:59
+//!     max_attempts: 3
+//!     score_policy: best
+//!     rubric_versions: [2, 3]
+//!   # Named after the rubric, with its deadlines and attempts
+//!   - rubric: rubrics/lab3.yml
+//! secret: secret key
+//! # Graders need one of these to submit
+//! api_tokens: [fall-2020-token]
+//! instructor_token: instructor token
+//! # Columns for LMS gradebooks, by LMS
+//! lms:
+//!   canvas:
+//!     columns: { SIS User ID: student_id }
+//!     assignments: { lab1: Lab 1 (12345) }
+//! ```
+//!
+//! Environment variables are the same keys, in capitals and starting with
+//! `RUBRIC_DROPBOX_`, like `RUBRIC_DROPBOX_PORT=8080`. Assignments are separated by
+//! commas, and can only be names. So are API tokens. LMS columns can't be set this way.
+
+// std uses
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use rocket::config::LoggingLevel;
+use serde::Deserialize;
+
+// internal uses
+use crate::Rubric;
+use crate::rubric::{LatePolicy, AttemptPolicy, ScorePolicy};
+use crate::rubric::attempt_policy::DEFAULT_IDENTITY_KEY;
+use crate::dropbox::Submission;
+use crate::dropbox::assignment::Assignment;
+use crate::dropbox::artifact::DEFAULT_ARTIFACT_DIR;
+use crate::dropbox::store::{CsvStore, StoreKind, SubmissionStore};
+use crate::dropbox::csv::DEFAULT_DELIMITER;
+use crate::dropbox::lms::{LmsExport, LmsFormat};
+use crate::Result;
+
+
+/// The largest upload the dropbox accepts by default, 20 MiB
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 20 * 1024 * 1024;
+/// The port the dropbox listens on if one isn't given
+pub const DEFAULT_PORT: u16 = 8080;
+/// The address the dropbox listens on by default, every interface
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0";
+/// Where submissions are written by default
+pub const DEFAULT_RESULTS_FILE: &str = "submissions.csv";
+/// Where receipts are kept by default
+pub const DEFAULT_RECEIPTS_FILE: &str = "receipts.jsonl";
+/// Environment variables that change settings start with this
+pub const ENV_PREFIX: &str = "RUBRIC_DROPBOX_";
+
+
+/// How the dropbox should treat submissions.
+///
+/// Build one with [`DropboxConfig::new`](crate::dropbox::config::DropboxConfig::new),
+/// then pass it to [`open_with`](crate::dropbox::open_with).
+///
+/// ```rust
+/// use rubric::dropbox::DropboxConfig;
+///
+/// let config = DropboxConfig::new(8080)
+///     .secret("secret key")
+///     .max_clock_skew(120);
+/// assert_eq!(config.port, 8080);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DropboxConfig {
+    /// The address to listen on
+    pub address: String,
+    /// The port to listen on
+    pub port: u16,
+    /// How submissions are stored
+    pub store: StoreKind,
+    /// The file submissions are written to
+    pub results_path: PathBuf,
+    /// What separates fields in the CSV file
+    pub csv_delimiter: char,
+    /// Where receipts are kept, so retried submissions are only recorded once
+    pub receipts_path: PathBuf,
+    /// How much the server logs
+    pub log_level: LoggingLevel,
+    /// If not empty, only submissions for these assignments are accepted.
+    /// See [`assignment`](crate::dropbox::assignment).
+    pub assignments: Vec<Assignment>,
+    /// If set, only submissions signed with this secret are accepted
+    pub secret: Option<String>,
+    /// If not empty, submitting needs one of these tokens.
+    /// See [`auth`](crate::dropbox::auth).
+    pub api_tokens: Vec<String>,
+    /// The token for reading submissions. Instructor routes are closed without one.
+    pub instructor_token: Option<String>,
+    /// If set, the dropbox decides lateness and penalties with its own clock
+    pub late_policy: Option<LatePolicy>,
+    /// Submissions whose time is further than this from when the dropbox received
+    /// them are flagged
+    pub max_clock_skew: Duration,
+    /// Where files attached to submissions are stored
+    pub artifact_dir: PathBuf,
+    /// The largest submission, including attached files, in bytes
+    pub max_upload_size: u64,
+    /// If set, submissions must be encrypted with the matching public key.
+    /// See [`encryption`](crate::dropbox::encryption).
+    pub private_key: Option<String>,
+    /// If set, the dropbox counts attempts per student and enforces the limit
+    pub attempt_policy: Option<AttemptPolicy>,
+    /// If set, a student has to wait this long between submissions
+    pub cooldown: Option<Duration>,
+    /// Which attempt counts in the gradebook. The last one, if this isn't set.
+    pub score_policy: Option<ScorePolicy>,
+    /// How gradebooks are written for each LMS, if not the usual way.
+    /// See [`lms`](crate::dropbox::lms).
+    pub lms_exports: Vec<LmsExport>,
+}
+
+impl DropboxConfig {
+    /// A config with default settings on the given port.
+    ///
+    /// The dropbox listens on every interface, and writes to `submissions.csv` and
+    /// `receipts.jsonl` in the current directory. Signatures aren't checked, lateness
+    /// is left to the grader, and clocks more than 5 minutes off are flagged.
+    /// Attached files are stored in `artifacts/`, and uploads can be up to 20 MiB.
+    pub fn new(port: u16) -> Self {
+        DropboxConfig {
+            address: String::from(DEFAULT_ADDRESS),
+            port,
+            store: StoreKind::Csv,
+            results_path: PathBuf::from(DEFAULT_RESULTS_FILE),
+            csv_delimiter: DEFAULT_DELIMITER,
+            receipts_path: PathBuf::from(DEFAULT_RECEIPTS_FILE),
+            log_level: LoggingLevel::Normal,
+            assignments: Vec::new(),
+            secret: None,
+            api_tokens: Vec::new(),
+            instructor_token: None,
+            late_policy: None,
+            max_clock_skew: Duration::minutes(5),
+            artifact_dir: PathBuf::from(DEFAULT_ARTIFACT_DIR),
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+            private_key: None,
+            attempt_policy: None,
+            cooldown: None,
+            score_policy: None,
+            lms_exports: Vec::new(),
+        }
+    }
+
+    /// Loads settings from a file, then from environment variables.
+    /// Anything that isn't set uses the default.
+    ///
+    /// ```no_run
+    /// use rubric::dropbox::{self, DropboxConfig};
+    ///
+    /// let config = DropboxConfig::load("dropbox.yml").expect("Couldn't load settings");
+    /// dropbox::open_with(config);
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        DropboxConfig::from_file(path)?.with_env()
+    }
+
+    /// Loads settings from a file. Files ending in `.toml` are read as TOML,
+    /// anything else as YAML.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .context(format!("Couldn't read dropbox settings from {}", path.display()))?;
+
+        let file: ConfigFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            _ => serde_yaml::from_str(&contents)?,
+        };
+        DropboxConfig::default().apply(file)
+    }
+
+    /// Default settings, changed by any environment variables that are set
+    pub fn from_env() -> Result<Self> {
+        DropboxConfig::default().with_env()
+    }
+
+    /// Changes these settings with any environment variables that are set
+    pub fn with_env(self) -> Result<Self> {
+        self.apply(ConfigFile::from_vars(|key| env::var(format!("{}{}", ENV_PREFIX, key)).ok())?)
+    }
+
+    // Overrides settings with everything set in the file
+    fn apply(mut self, file: ConfigFile) -> Result<Self> {
+        // The directory goes first, so specific paths can override it
+        if let Some(dir) = file.results_dir {
+            self = self.results_dir(dir);
+        }
+        if let Some(store) = file.store {
+            self = self.store(store.parse()?);
+        }
+        if let Some(address) = file.address {
+            self.address = address;
+        }
+        if let Some(port) = file.port {
+            self.port = port;
+        }
+        if let Some(path) = file.results {
+            self.results_path = path;
+        }
+        if let Some(delimiter) = file.delimiter {
+            self.csv_delimiter = parse_delimiter(&delimiter)?;
+        }
+        if let Some(path) = file.receipts {
+            self.receipts_path = path;
+        }
+        if let Some(dir) = file.artifact_dir {
+            self.artifact_dir = dir;
+        }
+        if let Some(level) = file.log_level {
+            self.log_level = level.parse()
+                .map_err(|e| anyhow!("Invalid log_level '{}', expected {}", level, e))?;
+        }
+        if let Some(size) = file.max_upload_size {
+            self.max_upload_size = size;
+        }
+        if let Some(assignments) = file.assignments {
+            self.assignments = assignments.into_iter()
+                .map(AssignmentEntry::into_assignment)
+                .collect::<Result<_>>()?;
+            self.check_assignments()?;
+        }
+        if let Some(secret) = file.secret {
+            self.secret = Some(secret);
+        }
+        if let Some(tokens) = file.api_tokens {
+            self.api_tokens = tokens;
+        }
+        if let Some(token) = file.instructor_token {
+            self.instructor_token = Some(token);
+        }
+        if let Some(path) = file.private_key_file {
+            let pem = fs::read_to_string(&path)
+                .context(format!("Couldn't read private key {}", path.display()))?;
+            self.private_key = Some(pem);
+        }
+        if let Some(seconds) = file.max_clock_skew {
+            self.max_clock_skew = Duration::seconds(seconds);
+        }
+        if let Some(seconds) = file.cooldown {
+            self.cooldown = Some(Duration::seconds(seconds));
+        }
+        if let Some(policy) = file.score_policy {
+            self.score_policy = Some(policy.parse()?);
+        }
+        for (lms, settings) in file.lms.unwrap_or_default() {
+            self = self.lms_export(settings.into_export(lms.parse()?));
+        }
+        Ok(self)
+    }
+
+    /// Listen on this address, like `127.0.0.1` to only accept local connections
+    pub fn address(mut self, address: &str) -> Self {
+        self.address = String::from(address);
+        self
+    }
+
+    /// Store submissions this way. See [`store`](crate::dropbox::store).
+    ///
+    /// If the results file is still the default one, in the current directory or
+    /// in [`results_dir`](DropboxConfig::results_dir), its extension changes to
+    /// match, so `submissions.csv` becomes `submissions.jsonl`. A file you named
+    /// yourself is left alone.
+    pub fn store(mut self, kind: StoreKind) -> Self {
+        let default_name = Path::new(DEFAULT_RESULTS_FILE).with_extension(self.store.extension());
+        if self.results_path.file_name() == Some(default_name.as_os_str()) {
+            self.results_path.set_extension(kind.extension());
+        }
+        self.store = kind;
+        self
+    }
+
+    /// Separate fields in the CSV file with this, like `';'` or `'\t'`
+    pub fn csv_delimiter(mut self, delimiter: char) -> Self {
+        self.csv_delimiter = delimiter;
+        self
+    }
+
+    /// Opens the store submissions are written to
+    pub fn open_store(&self) -> Result<Box<dyn SubmissionStore>> {
+        match self.store {
+            StoreKind::Csv => Ok(Box::new(CsvStore::open(&self.results_path, self.csv_delimiter)?)),
+            kind => kind.open(&self.results_path),
+        }
+    }
+
+    /// Write submissions to this file
+    pub fn results_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.results_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Keep receipts in this file
+    pub fn receipts_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.receipts_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Keep everything in this directory. Submissions go in `submissions.csv`
+    /// (or another extension, depending on the store), receipts in `receipts.jsonl`,
+    /// and attached files in `artifacts/`.
+    pub fn results_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        let dir = dir.as_ref();
+        self.results_path = dir.join(DEFAULT_RESULTS_FILE).with_extension(self.store.extension());
+        self.receipts_path = dir.join(DEFAULT_RECEIPTS_FILE);
+        self.artifact_dir = dir.join(DEFAULT_ARTIFACT_DIR);
+        self
+    }
+
+    /// How much the server logs
+    pub fn log_level(mut self, level: LoggingLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Only accept submissions for these assignments, with the dropbox's settings.
+    /// Any others are refused with `403 Forbidden`.
+    ///
+    /// A submission's assignment is the name of the rubric it was graded against,
+    /// or whatever was set with
+    /// [`Submission::set_assignment`](crate::dropbox::submission::Submission::set_assignment).
+    /// Each assignment's submissions are kept apart, see [`assignment`](crate::dropbox::assignment).
+    pub fn assignments<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+        self.assignments = names.iter().map(|n| Assignment::new(n.as_ref())).collect();
+        self
+    }
+
+    /// Accept submissions for this assignment, with its own settings.
+    /// An assignment with the same name is replaced.
+    pub fn assignment(mut self, assignment: Assignment) -> Self {
+        self.assignments.retain(|a| a.name != assignment.name);
+        self.assignments.push(assignment);
+        self
+    }
+
+    /// The names of the assignments the dropbox accepts
+    pub fn assignment_names(&self) -> Vec<&str> {
+        self.assignments.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    /// The assignment with this name, if the dropbox accepts it
+    pub fn find_assignment(&self, name: &str) -> Option<&Assignment> {
+        self.assignments.iter().find(|a| a.name == name)
+    }
+
+    /// Returns true if the dropbox accepts submissions for this assignment
+    pub fn accepts_assignment(&self, assignment: Option<&str>) -> bool {
+        if self.assignments.is_empty() {
+            return true;
+        }
+        assignment.map_or(false, |a| self.find_assignment(a).is_some())
+    }
+
+    /// Makes sure each assignment gets a directory of its own. Names like `lab 1` and
+    /// `lab_1` would share one, and a name like `..` wouldn't have one at all.
+    pub fn check_assignments(&self) -> Result<()> {
+        // Lowercased, since some filesystems don't care about case
+        let mut dirs: HashMap<String, &str> = HashMap::new();
+        for assignment in &self.assignments {
+            let dir = assignment.dir_name();
+            if dir.is_empty() {
+                return Err(anyhow!("Assignment '{}' doesn't have a usable directory name", assignment.name));
+            }
+            if let Some(other) = dirs.insert(dir.to_lowercase(), &assignment.name) {
+                return Err(anyhow!(
+                    "Assignments '{}' and '{}' would both be stored in '{}'",
+                    other, assignment.name, dir
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The settings for one assignment. They're the same as these, except results,
+    /// receipts, and attached files are kept in a directory named after the assignment,
+    /// and the assignment's policies are used.
+    ///
+    /// ```rust
+    /// use std::path::PathBuf;
+    /// use rubric::dropbox::{Assignment, DropboxConfig};
+    ///
+    /// let config = DropboxConfig::new(8080).results_dir("/srv/dropbox");
+    /// let lab = config.for_assignment(&Assignment::new("lab1"));
+    /// assert_eq!(lab.results_path, PathBuf::from("/srv/dropbox/lab1/submissions.csv"));
+    /// ```
+    pub fn for_assignment(&self, assignment: &Assignment) -> DropboxConfig {
+        let dir = assignment.dir_name();
+        // Puts a file in the assignment's directory, next to where it would have been
+        let move_in = |path: &Path| {
+            let parent = path.parent().unwrap_or_else(|| Path::new(""));
+            match path.file_name() {
+                Some(name) => parent.join(&dir).join(name),
+                None => parent.join(&dir),
+            }
+        };
+
+        let mut config = self.clone();
+        config.results_path = move_in(&self.results_path);
+        config.receipts_path = move_in(&self.receipts_path);
+        config.artifact_dir = self.artifact_dir.join(&dir);
+        config.assignments = vec![assignment.clone()];
+        if let Some(policy) = &assignment.late_policy {
+            config.late_policy = Some(policy.clone());
+        }
+        if let Some(policy) = &assignment.attempt_policy {
+            config.attempt_policy = Some(policy.clone());
+        }
+        if let Some(policy) = assignment.score_policy {
+            config.score_policy = Some(policy);
+        }
+        config
+    }
+
+    /// Only accept submissions signed with this secret. See
+    /// [`signature`](crate::dropbox::signature).
+    pub fn secret(mut self, secret: &str) -> Self {
+        self.secret = Some(String::from(secret));
+        self
+    }
+
+    /// Only accept submissions sent with this token, or any other given.
+    /// Call this again with a new token to rotate it, see [`auth`](crate::dropbox::auth).
+    pub fn api_token(mut self, token: &str) -> Self {
+        self.api_tokens.push(String::from(token));
+        self
+    }
+
+    /// Only accept submissions sent with one of these tokens
+    pub fn api_tokens<S: AsRef<str>>(mut self, tokens: &[S]) -> Self {
+        self.api_tokens = tokens.iter().map(|t| String::from(t.as_ref())).collect();
+        self
+    }
+
+    /// Open the instructor routes to requests with this token
+    pub fn instructor_token(mut self, token: &str) -> Self {
+        self.instructor_token = Some(String::from(token));
+        self
+    }
+
+    /// Decide lateness and penalties with the dropbox's clock, using this policy.
+    /// You probably want [`Rubric::late_policy`](crate::rubric::Rubric::late_policy).
+    pub fn late_policy(mut self, policy: LatePolicy) -> Self {
+        self.late_policy = Some(policy);
+        self
+    }
+
+    /// Count attempts per student and enforce the limit, using this policy.
+    /// You probably want [`Rubric::attempt_policy`](crate::rubric::Rubric::attempt_policy).
+    pub fn attempt_policy(mut self, policy: AttemptPolicy) -> Self {
+        self.attempt_policy = Some(policy);
+        self
+    }
+
+    /// Make students wait this many seconds between submissions. Submissions that
+    /// come too soon are refused with `429 Too Many Requests`.
+    ///
+    /// Students are identified the same way as the attempt policy, or by
+    /// their `id` if there isn't one.
+    pub fn cooldown(mut self, seconds: i64) -> Self {
+        self.cooldown = Some(Duration::seconds(seconds));
+        self
+    }
+
+    /// Take one grade from each student's attempts this way in the gradebook.
+    /// You probably want the rubric's [`score_policy`](crate::rubric::Rubric::score_policy).
+    pub fn score_policy(mut self, policy: ScorePolicy) -> Self {
+        self.score_policy = Some(policy);
+        self
+    }
+
+    /// Write gradebooks for this export's LMS this way. Another
+    /// export for the same LMS is replaced.
+    pub fn lms_export(mut self, export: LmsExport) -> Self {
+        self.lms_exports.retain(|e| e.format != export.format);
+        self.lms_exports.push(export);
+        self
+    }
+
+    /// How gradebooks are written for this LMS, the usual way unless one was given
+    pub fn export_for(&self, format: LmsFormat) -> LmsExport {
+        self.lms_exports.iter()
+            .find(|e| e.format == format)
+            .cloned()
+            .unwrap_or_else(|| LmsExport::new(format))
+    }
+
+    /// Returns true if the dropbox needs to know who made each submission
+    pub fn tracks_students(&self) -> bool {
+        self.attempt_policy.is_some() || self.cooldown.is_some()
+    }
+
+    /// The data key that identifies a student, if the dropbox tracks students
+    pub fn identity_key(&self) -> Option<&str> {
+        if !self.tracks_students() {
+            return None;
+        }
+        Some(self.attempt_policy.as_ref().map_or(DEFAULT_IDENTITY_KEY, |p| &p.identity_key))
+    }
+
+    /// The student who made a submission, if the dropbox tracks students
+    pub fn student(&self, sub: &Submission) -> Option<String> {
+        let key = self.identity_key()?;
+        sub.data.get(key).filter(|s| !s.is_empty()).cloned()
+    }
+
+    /// Flag submissions whose time is more than this many seconds
+    /// from the dropbox's clock.
+    pub fn max_clock_skew(mut self, seconds: i64) -> Self {
+        self.max_clock_skew = Duration::seconds(seconds);
+        self
+    }
+
+    /// Store attached files in this directory
+    pub fn artifact_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.artifact_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Decrypt submissions with this PEM encoded RSA private key, and
+    /// refuse any that aren't encrypted.
+    pub fn private_key(mut self, pem: &str) -> Self {
+        self.private_key = Some(String::from(pem));
+        self
+    }
+
+    /// Refuse uploads larger than this many bytes
+    pub fn max_upload_size(mut self, bytes: u64) -> Self {
+        self.max_upload_size = bytes;
+        self
+    }
+}
+
+impl Default for DropboxConfig {
+    /// Default settings on port 8080
+    fn default() -> Self {
+        DropboxConfig::new(DEFAULT_PORT)
+    }
+}
+
+
+/// The settings that can be set in a file or the environment.
+/// Everything is optional, and overrides the defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    address: Option<String>,
+    port: Option<u16>,
+    store: Option<String>,
+    results: Option<PathBuf>,
+    delimiter: Option<String>,
+    results_dir: Option<PathBuf>,
+    receipts: Option<PathBuf>,
+    artifact_dir: Option<PathBuf>,
+    log_level: Option<String>,
+    max_upload_size: Option<u64>,
+    assignments: Option<Vec<AssignmentEntry>>,
+    secret: Option<String>,
+    api_tokens: Option<Vec<String>>,
+    instructor_token: Option<String>,
+    private_key_file: Option<PathBuf>,
+    max_clock_skew: Option<i64>,
+    cooldown: Option<i64>,
+    score_policy: Option<String>,
+    lms: Option<HashMap<String, LmsFile>>,
+}
+
+/// The columns of an LMS's gradebook
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LmsFile {
+    /// The data key each column is filled from
+    #[serde(default)]
+    columns: BTreeMap<String, String>,
+    /// Column names, by assignment
+    #[serde(default)]
+    assignments: BTreeMap<String, String>,
+}
+
+impl LmsFile {
+    fn into_export(self, format: LmsFormat) -> LmsExport {
+        let export = self.columns.iter()
+            .fold(LmsExport::new(format), |e, (column, key)| e.column(column, key));
+        self.assignments.iter()
+            .fold(export, |e, (assignment, column)| e.assignment_column(assignment, column))
+    }
+}
+
+/// An assignment in the settings file. It's either just a name,
+/// or its settings.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AssignmentEntry {
+    Name(String),
+    Settings(AssignmentFile),
+}
+
+/// An assignment's settings. If there's a rubric, the assignment starts
+/// out with its name and policies, and anything else here overrides them.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssignmentFile {
+    name: Option<String>,
+    rubric: Option<PathBuf>,
+    deadline: Option<String>,
+    max_attempts: Option<u32>,
+    score_policy: Option<String>,
+    rubric_versions: Option<Vec<Version>>,
+}
+
+/// A rubric version, which can be written as a number
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Version {
+    Text(String),
+    Number(serde_json::Number),
+}
+
+impl AssignmentEntry {
+    fn into_assignment(self) -> Result<Assignment> {
+        let file = match self {
+            AssignmentEntry::Name(name) => return Ok(Assignment::new(&name)),
+            AssignmentEntry::Settings(file) => file,
+        };
+
+        let mut assignment = match (&file.rubric, &file.name) {
+            (Some(path), _) => {
+                let yaml = fs::read_to_string(path)
+                    .context(format!("Couldn't read rubric {}", path.display()))?;
+                Assignment::from_rubric(&Rubric::from_yaml(&yaml)?)
+            },
+            (None, Some(name)) => Assignment::new(name),
+            (None, None) => return Err(anyhow!("Every assignment needs a name or a rubric")),
+        };
+        if let Some(name) = file.name {
+            assignment.name = name;
+        }
+        if let Some(deadline) = file.deadline {
+            assignment = assignment.deadline(parse_deadline(&deadline)?);
+        }
+        if let Some(max) = file.max_attempts {
+            assignment = assignment.max_attempts(max);
+        }
+        if let Some(policy) = file.score_policy {
+            assignment = assignment.score_policy(policy.parse()?);
+        }
+        if let Some(versions) = file.rubric_versions {
+            assignment.rubric_versions = versions.into_iter()
+                .map(|v| match v {
+                    Version::Text(text) => text,
+                    Version::Number(n) => n.to_string(),
+                })
+                .collect();
+        }
+        Ok(assignment)
+    }
+}
+
+// A deadline from a setting, like `2020-05-24 23:59:59` in the local
+// timezone. This is the same format rubrics use.
+// The offset is the one in effect on that day, not today's, so
+// deadlines across a daylight saving change aren't an hour off.
+fn parse_deadline(value: &str) -> Result<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(value.trim(), "%F %T")
+        .map_err(|_| anyhow!("Invalid deadline '{}', expected YYYY-MM-DD HH:MM:SS", value))?;
+    Local.from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow!("Invalid deadline '{}', that time doesn't exist in the local timezone", value))
+}
+
+// A delimiter from a setting. It has to be one character, or `tab`.
+fn parse_delimiter(value: &str) -> Result<char> {
+    if value == "tab" {
+        return Ok('\t');
+    }
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '"' && c != '\n' && c != '\r' => Ok(c),
+        _ => Err(anyhow!("Invalid delimiter '{}', it should be one character", value)),
+    }
+}
+
+impl ConfigFile {
+    // Reads settings from variables, given a way to look them up by key
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<ConfigFile> {
+        // Parses a number, naming the variable if it isn't one
+        fn number<T: std::str::FromStr>(key: &str, value: Option<String>) -> Result<Option<T>> {
+            match value {
+                Some(v) => v.trim().parse().map(Some)
+                    .map_err(|_| anyhow!("{}{} should be a number, not '{}'", ENV_PREFIX, key, v)),
+                None => Ok(None),
+            }
+        }
+
+        Ok(ConfigFile {
+            address: var("ADDRESS"),
+            port: number("PORT", var("PORT"))?,
+            store: var("STORE"),
+            results: var("RESULTS").map(PathBuf::from),
+            delimiter: var("DELIMITER"),
+            results_dir: var("RESULTS_DIR").map(PathBuf::from),
+            receipts: var("RECEIPTS").map(PathBuf::from),
+            artifact_dir: var("ARTIFACT_DIR").map(PathBuf::from),
+            log_level: var("LOG_LEVEL"),
+            max_upload_size: number("MAX_UPLOAD_SIZE", var("MAX_UPLOAD_SIZE"))?,
+            assignments: var("ASSIGNMENTS").map(|a| {
+                a.split(',')
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| AssignmentEntry::Name(s.to_string()))
+                    .collect()
+            }),
+            secret: var("SECRET"),
+            api_tokens: var("API_TOKENS").map(|t| {
+                t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+            }),
+            instructor_token: var("INSTRUCTOR_TOKEN"),
+            private_key_file: var("PRIVATE_KEY_FILE").map(PathBuf::from),
+            max_clock_skew: number("MAX_CLOCK_SKEW", var("MAX_CLOCK_SKEW"))?,
+            cooldown: number("COOLDOWN", var("COOLDOWN"))?,
+            score_policy: var("SCORE_POLICY"),
+            lms: None,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_defaults() {
+        let config = DropboxConfig::default();
+        assert_eq!(config.address, "0.0.0.0");
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.results_path, PathBuf::from("submissions.csv"));
+        assert_eq!(config.receipts_path, PathBuf::from("receipts.jsonl"));
+        assert_eq!(config.log_level, LoggingLevel::Normal);
+        assert!(config.accepts_assignment(None));
+    }
+
+    #[test]
+    fn test_results_dir() {
+        let config = DropboxConfig::new(8080).results_dir("/srv/dropbox");
+        assert_eq!(config.results_path, PathBuf::from("/srv/dropbox/submissions.csv"));
+        assert_eq!(config.receipts_path, PathBuf::from("/srv/dropbox/receipts.jsonl"));
+        assert_eq!(config.artifact_dir, PathBuf::from("/srv/dropbox/artifacts"));
+    }
+
+    #[test]
+    fn test_store_changes_extension() {
+        let config = DropboxConfig::new(8080).store(StoreKind::JsonLines);
+        assert_eq!(config.results_path, PathBuf::from("submissions.jsonl"));
+
+        let config = DropboxConfig::new(8080).store(StoreKind::Sqlite).results_dir("/srv");
+        assert_eq!(config.results_path, PathBuf::from("/srv/submissions.sqlite"));
+
+        let config = DropboxConfig::new(8080).results_dir("/srv").store(StoreKind::Sqlite);
+        assert_eq!(config.results_path, PathBuf::from("/srv/submissions.sqlite"));
+    }
+
+    #[test]
+    fn test_store_keeps_named_file() {
+        let config = DropboxConfig::new(8080)
+            .results_path("/srv/lab1.csv")
+            .store(StoreKind::JsonLines);
+        assert_eq!(config.results_path, PathBuf::from("/srv/lab1.csv"));
+
+        let config = DropboxConfig::new(8080)
+            .results_path("/srv/grades.db")
+            .store(StoreKind::Sqlite);
+        assert_eq!(config.results_path, PathBuf::from("/srv/grades.db"));
+    }
+
+    #[test]
+    fn test_accepts_assignment() {
+        let config = DropboxConfig::new(8080).assignments(&["lab1", "lab2"]);
+        assert!(config.accepts_assignment(Some("lab1")));
+        assert!(!config.accepts_assignment(Some("lab3")));
+        assert!(!config.accepts_assignment(None));
+
+        // Settings replace an assignment with the same name
+        let config = config.assignment(Assignment::new("lab2").max_attempts(2));
+        assert_eq!(config.assignment_names(), vec!["lab1", "lab2"]);
+        assert!(config.find_assignment("lab2").unwrap().attempt_policy.is_some());
+    }
+
+    #[test]
+    fn test_check_assignments() {
+        assert!(DropboxConfig::new(8080).assignments(&["lab1", "lab2"]).check_assignments().is_ok());
+        assert!(DropboxConfig::new(8080).assignments(&["lab 1", "lab_1"]).check_assignments().is_err());
+        assert!(DropboxConfig::new(8080).assignments(&["Lab1", "lab1"]).check_assignments().is_err());
+        assert!(DropboxConfig::new(8080).assignments(&[".."]).check_assignments().is_err());
+
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.path().join("dropbox.yml");
+        fs::write(&path, "assignments: [lab 1, lab_1]").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_for_assignment() {
+        let config = DropboxConfig::new(8080)
+            .results_dir("/srv/dropbox")
+            .cooldown(60)
+            .attempt_policy(AttemptPolicy::default());
+        let lab = Assignment::new("lab 1").max_attempts(3).score_policy(ScorePolicy::Best);
+        let lab_config = config.for_assignment(&lab);
+
+        assert_eq!(lab_config.results_path, PathBuf::from("/srv/dropbox/lab_1/submissions.csv"));
+        assert_eq!(lab_config.receipts_path, PathBuf::from("/srv/dropbox/lab_1/receipts.jsonl"));
+        assert_eq!(lab_config.artifact_dir, PathBuf::from("/srv/dropbox/artifacts/lab_1"));
+        assert_eq!(lab_config.attempt_policy.unwrap().max_attempts, Some(3));
+        assert_eq!(lab_config.score_policy, Some(ScorePolicy::Best));
+        // Everything else is kept
+        assert_eq!(lab_config.cooldown, config.cooldown);
+        assert!(lab_config.late_policy.is_none());
+
+        // Results in the current directory
+        let lab_config = DropboxConfig::new(8080).for_assignment(&lab);
+        assert_eq!(lab_config.results_path, PathBuf::from("lab_1/submissions.csv"));
+    }
+
+    #[test]
+    fn test_from_yaml_file() {
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.path().join("dropbox.yml");
+        fs::write(&path, "
+address: 127.0.0.1
+port: 9000
+results_dir: /srv/dropbox
+receipts: /tmp/receipts.jsonl
+log_level: critical
+store: jsonl
+assignments: [lab1]
+cooldown: 60
+").unwrap();
+
+        let config = DropboxConfig::from_file(&path).unwrap();
+        assert_eq!(config.address, "127.0.0.1");
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.store, StoreKind::JsonLines);
+        assert_eq!(config.results_path, PathBuf::from("/srv/dropbox/submissions.jsonl"));
+        assert_eq!(config.receipts_path, PathBuf::from("/tmp/receipts.jsonl"));
+        assert_eq!(config.log_level, LoggingLevel::Critical);
+        assert_eq!(config.assignment_names(), vec!["lab1"]);
+        assert_eq!(config.cooldown, Some(Duration::seconds(60)));
+    }
+
+    #[test]
+    fn test_assignment_settings() {
+        let dir = TempDir::new("config").unwrap();
+        let rubric = dir.path().join("lab3.yml");
+        fs::write(&rubric, "
+name: lab3
+version: 4
+max_attempts: 5
+criteria: {}
+").unwrap();
+        let path = dir.path().join("dropbox.yml");
+        fs::write(&path, format!("
+assignments:
+  - lab1
+  - name: lab2
+    deadline: 2020-05-24 23:59:59
+    max_attempts: 3
+    score_policy: average
+    rubric_versions: [2, \"3b\"]
+  - rubric: {}
+", rubric.display())).unwrap();
+
+        let config = DropboxConfig::from_file(&path).unwrap();
+        assert_eq!(config.assignment_names(), vec!["lab1", "lab2", "lab3"]);
+        assert_eq!(config.assignments[0], Assignment::new("lab1"));
+
+        let lab2 = &config.assignments[1];
+        assert!(lab2.late_policy.as_ref().unwrap().deadline.is_some());
+        assert_eq!(lab2.attempt_policy.as_ref().unwrap().max_attempts, Some(3));
+        assert_eq!(lab2.rubric_versions, vec!["2", "3b"]);
+        assert_eq!(lab2.score_policy, Some(ScorePolicy::Average));
+
+        let lab3 = &config.assignments[2];
+        assert_eq!(lab3.attempt_policy.as_ref().unwrap().max_attempts, Some(5));
+        assert_eq!(lab3.rubric_versions, vec!["4"]);
+
+        fs::write(&path, "assignments: [{max_attempts: 3}]").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+        fs::write(&path, "assignments: [{name: lab1, deadline: tomorrow}]").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_parse_deadline() {
+        // Winter and summer, so one of them is across a DST change from today
+        assert_eq!(parse_deadline("2020-01-15 23:59:59").unwrap(), Local.ymd(2020, 1, 15).and_hms(23, 59, 59));
+        assert_eq!(parse_deadline("2020-07-15 23:59:59").unwrap(), Local.ymd(2020, 7, 15).and_hms(23, 59, 59));
+        assert!(parse_deadline("2020-07-15").is_err());
+    }
+
+    #[test]
+    fn test_lms_settings() {
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.path().join("dropbox.yml");
+        fs::write(&path, "
+lms:
+  canvas:
+    columns:
+      SIS User ID: ''
+      SIS Login ID: email
+    assignments:
+      lab1: Lab 1 (12345)
+  Moodle:
+    columns: { Email address: school_email }
+").unwrap();
+
+        let config = DropboxConfig::from_file(&path).unwrap();
+        let canvas = config.export_for(LmsFormat::Canvas);
+        assert_eq!(canvas, LmsExport::new(LmsFormat::Canvas)
+            .column("SIS User ID", "")
+            .column("SIS Login ID", "email")
+            .assignment_column("lab1", "Lab 1 (12345)"));
+        let moodle = config.export_for(LmsFormat::Moodle);
+        assert!(moodle.columns.contains(&(String::from("Email address"), String::from("school_email"))));
+        // Not set, so the usual way
+        assert_eq!(config.export_for(LmsFormat::Blackboard), LmsExport::new(LmsFormat::Blackboard));
+
+        fs::write(&path, "lms: { sakai: {} }").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_from_toml_file() {
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.path().join("dropbox.toml");
+        fs::write(&path, "
+port = 9001
+results = \"lab.csv\"
+max_upload_size = 1024
+secret = \"shh\"
+api_tokens = [\"token\"]
+instructor_token = \"instructor\"
+").unwrap();
+
+        let config = DropboxConfig::from_file(&path).unwrap();
+        assert_eq!(config.port, 9001);
+        assert_eq!(config.results_path, PathBuf::from("lab.csv"));
+        assert_eq!(config.max_upload_size, 1024);
+        assert_eq!(config.secret.as_deref(), Some("shh"));
+        assert_eq!(config.api_tokens, vec!["token"]);
+        assert_eq!(config.instructor_token.as_deref(), Some("instructor"));
+    }
+
+    #[test]
+    fn test_bad_file() {
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.path().join("dropbox.yml");
+
+        fs::write(&path, "log_level: loud").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+
+        fs::write(&path, "delimiter: ',,'").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+
+        fs::write(&path, "score_policy: worst").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+
+        fs::write(&path, "prot: 8080").unwrap();
+        assert!(DropboxConfig::from_file(&path).is_err());
+
+        assert!(DropboxConfig::from_file(dir.path().join("missing.yml")).is_err());
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let mut vars = HashMap::new();
+        vars.insert("PORT", "9002");
+        vars.insert("ASSIGNMENTS", "lab1, lab2,");
+        vars.insert("LOG_LEVEL", "off");
+        vars.insert("DELIMITER", "tab");
+        vars.insert("API_TOKENS", "old,new");
+        vars.insert("SCORE_POLICY", "best");
+
+        let file = ConfigFile::from_vars(|k| vars.get(k).map(|v| v.to_string())).unwrap();
+        let config = DropboxConfig::default().apply(file).unwrap();
+        assert_eq!(config.port, 9002);
+        assert_eq!(config.assignment_names(), vec!["lab1", "lab2"]);
+        assert_eq!(config.log_level, LoggingLevel::Off);
+        assert_eq!(config.csv_delimiter, '\t');
+        assert_eq!(config.api_tokens, vec!["old", "new"]);
+        assert_eq!(config.score_policy, Some(ScorePolicy::Best));
+        assert!(config.instructor_token.is_none());
+        // Anything not set is left alone
+        assert_eq!(config.address, "0.0.0.0");
+
+        vars.insert("PORT", "eighty");
+        assert!(ConfigFile::from_vars(|k| vars.get(k).map(|v| v.to_string())).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! The structured result of grading one criterion
+
+// std uses
+use std::time::Duration;
+
+// external uses
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use crate::rubric::Criterion;
+
+
+/// What happened when a criterion was graded
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStatus {
+    /// The criterion passed, or a deduction wasn't triggered
+    Passed,
+    /// The criterion failed
+    Failed,
+    /// A deduction was triggered
+    Deducted,
+}
+
+/// The result of grading a single criterion.
+///
+/// A [`Submission`](crate::dropbox::submission::Submission) keeps one of these for
+/// every criterion it was graded against. Unlike the `passed` and `failed` lists, these
+/// are meant to be analysed by a program rather than read by a person.
+///
+/// A criterion with children has a result for each of them in `children`, nested
+/// the same way as in the rubric.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CriterionResult {
+    /// The criterion's func, which is unique within a rubric
+    pub func: String,
+    /// The criterion's name
+    pub name: String,
+    /// The most the criterion could earn. For a deduction,
+    /// this is the amount it subtracts.
+    pub worth: isize,
+    /// The points actually earned. Negative for a triggered deduction.
+    pub earned: isize,
+    /// If the criterion passed, failed, or deducted points
+    pub status: ResultStatus,
+    /// The success or failure message shown to the student
+    pub message: String,
+    /// How long the criterion's test took to run
+    pub duration: Duration,
+    /// If the criterion is hidden from the student
+    #[serde(default)]
+    pub hidden: bool,
+    /// The results of the criterion's children, if it has any. Their
+    /// durations are zero, the parent's duration covers them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<CriterionResult>,
+}
+
+impl CriterionResult {
+    /// Builds a result from a criterion that has already been tested.
+    ///
+    /// A criterion that hasn't been tested is counted as failed.
+    pub fn from_criterion(crit: &Criterion, duration: Duration) -> Self {
+        let triggered = crit.status == Some(true);
+        let status = match (crit.deduction, triggered) {
+            (false, true) => ResultStatus::Passed,
+            (false, false) => ResultStatus::Failed,
+            (true, true) => ResultStatus::Deducted,
+            (true, false) => ResultStatus::Passed,
+        };
+
+        CriterionResult {
+            func: crit.func.clone(),
+            name: crit.name.clone(),
+            worth: crit.worth,
+            earned: crit.earned(),
+            status,
+            message: crit.status_message(),
+            duration,
+            hidden: crit.hide,
+            children: crit.children.iter()
+                .map(|c| CriterionResult::from_criterion(c, Duration::default()))
+                .collect(),
+        }
+    }
+
+    /// Finds this result or one of its children's, by func
+    pub fn find(&self, func: &str) -> Option<&CriterionResult> {
+        if self.func == func {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(func))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestData;
+
+    #[test]
+    fn test_from_criterion() {
+        let mut crit = Criterion::new("Git init")
+            .worth(25)
+            .test(Box::new(|_: &TestData| true))
+            .build();
+        crit.test();
+
+        let result = CriterionResult::from_criterion(&crit, Duration::from_millis(3));
+        assert_eq!(result.func, "git_init");
+        assert_eq!(result.earned, 25);
+        assert_eq!(result.status, ResultStatus::Passed);
+        assert_eq!(result.message, "passed");
+
+        let mut deduction = Criterion::new("Uses unsafe")
+            .worth(5)
+            .deduction(true)
+            .test(Box::new(|_: &TestData| true))
+            .build();
+        deduction.test();
+
+        let result = CriterionResult::from_criterion(&deduction, Duration::from_millis(3));
+        assert_eq!(result.earned, -5);
+        assert_eq!(result.status, ResultStatus::Deducted);
+    }
+
+    #[test]
+    fn test_children() {
+        let mut crit = Criterion::new("Builds")
+            .worth(20)
+            .child(Criterion::new("Compiles").worth(10).test(Box::new(|_: &TestData| true)).build())
+            .child(Criterion::new("Links").worth(10).build())
+            .build();
+        crit.test();
+
+        let result = CriterionResult::from_criterion(&crit, Duration::from_millis(3));
+        assert_eq!(result.children.len(), 2);
+        assert_eq!(result.find("compiles").unwrap().status, ResultStatus::Passed);
+        assert_eq!(result.find("links").unwrap().earned, 0);
+        assert_eq!(result.find("links").unwrap().duration, Duration::default());
+        assert!(result.find("runs").is_none());
+
+        // Leaves don't write an empty list
+        let json = serde_json::to_string(&result.children[0]).unwrap();
+        assert!(!json.contains("children"));
+        let back: CriterionResult = serde_json::from_str(&serde_json::to_string(&result).unwrap()).unwrap();
+        assert_eq!(back, result);
+    }
+
+    #[test]
+    fn test_status_serializes_lowercase() {
+        let json = serde_json::to_string(&ResultStatus::Deducted).unwrap();
+        assert_eq!(json, r#""deducted""#);
+    }
+}
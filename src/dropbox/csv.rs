@@ -0,0 +1,254 @@
+//! Reading and writing CSV, following [RFC 4180](https://tools.ietf.org/html/rfc4180)
+//!
+//! Fields with the delimiter, a quote, or a line break in them are wrapped in quotes,
+//! and quotes inside are doubled. So a student named `Jo "JJ" Smith, Jr.` is written as
+//! `"Jo ""JJ"" Smith, Jr."`, and read back the way it was.
+//!
+//! ```rust
+//! use rubric::dropbox::csv;
+//!
+//! let row = csv::write_row(&["Smith, John", "42"], ',');
+//! assert_eq!(row, r#""Smith, John",42"#);
+//!
+//! let records = csv::read_str(&format!("name,grade\n{}", row), ',').unwrap();
+//! assert_eq!(records[0].get("name"), Some("Smith, John"));
+//! assert_eq!(records[0].parse::<isize>("grade").unwrap(), 42);
+//! ```
+
+// std uses
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// external uses
+use anyhow::{anyhow, bail, Context};
+
+// internal uses
+use crate::Result;
+
+
+/// The delimiter used unless another is given
+pub const DEFAULT_DELIMITER: char = ',';
+
+
+/// Escapes one field. It's quoted if it has the delimiter, a quote,
+/// or a line break in it, and any quotes inside are doubled.
+///
+/// ```rust
+/// use rubric::dropbox::csv::escape_field;
+///
+/// assert_eq!(escape_field("plain", ','), "plain");
+/// assert_eq!(escape_field("a,b", ','), r#""a,b""#);
+/// assert_eq!(escape_field(r#"say "hi""#, ','), r#""say ""hi""""#);
+/// assert_eq!(escape_field("a,b", '\t'), "a,b");
+/// ```
+pub fn escape_field(field: &str, delimiter: char) -> String {
+    let needs_quotes = field.chars().any(|c| c == delimiter || c == '"' || c == '\n' || c == '\r');
+    if needs_quotes {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+/// Escapes each field and joins them into a row. There's no line break at the end.
+pub fn write_row<S: AsRef<str>>(fields: &[S], delimiter: char) -> String {
+    fields.iter()
+        .map(|f| escape_field(f.as_ref(), delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
+
+/// Splits CSV text into rows of fields.
+///
+/// Quoted fields can have the delimiter, quotes, and line breaks in them.
+/// Lines can end in `\n` or `\r\n`, and blank lines are skipped. A quote that's
+/// never closed is an error.
+pub fn parse(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    // If the current field was quoted, so an empty one still counts
+    let mut was_quoted = false;
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                },
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() && !was_quoted => {
+                quoted = true;
+                was_quoted = true;
+            },
+            c if c == delimiter => {
+                row.push(std::mem::take(&mut field));
+                was_quoted = false;
+            },
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                line += 1;
+                if !row.is_empty() || !field.is_empty() || was_quoted {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                was_quoted = false;
+            },
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("Unclosed quote in CSV, starting before line {}", line);
+    }
+    if !row.is_empty() || !field.is_empty() || was_quoted {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+
+/// One row of a CSV file, with its values named by the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvRecord {
+    pub header: Vec<String>,
+    pub values: Vec<String>,
+}
+
+impl CsvRecord {
+    /// The value in a column. `None` if there's no such column, or this row is too short for it.
+    pub fn get(&self, column: &str) -> Option<&str> {
+        let index = self.header.iter().position(|h| h == column)?;
+        self.values.get(index).map(|v| v.as_str())
+    }
+
+    /// Parses the value in a column into any type that implements `FromStr`
+    ///
+    /// ```rust
+    /// use rubric::dropbox::csv;
+    ///
+    /// let records = csv::read_str("grade,late\n50,true", ',').unwrap();
+    /// assert_eq!(records[0].parse::<isize>("grade").unwrap(), 50);
+    /// assert_eq!(records[0].parse::<bool>("late").unwrap(), true);
+    /// assert!(records[0].parse::<isize>("late").is_err());
+    /// ```
+    pub fn parse<T: FromStr>(&self, column: &str) -> Result<T> {
+        let value = self.get(column).ok_or_else(|| anyhow!("No column named {}", column))?;
+        value.parse().map_err(|_| anyhow!("Couldn't parse '{}' in column {}", value, column))
+    }
+
+    /// The record as a map from column to value
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.header.iter().cloned().zip(self.values.iter().cloned()).collect()
+    }
+}
+
+
+/// Reads CSV text into records. The first row is the header.
+///
+/// Rows shorter than the header are missing the last columns, and anything
+/// past the end of the header is ignored.
+pub fn read_str(text: &str, delimiter: char) -> Result<Vec<CsvRecord>> {
+    let mut rows = parse(text, delimiter)?.into_iter();
+    let header = match rows.next() {
+        Some(h) => h,
+        None => return Ok(Vec::new()),
+    };
+    Ok(rows.map(|mut values| {
+        values.truncate(header.len());
+        CsvRecord { header: header.clone(), values }
+    }).collect())
+}
+
+/// Reads a CSV file into records. See [`read_str`](crate::dropbox::csv::read_str).
+pub fn read_file<P: AsRef<Path>>(path: P, delimiter: char) -> Result<Vec<CsvRecord>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).context(format!("Couldn't read {}", path.display()))?;
+    read_str(&contents, delimiter)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let fields = vec![
+            "plain",
+            "with, comma",
+            r#"with "quotes""#,
+            "multiple\nlines",
+            "windows\r\nline",
+            "",
+            "trailing space ",
+        ];
+        let row = write_row(&fields, ',');
+        let parsed = parse(&row, ',').unwrap();
+        assert_eq!(parsed, vec![fields.iter().map(|f| f.to_string()).collect::<Vec<_>>()]);
+    }
+
+    #[test]
+    fn test_other_delimiter() {
+        let row = write_row(&["a,b", "c;d"], ';');
+        assert_eq!(row, r#"a,b;"c;d""#);
+        assert_eq!(parse(&row, ';').unwrap(), vec![vec!["a,b", "c;d"]]);
+    }
+
+    #[test]
+    fn test_parse_rows() {
+        let text = "a,b\r\n1,\"2\n3\"\n\n\"\",x\n";
+        let rows = parse(text, ',').unwrap();
+        assert_eq!(rows, vec![
+            vec!["a", "b"],
+            vec!["1", "2\n3"],
+            vec!["", "x"],
+        ]);
+
+        // A row that's only an empty quoted field
+        assert_eq!(parse("\"\"", ',').unwrap(), vec![vec![""]]);
+        // Trailing delimiter means an empty last field
+        assert_eq!(parse("a,", ',').unwrap(), vec![vec!["a", ""]]);
+    }
+
+    #[test]
+    fn test_unclosed_quote() {
+        assert!(parse("a,\"b\nc", ',').is_err());
+    }
+
+    #[test]
+    fn test_read_records() {
+        let records = read_str("name,grade,late\n\"Smith, J\",50,false\nshort\n", ',').unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("name"), Some("Smith, J"));
+        assert_eq!(records[0].parse::<isize>("grade").unwrap(), 50);
+        assert!(!records[0].parse::<bool>("late").unwrap());
+        assert_eq!(records[0].to_map()["grade"], "50");
+
+        assert_eq!(records[1].get("name"), Some("short"));
+        assert_eq!(records[1].get("grade"), None);
+        assert!(records[1].parse::<isize>("grade").is_err());
+        assert!(records[0].parse::<isize>("nothing").is_err());
+
+        assert!(read_str("", ',').unwrap().is_empty());
+    }
+}
@@ -0,0 +1,672 @@
+//! A page to watch submissions come in
+//!
+//! `GET /dashboard` shows what the dropbox has collected so far, and reloads
+//! itself every 30 seconds.
+//!
+//! - The most recent submissions
+//! - Each student's latest grade, for every assignment
+//! - How often each criterion passed
+//! - How many submissions were late
+//!
+//! It needs the instructor token like the [`instructor`](crate::dropbox::instructor) routes.
+//! Browsers can't send it in a header, so the dashboard asks for it and keeps it in
+//! a cookie. The download links on the page use the same cookie.
+//!
+//! A CSV file doesn't keep each criterion's result, so when the dropbox stores
+//! submissions in one, criteria are counted from the passed and failed lists,
+//! by name. Deductions only show up there when they were triggered.
+//!
+//! ```rust
+//! use rubric::{data, Submission};
+//! use rubric::dropbox::dashboard::Dashboard;
+//!
+//! let mut sub = Submission::from_data(data! { "id" => "s1" });
+//! sub.late = true;
+//!
+//! let dashboard = Dashboard::new(vec![sub], "id");
+//! assert_eq!(dashboard.late_counts()[0].late, 1);
+//! assert!(dashboard.to_html().contains("s1"));
+//! ```
+
+// std uses
+use std::collections::HashMap;
+
+// external uses
+use rocket::State;
+use rocket::http::{Cookie, Cookies, SameSite, Status};
+use rocket::request::Form;
+use rocket::response::{status, Redirect};
+use rocket::response::content::Html;
+
+// internal uses
+use crate::rubric::{LatePenalty, ScorePolicy};
+use crate::rubric::attempt_policy::DEFAULT_IDENTITY_KEY;
+use crate::dropbox::{AssignmentBoxes, DropboxConfig, Gradebook, ResultStatus, Submission};
+use crate::dropbox::auth::{self, Access, InstructorToken};
+use crate::dropbox::gradebook::UNNAMED_ASSIGNMENT;
+use crate::dropbox::lms::LmsFormat;
+use crate::dropbox::query::SubmissionQuery;
+
+
+/// How often the page reloads, in seconds
+pub const REFRESH_SECONDS: u32 = 30;
+/// How many of the newest submissions are shown
+pub const RECENT_SUBMISSIONS: usize = 20;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+table { border-collapse: collapse; margin-bottom: 2em; } \
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; } \
+th { background: #eee; } \
+.late { color: #b00; }";
+
+
+/// How often one of an assignment's criteria passed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriterionStats {
+    /// The assignment
+    pub assignment: String,
+    /// The criterion's func
+    pub func: String,
+    /// The criterion's name
+    pub name: String,
+    /// How many submissions passed it
+    pub passed: usize,
+    /// How many submissions were graded against it
+    pub total: usize,
+}
+
+impl CriterionStats {
+    /// The percent of submissions that passed, from 0 to 100
+    pub fn pass_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.passed as f64 / self.total as f64 * 100.0
+    }
+}
+
+/// How many of an assignment's submissions were late
+#[derive(Debug, Clone, PartialEq)]
+pub struct LateCount {
+    /// The assignment
+    pub assignment: String,
+    /// Every submission for it
+    pub submissions: usize,
+    /// The late ones
+    pub late: usize,
+}
+
+
+/// What the dashboard shows, from the stored submissions
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    submissions: Vec<Submission>,
+    identity_key: String,
+    assignment_keys: HashMap<String, String>,
+    refresh: u32,
+    recent: usize,
+}
+
+impl Dashboard {
+    /// A dashboard for these submissions, telling students apart by the identity key
+    pub fn new(submissions: Vec<Submission>, identity_key: &str) -> Self {
+        Dashboard {
+            submissions,
+            identity_key: String::from(identity_key),
+            assignment_keys: HashMap::new(),
+            refresh: REFRESH_SECONDS,
+            recent: RECENT_SUBMISSIONS,
+        }
+    }
+
+    /// Tells students apart by another key in this assignment's submissions
+    pub fn assignment_key(mut self, assignment: &str, identity_key: &str) -> Self {
+        self.assignment_keys.insert(String::from(assignment), String::from(identity_key));
+        self
+    }
+
+    /// Reload the page this often, in seconds. 0 doesn't reload it.
+    pub fn refresh(mut self, seconds: u32) -> Self {
+        self.refresh = seconds;
+        self
+    }
+
+    /// Show this many of the newest submissions
+    pub fn recent(mut self, count: usize) -> Self {
+        self.recent = count;
+        self
+    }
+
+    /// The newest submissions, newest first
+    pub fn recent_submissions(&self) -> Vec<&Submission> {
+        let mut subs: Vec<&Submission> = self.submissions.iter().collect();
+        subs.sort_by_key(|s| std::cmp::Reverse(s.received_at.unwrap_or(s.time)));
+        subs.truncate(self.recent);
+        subs
+    }
+
+    /// Every student's latest grade for each assignment, with students
+    /// found by each assignment's key
+    pub fn gradebook(&self) -> Gradebook {
+        let mut names: Vec<&str> = Vec::new();
+        for sub in &self.submissions {
+            if !names.contains(&assignment_name(sub)) {
+                names.push(assignment_name(sub));
+            }
+        }
+
+        names.into_iter().fold(Gradebook::new(&self.identity_key), |book, name| {
+            let subs: Vec<Submission> = self.submissions.iter()
+                .filter(|s| assignment_name(s) == name)
+                .cloned()
+                .collect();
+            book.assignment_by(name, &subs, ScorePolicy::Latest, self.key_for(name))
+        })
+    }
+
+    // The identity key for an assignment's submissions
+    fn key_for(&self, assignment: &str) -> &str {
+        self.assignment_keys.get(assignment).unwrap_or(&self.identity_key)
+    }
+
+    /// How often each criterion passed, by assignment, in the order they were graded
+    pub fn criteria(&self) -> Vec<CriterionStats> {
+        let mut stats: Vec<CriterionStats> = Vec::new();
+        for sub in &self.submissions {
+            let assignment = assignment_name(sub);
+            for (func, name, passed) in graded(sub) {
+                let index = match stats.iter().position(|s| s.assignment == assignment && s.func == func) {
+                    Some(i) => i,
+                    None => {
+                        stats.push(CriterionStats {
+                            assignment: String::from(assignment),
+                            func,
+                            name,
+                            passed: 0,
+                            total: 0,
+                        });
+                        stats.len() - 1
+                    }
+                };
+                stats[index].total += 1;
+                if passed {
+                    stats[index].passed += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    /// How many submissions were late, by assignment
+    pub fn late_counts(&self) -> Vec<LateCount> {
+        let mut counts: Vec<LateCount> = Vec::new();
+        for sub in &self.submissions {
+            let assignment = assignment_name(sub);
+            let index = match counts.iter().position(|c| c.assignment == assignment) {
+                Some(i) => i,
+                None => {
+                    counts.push(LateCount { assignment: String::from(assignment), submissions: 0, late: 0 });
+                    counts.len() - 1
+                }
+            };
+            counts[index].submissions += 1;
+            if sub.late {
+                counts[index].late += 1;
+            }
+        }
+        counts
+    }
+
+    /// The page
+    pub fn to_html(&self) -> String {
+        let book = self.gradebook();
+        let late: usize = self.submissions.iter().filter(|s| s.late).count();
+        let mut body = format!(
+            "<h1>Dropbox</h1>\n<p>Submissions: {}. Students: {}. Late: {}.</p>\n",
+            self.submissions.len(), book.rows.len(), late
+        );
+
+        let mut links = vec![
+            link("/submissions.csv", "Submissions (CSV)"),
+            link("/submissions.jsonl", "Submissions (JSON Lines)"),
+            link("/gradebook.csv", "Gradebook (CSV)"),
+        ];
+        for lms in &[LmsFormat::Canvas, LmsFormat::Moodle, LmsFormat::Blackboard] {
+            links.push(link(&format!("/gradebook/{}", lms), &format!("Gradebook ({})", lms)));
+        }
+        body += &format!("<p>Download: {}</p>\n", links.join(" | "));
+
+        body += "<h2>Recent submissions</h2>\n";
+        let rows = self.recent_submissions().into_iter()
+            .map(|s| vec![
+                s.received_at.unwrap_or(s.time).format("%F %T").to_string(),
+                String::from(assignment_name(s)),
+                self.student(s),
+                s.grade.to_string(),
+                if s.late { String::from("late") } else { String::new() },
+                s.flags.join(", "),
+            ])
+            .collect();
+        body += &table(&["Received", "Assignment", "Student", "Grade", "Late", "Flags"], rows);
+
+        body += "<h2>Latest grades</h2>\n";
+        let mut header = vec![book.identity_key.as_str()];
+        header.extend(book.assignments.iter().map(|a| a.as_str()));
+        let rows = book.rows.iter()
+            .map(|row| {
+                let mut cells = vec![row.student.clone()];
+                for a in &book.assignments {
+                    cells.push(match row.scores.get(a) {
+                        Some(score) if score.late => format!("{} (late)", score.grade),
+                        Some(score) => score.grade.to_string(),
+                        None => String::new(),
+                    });
+                }
+                cells
+            })
+            .collect();
+        body += &table(&header, rows);
+
+        body += "<h2>Criteria</h2>\n";
+        let rows = self.criteria().into_iter()
+            .map(|c| vec![
+                c.assignment.clone(),
+                c.name.clone(),
+                format!("{} of {}", c.passed, c.total),
+                format!("{:.0}%", c.pass_rate()),
+            ])
+            .collect();
+        body += &table(&["Assignment", "Criterion", "Passed", "Pass rate"], rows);
+
+        body += "<h2>Late submissions</h2>\n";
+        let rows = self.late_counts().into_iter()
+            .map(|c| vec![c.assignment.clone(), c.submissions.to_string(), c.late.to_string()])
+            .collect();
+        body += &table(&["Assignment", "Submissions", "Late"], rows);
+
+        body += "<form method=\"post\" action=\"/dashboard/logout\"><button>Log out</button></form>\n";
+        page(&body, self.refresh)
+    }
+
+    // Who made a submission, by the identity key or the team's members
+    fn student(&self, sub: &Submission) -> String {
+        match sub.data.get(self.key_for(assignment_name(sub))) {
+            Some(id) => id.clone(),
+            None => sub.members.join(", "),
+        }
+    }
+}
+
+/// The page that asks for the instructor token, with why it's asking again if it is
+pub fn login_page(message: Option<&str>) -> String {
+    let mut body = String::from("<h1>Dropbox</h1>\n");
+    if let Some(message) = message {
+        body += &format!("<p class=\"late\">{}</p>\n", escape(message));
+    }
+    body += "<form method=\"post\" action=\"/dashboard\">\n\
+        <label>Instructor token <input type=\"password\" name=\"token\" autofocus></label>\n\
+        <button>Log in</button>\n\
+        </form>\n";
+    page(&body, 0)
+}
+
+/// Escapes text to go in HTML
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+
+// The assignment a submission counts toward, named like the gradebook's columns
+fn assignment_name(sub: &Submission) -> &str {
+    sub.assignment.as_deref().unwrap_or(UNNAMED_ASSIGNMENT)
+}
+
+// Each criterion a submission was graded on, as its func, name, and whether it passed.
+//
+// Submissions read back from a CSV file don't have their results, only their
+// passed and failed lists, like `Compiles (+10)`. Those criteria go by their
+// name, and late penalties and deductions that weren't triggered aren't counted.
+fn graded(sub: &Submission) -> Vec<(String, String, bool)> {
+    if !sub.results.is_empty() {
+        return sub.results.iter()
+            .map(|r| (r.func.clone(), r.name.clone(), r.status == ResultStatus::Passed))
+            .collect();
+    }
+    let passed = sub.passed.iter().map(|p| (p, true));
+    let failed = sub.failed.iter()
+        .filter(|f| !(sub.late && LatePenalty::from_message(f).is_some()))
+        .map(|f| (f, false));
+    passed.chain(failed)
+        .map(|(entry, passed)| {
+            // Without the points at the end
+            let name = entry.rfind(" (").map_or(entry.as_str(), |i| &entry[..i]);
+            (String::from(name), String::from(name), passed)
+        })
+        .collect()
+}
+
+fn link(href: &str, text: &str) -> String {
+    format!("<a href=\"{}\">{}</a>", escape(href), escape(text))
+}
+
+// A table of text, or a note that there's nothing to put in it
+fn table<S: AsRef<str>>(header: &[S], rows: Vec<Vec<String>>) -> String {
+    if rows.is_empty() {
+        return String::from("<p>Nothing yet.</p>\n");
+    }
+    let mut html = String::from("<table>\n<tr>");
+    for h in header {
+        html += &format!("<th>{}</th>", escape(h.as_ref()));
+    }
+    html += "</tr>\n";
+    for row in rows {
+        html += "<tr>";
+        for cell in row {
+            html += &format!("<td>{}</td>", escape(&cell));
+        }
+        html += "</tr>\n";
+    }
+    html + "</table>\n"
+}
+
+// The whole document, reloading every so many seconds unless it's 0
+fn page(body: &str, refresh: u32) -> String {
+    let refresh = match refresh {
+        0 => String::new(),
+        seconds => format!("<meta http-equiv=\"refresh\" content=\"{}\">\n", seconds),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n{}<title>Dropbox</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        refresh, STYLE, body
+    )
+}
+
+
+/// The token an instructor logs in with
+#[derive(FromForm)]
+struct Login {
+    token: String,
+}
+
+type Page = status::Custom<Html<String>>;
+
+/// Shows the dashboard, or asks for the token if the request doesn't have it
+#[get("/dashboard")]
+fn dashboard(token: Option<InstructorToken>, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>) -> Page {
+    if token.is_none() {
+        return status::Custom(Status::Unauthorized, Html(login_page(None)));
+    }
+    match boxes.submissions(&SubmissionQuery::default()) {
+        Ok(subs) => {
+            let identity_key = config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY);
+            let mut dashboard = Dashboard::new(subs, identity_key);
+            // Each assignment finds its students the way /gradebook does
+            for (name, assignment_box) in &boxes.by_name {
+                let key = assignment_box.config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY);
+                dashboard = dashboard.assignment_key(name, key);
+            }
+            status::Custom(Status::Ok, Html(dashboard.to_html()))
+        }
+        Err(e) => {
+            eprintln!("Error: Could not read submissions: {}", e);
+            let body = format!("<p>Couldn't read submissions: {}</p>\n", escape(&e.to_string()));
+            status::Custom(Status::InternalServerError, Html(page(&body, REFRESH_SECONDS)))
+        }
+    }
+}
+
+/// Keeps the token in a cookie if it's the instructor's, and goes to the dashboard
+#[post("/dashboard", data = "<login>")]
+fn log_in(config: State<DropboxConfig>, mut cookies: Cookies, login: Form<Login>) -> Result<Redirect, Page> {
+    match auth::instructor_access(config.inner(), Some(login.token.as_str())) {
+        Access::Granted => {
+            let cookie = Cookie::build(auth::TOKEN_COOKIE, login.token.clone())
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Strict)
+                .finish();
+            cookies.add(cookie);
+            Ok(Redirect::to("/dashboard"))
+        }
+        _ => Err(status::Custom(Status::Forbidden, Html(login_page(Some("That token isn't the instructor's."))))),
+    }
+}
+
+/// Forgets the token
+#[post("/dashboard/logout")]
+fn log_out(mut cookies: Cookies) -> Redirect {
+    cookies.remove(Cookie::build(auth::TOKEN_COOKIE, "").path("/").finish());
+    Redirect::to("/dashboard")
+}
+
+/// The dashboard's routes, to mount at `/`
+pub fn routes() -> Vec<rocket::Route> {
+    routes![dashboard, log_in, log_out]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use chrono::{Local, TimeZone};
+    use rocket::http::{ContentType, Header};
+    use rocket::local::Client;
+    use tempdir::TempDir;
+    use crate::data;
+    use crate::dropbox::{new_rocket, StoreKind};
+    use crate::dropbox::CriterionResult;
+
+    fn result(func: &str, status: ResultStatus) -> CriterionResult {
+        CriterionResult {
+            func: String::from(func),
+            name: format!("The {} criterion", func),
+            worth: 10,
+            earned: if status == ResultStatus::Passed { 10 } else { 0 },
+            status,
+            message: String::new(),
+            duration: Duration::from_millis(1),
+            hidden: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn sub(id: &str, assignment: &str, minute: u32, grade: isize) -> Submission {
+        let mut sub = Submission::from_data(data! { "id" => id });
+        sub.time = Local.ymd(2020, 5, 24).and_hms(13, minute, 0);
+        sub.grade = grade;
+        sub.set_assignment(assignment);
+        sub
+    }
+
+    fn sample() -> Dashboard {
+        let mut first = sub("s1", "lab1", 0, 50);
+        first.results = vec![result("compiles", ResultStatus::Passed), result("tests", ResultStatus::Failed)];
+        let mut second = sub("s1", "lab1", 10, 90);
+        second.results = vec![result("compiles", ResultStatus::Passed), result("tests", ResultStatus::Passed)];
+        second.late = true;
+        let mut third = sub("s2", "lab2", 5, 70);
+        third.results = vec![result("compiles", ResultStatus::Failed)];
+        Dashboard::new(vec![first, second, third], "id")
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<b>\"Tom\" & 'Jerry'</b>"), "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;");
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_recent_submissions() {
+        let dashboard = sample();
+        let recent: Vec<isize> = dashboard.recent_submissions().iter().map(|s| s.grade).collect();
+        assert_eq!(recent, vec![90, 70, 50]);
+
+        let dashboard = dashboard.recent(1);
+        assert_eq!(dashboard.recent_submissions()[0].grade, 90);
+        assert_eq!(dashboard.recent_submissions().len(), 1);
+    }
+
+    #[test]
+    fn test_criteria() {
+        let criteria = sample().criteria();
+        assert_eq!(criteria.len(), 3);
+        assert_eq!((criteria[0].assignment.as_str(), criteria[0].func.as_str()), ("lab1", "compiles"));
+        assert_eq!((criteria[0].passed, criteria[0].total), (2, 2));
+        assert_eq!(criteria[1].pass_rate(), 50.0);
+        assert_eq!((criteria[2].assignment.as_str(), criteria[2].passed), ("lab2", 0));
+    }
+
+    #[test]
+    fn test_criteria_from_csv() {
+        use crate::dropbox::store::{CsvStore, SubmissionStore};
+
+        let mut graded = sub("s1", "lab1", 0, 5);
+        graded.results = vec![result("compiles", ResultStatus::Passed), result("tests", ResultStatus::Failed)];
+        graded.passed = vec![String::from("The compiles criterion (+10)")];
+        graded.failed = vec![String::from("The tests criterion (-0)"), String::from("Late submission (-5)")];
+        graded.late = true;
+
+        // The CSV file only keeps the passed and failed lists
+        let dir = TempDir::new("dashboard").unwrap();
+        let mut store = CsvStore::open(dir.path().join("submissions.csv"), ',').unwrap();
+        store.store(&[graded]).unwrap();
+        let subs = store.submissions().unwrap();
+        assert!(subs[0].results.is_empty());
+
+        let criteria = Dashboard::new(subs, "id").criteria();
+        let counts: Vec<(&str, usize, usize)> = criteria.iter().map(|c| (c.name.as_str(), c.passed, c.total)).collect();
+        assert_eq!(counts, vec![("The compiles criterion", 1, 1), ("The tests criterion", 0, 1)]);
+    }
+
+    #[test]
+    fn test_late_counts() {
+        let counts = sample().late_counts();
+        assert_eq!(counts, vec![
+            LateCount { assignment: String::from("lab1"), submissions: 2, late: 1 },
+            LateCount { assignment: String::from("lab2"), submissions: 1, late: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_latest_grades() {
+        let book = sample().gradebook();
+        assert_eq!(book.row("s1").unwrap().scores["lab1"].grade, 90.0);
+        assert_eq!(book.row("s2").unwrap().scores["lab2"].grade, 70.0);
+    }
+
+    #[test]
+    fn test_assignment_keys() {
+        let mut by_email = sub("s2", "lab2", 20, 60);
+        by_email.data.insert(String::from("email"), String::from("s2@example.edu"));
+        let dashboard = Dashboard::new(vec![sub("s1", "lab1", 0, 50), by_email], "id")
+            .assignment_key("lab2", "email");
+
+        let book = dashboard.gradebook();
+        assert_eq!(book.row("s1").unwrap().scores["lab1"].grade, 50.0);
+        // Found by email, but still in the gradebook by id
+        assert_eq!(book.row("s2").unwrap().scores["lab2"].grade, 60.0);
+        assert!(book.row("s2@example.edu").is_none());
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html = sample().to_html();
+        assert!(html.contains("<meta http-equiv=\"refresh\" content=\"30\">"));
+        assert!(html.contains("Submissions: 3. Students: 2. Late: 1."));
+        assert!(html.contains("<td>The tests criterion</td><td>1 of 2</td><td>50%</td>"));
+        assert!(html.contains("<td>s1</td><td>90 (late)</td><td></td>"));
+        assert!(html.contains("href=\"/gradebook/canvas\""));
+
+        let html = sample().refresh(0).to_html();
+        assert!(!html.contains("http-equiv"));
+    }
+
+    #[test]
+    fn test_to_html_escapes() {
+        let html = Dashboard::new(vec![sub("<script>alert(1)</script>", "lab1", 0, 10)], "id").to_html();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn test_empty() {
+        let html = Dashboard::new(Vec::new(), "id").to_html();
+        assert!(html.contains("Submissions: 0. Students: 0."));
+        assert!(html.contains("Nothing yet."));
+    }
+
+    fn client(dir: &TempDir) -> Client {
+        let config = DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .store(StoreKind::JsonLines)
+            .instructor_token("teacher");
+        Client::new(new_rocket(config)).expect("valid rocket instance")
+    }
+
+    fn post_token(client: &Client, token: &str) -> Status {
+        client.post("/dashboard")
+            .header(ContentType::Form)
+            .body(format!("token={}", token))
+            .dispatch()
+            .status()
+    }
+
+    #[test]
+    fn test_log_in() {
+        let dir = TempDir::new("dashboard").unwrap();
+        let client = client(&dir);
+
+        let mut resp = client.get("/dashboard").dispatch();
+        assert_eq!(resp.status(), Status::Unauthorized);
+        assert!(resp.body_string().unwrap().contains("name=\"token\""));
+
+        assert_eq!(post_token(&client, "student"), Status::Forbidden);
+        assert_eq!(client.get("/dashboard").dispatch().status(), Status::Unauthorized);
+
+        // The client keeps the cookie
+        assert_eq!(post_token(&client, "teacher"), Status::SeeOther);
+        let mut resp = client.get("/dashboard").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.body_string().unwrap().contains("<h2>Recent submissions</h2>"));
+        // So do the downloads
+        assert_eq!(client.get("/submissions.csv").dispatch().status(), Status::Ok);
+
+        assert_eq!(client.post("/dashboard/logout").dispatch().status(), Status::SeeOther);
+        assert_eq!(client.get("/dashboard").dispatch().status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let dir = TempDir::new("dashboard").unwrap();
+        let client = client(&dir);
+        let mut sub = Submission::from_data(data! { "id" => "s1" });
+        sub.grade = 85;
+        sub.results = vec![result("compiles", ResultStatus::Passed)];
+        let resp = client.post("/submit")
+            .body(serde_json::to_string(&sub).unwrap())
+            .header(ContentType::JSON)
+            .dispatch();
+        assert_eq!(resp.status(), Status::Accepted);
+
+        let mut resp = client.get("/dashboard")
+            .header(Header::new("Authorization", auth::bearer_header("teacher")))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        let html = resp.body_string().unwrap();
+        assert!(html.contains("Submissions: 1. Students: 1. Late: 0."));
+        assert!(html.contains("<td>s1</td><td>85</td>"));
+        assert!(html.contains("<td>The compiles criterion</td><td>1 of 1</td><td>100%</td>"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Encrypting submissions on their way to the dropbox
+//!
+//! The dropbox is often run without TLS, so anyone between the student and the dropbox
+//! could read a submission. Instead, the grader can encrypt submissions with the
+//! dropbox's public key. Only the dropbox, with the matching private key, can decrypt them.
+//!
+//! Each submission is encrypted with a new random AES-256-GCM key, and that key is
+//! encrypted with the dropbox's RSA public key (OAEP padding).
+//!
+//! This only keeps submissions secret. The public key ships in the grader, so anyone
+//! can encrypt a submission of their own, or a changed copy of someone else's. To tell
+//! if a submission is genuine, [sign](crate::dropbox::signature) it too.
+//!
+//! Make a key pair once, then embed the public key in the grader and give the
+//! private key to the dropbox.
+//!
+//! ```no_run
+//! use rubric::dropbox::encryption;
+//!
+//! let (private_key, public_key) = encryption::generate_keys().unwrap();
+//! std::fs::write("dropbox_private.pem", private_key).unwrap();
+//! std::fs::write("dropbox_public.pem", public_key).unwrap();
+//! ```
+
+// external uses
+use anyhow::{anyhow, Context};
+use openssl::rand::rand_bytes;
+use openssl::rsa::{Padding, Rsa};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// internal uses
+use crate::Result;
+
+
+/// The JSON field an encrypted submission is sent in
+pub const ENCRYPTED_FIELD: &str = "encrypted";
+
+/// The size of generated RSA keys, in bits
+pub const KEY_BITS: u32 = 3072;
+
+// AES-256 key, and GCM nonce and tag sizes in bytes
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+
+/// Something encrypted for the dropbox. Everything is base64 encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedPayload {
+    /// The AES key, encrypted with the dropbox's public key
+    pub key: String,
+    /// The GCM nonce
+    pub nonce: String,
+    /// The encrypted data, followed by the GCM tag
+    pub ciphertext: String,
+}
+
+impl EncryptedPayload {
+    /// Wraps the payload in JSON, the way it's sent to the dropbox
+    pub fn into_value(self) -> Value {
+        let mut map = serde_json::Map::new();
+        map.insert(String::from(ENCRYPTED_FIELD), serde_json::to_value(self).expect("Payload is always valid JSON"));
+        Value::Object(map)
+    }
+
+    /// Reads the payload out of JSON sent to the dropbox, if it's encrypted
+    pub fn from_value(value: &Value) -> Option<EncryptedPayload> {
+        serde_json::from_value(value.get(ENCRYPTED_FIELD)?.clone()).ok()
+    }
+}
+
+
+/// Makes a new RSA key pair, returned as `(private, public)` PEM strings.
+pub fn generate_keys() -> Result<(String, String)> {
+    let rsa = Rsa::generate(KEY_BITS)?;
+    let private = String::from_utf8(rsa.private_key_to_pem()?)?;
+    let public = String::from_utf8(rsa.public_key_to_pem()?)?;
+    Ok((private, public))
+}
+
+/// Returns an error if `pem` isn't an RSA public key
+pub fn check_public_key(pem: &str) -> Result<()> {
+    Rsa::public_key_from_pem(pem.as_bytes()).context("Invalid public key")?;
+    Ok(())
+}
+
+/// Encrypts some data with a PEM encoded RSA public key
+///
+/// ```rust
+/// use rubric::dropbox::encryption;
+///
+/// let (private_key, public_key) = encryption::generate_keys().unwrap();
+/// let payload = encryption::encrypt(&public_key, b"student data").unwrap();
+/// assert_eq!(encryption::decrypt(&private_key, &payload).unwrap(), b"student data");
+/// ```
+pub fn encrypt(public_key: &str, plaintext: &[u8]) -> Result<EncryptedPayload> {
+    let rsa = Rsa::public_key_from_pem(public_key.as_bytes()).context("Invalid public key")?;
+
+    let mut key = [0u8; KEY_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand_bytes(&mut key)?;
+    rand_bytes(&mut nonce)?;
+
+    let mut tag = [0u8; TAG_LEN];
+    let mut ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), &[], plaintext, &mut tag)?;
+    ciphertext.extend_from_slice(&tag);
+
+    let mut wrapped = vec![0u8; rsa.size() as usize];
+    let len = rsa.public_encrypt(&key, &mut wrapped, Padding::PKCS1_OAEP)?;
+    wrapped.truncate(len);
+
+    Ok(EncryptedPayload {
+        key: base64::encode(wrapped),
+        nonce: base64::encode(nonce),
+        ciphertext: base64::encode(ciphertext),
+    })
+}
+
+/// Decrypts a payload with a PEM encoded RSA private key.
+///
+/// Returns an error if it was encrypted with a different key, or changed after it was encrypted.
+pub fn decrypt(private_key: &str, payload: &EncryptedPayload) -> Result<Vec<u8>> {
+    let rsa = Rsa::private_key_from_pem(private_key.as_bytes()).context("Invalid private key")?;
+
+    let wrapped = base64::decode(&payload.key)?;
+    let nonce = base64::decode(&payload.nonce)?;
+    let mut ciphertext = base64::decode(&payload.ciphertext)?;
+    if ciphertext.len() < TAG_LEN || nonce.len() != NONCE_LEN {
+        return Err(anyhow!("Encrypted payload is malformed"));
+    }
+    let tag = ciphertext.split_off(ciphertext.len() - TAG_LEN);
+
+    let mut key = vec![0u8; rsa.size() as usize];
+    let len = rsa.private_decrypt(&wrapped, &mut key, Padding::PKCS1_OAEP)
+        .context("Couldn't decrypt the payload's key")?;
+    key.truncate(len);
+    if key.len() != KEY_LEN {
+        return Err(anyhow!("Encrypted payload is malformed"));
+    }
+
+    let plaintext = decrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), &[], &ciphertext, &tag)
+        .context("Couldn't decrypt the payload, it may have been changed")?;
+    Ok(plaintext)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let (private_key, public_key) = generate_keys().unwrap();
+        assert!(check_public_key(&public_key).is_ok());
+        assert!(check_public_key("not a key").is_err());
+
+        let payload = encrypt(&public_key, b"some secret data").unwrap();
+        assert!(!payload.ciphertext.contains("secret"));
+        assert_eq!(decrypt(&private_key, &payload).unwrap(), b"some secret data");
+
+        // As JSON
+        let value = payload.clone().into_value();
+        assert_eq!(EncryptedPayload::from_value(&value), Some(payload));
+        assert_eq!(EncryptedPayload::from_value(&serde_json::json!({"grade": 10})), None);
+    }
+
+    #[test]
+    fn test_wrong_key_or_tampered() {
+        let (private_key, public_key) = generate_keys().unwrap();
+        let (other_private_key, _) = generate_keys().unwrap();
+
+        let payload = encrypt(&public_key, b"data").unwrap();
+        assert!(decrypt(&other_private_key, &payload).is_err());
+
+        // Flip a bit in the ciphertext
+        let mut payload = payload;
+        let mut bytes = base64::decode(&payload.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        payload.ciphertext = base64::encode(bytes);
+        assert!(decrypt(&private_key, &payload).is_err());
+    }
+}
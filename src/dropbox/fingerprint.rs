@@ -2,12 +2,140 @@
 
 // std uses
 use std::env;
+use std::fs;
 
 // external uses
 use serde::{Serialize, Deserialize};
 
 // internal uses
 use crate::dropbox::AsCsv;
+use crate::dropbox::receipt::digest;
+use crate::dropbox::csv;
+use crate::helpers::cli;
+
+
+/// The columns a fingerprint adds to a submission's CSV, in order
+pub const FINGERPRINT_COLUMNS: [&str; 7] = [
+    "platform", "hostname", "username", "machine_id", "working_dir", "git_remote", "git_email",
+];
+
+
+/// Which machine information a [`Fingerprint`] collects.
+///
+/// Everything is collected by default. The machine id is always hashed, and
+/// the hostname, username, working directory, and git email are hashed unless
+/// you turn `hash_personal` off. Hashes can still be compared to each other, to
+/// find students submitting from the same machine, without revealing the value.
+///
+/// Set a different `salt` for each course, so hashes can't be matched between courses.
+///
+/// ```rust
+/// use rubric::dropbox::fingerprint::FingerprintOptions;
+///
+/// let options = FingerprintOptions::default()
+///     .salt("cs101-fall")
+///     .working_dir(false);
+/// assert!(!options.working_dir);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintOptions {
+    pub hostname: bool,
+    pub username: bool,
+    pub machine_id: bool,
+    pub working_dir: bool,
+    pub git_remote: bool,
+    pub git_email: bool,
+    /// Hash the hostname, username, working directory, and git email
+    pub hash_personal: bool,
+    /// Mixed into every hash
+    pub salt: String,
+}
+
+impl Default for FingerprintOptions {
+    fn default() -> Self {
+        FingerprintOptions {
+            hostname: true,
+            username: true,
+            machine_id: true,
+            working_dir: true,
+            git_remote: true,
+            git_email: true,
+            hash_personal: true,
+            salt: String::new(),
+        }
+    }
+}
+
+impl FingerprintOptions {
+    /// Only collects the platform, like fingerprints used to
+    pub fn minimal() -> Self {
+        FingerprintOptions {
+            hostname: false,
+            username: false,
+            machine_id: false,
+            working_dir: false,
+            git_remote: false,
+            git_email: false,
+            ..FingerprintOptions::default()
+        }
+    }
+
+    pub fn hostname(mut self, collect: bool) -> Self {
+        self.hostname = collect;
+        self
+    }
+
+    pub fn username(mut self, collect: bool) -> Self {
+        self.username = collect;
+        self
+    }
+
+    pub fn machine_id(mut self, collect: bool) -> Self {
+        self.machine_id = collect;
+        self
+    }
+
+    pub fn working_dir(mut self, collect: bool) -> Self {
+        self.working_dir = collect;
+        self
+    }
+
+    pub fn git_remote(mut self, collect: bool) -> Self {
+        self.git_remote = collect;
+        self
+    }
+
+    pub fn git_email(mut self, collect: bool) -> Self {
+        self.git_email = collect;
+        self
+    }
+
+    /// Sets whether personal information is hashed
+    pub fn hash_personal(mut self, hash: bool) -> Self {
+        self.hash_personal = hash;
+        self
+    }
+
+    /// Sets the salt mixed into every hash
+    pub fn salt(mut self, salt: &str) -> Self {
+        self.salt = String::from(salt);
+        self
+    }
+
+    // Hashes a value with the salt
+    fn hash(&self, value: &str) -> String {
+        digest(&format!("{}{}", self.salt, value))
+    }
+
+    // Hashes a value only if personal information should be hashed
+    fn personal(&self, value: String) -> String {
+        if self.hash_personal {
+            self.hash(&value)
+        } else {
+            value
+        }
+    }
+}
 
 
 /// A set of data to verify a Submission. This struct contains some system data, and
@@ -15,27 +143,133 @@ use crate::dropbox::AsCsv;
 /// 
 /// You shouldn't create one of these directly, instead add a fingerprint to a submission
 /// with [`Submission::set_fingerprint`](crate::dropbox::submission::Submission::set_fingerprint).
-#[derive(Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
 pub struct Fingerprint {
-    /// Any random string
+    /// Any random string.
+    ///
+    /// This is used to [sign](crate::dropbox::signature) the submission, and is
+    /// never sent or written anywhere.
+    #[serde(skip_serializing, default)]
     pub secret: String,
-    pub platform: String
+    pub platform: String,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Always hashed
+    #[serde(default)]
+    pub machine_id: Option<String>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// The url of the `origin` remote, if the grader is run in a git repo
+    #[serde(default)]
+    pub git_remote: Option<String>,
+    /// The user's git email, if they've set one
+    #[serde(default)]
+    pub git_email: Option<String>,
 }
 
 impl Fingerprint {
 
-    /// Creates a new fingerprint from a secret string
+    /// Creates a new fingerprint from a secret string, collecting
+    /// everything in the default [`FingerprintOptions`].
     pub fn from_secret(secret: &str) -> Self {
+        Fingerprint::with_options(secret, &FingerprintOptions::default())
+    }
+
+    /// Creates a new fingerprint from a secret string, collecting
+    /// the machine information in `options`.
+    pub fn with_options(secret: &str, options: &FingerprintOptions) -> Self {
+        let collect = |enabled: bool, f: fn() -> Option<String>| {
+            if enabled { f() } else { None }
+        };
+
         Fingerprint {
             secret: String::from(secret),
-            platform: String::from(env::consts::OS)
+            platform: String::from(env::consts::OS),
+            hostname: collect(options.hostname, hostname).map(|h| options.personal(h)),
+            username: collect(options.username, username).map(|u| options.personal(u)),
+            machine_id: collect(options.machine_id, machine_id).map(|m| options.hash(&m)),
+            working_dir: collect(options.working_dir, working_dir).map(|w| options.personal(w)),
+            git_remote: collect(options.git_remote, git_remote),
+            git_email: collect(options.git_email, git_email).map(|e| options.personal(e)),
         }
     }
 }
 
+
+// Runs a command, returning its trimmed output if it succeeded and wasn't empty
+fn command_output(command: &str) -> Option<String> {
+    let output = cli::cmd(command).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    non_empty(String::from_utf8(output.stdout).ok()?)
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+fn hostname() -> Option<String> {
+    env::var("HOSTNAME").ok()
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .and_then(non_empty)
+        .or_else(|| fs::read_to_string("/etc/hostname").ok().and_then(non_empty))
+        .or_else(|| command_output("hostname"))
+}
+
+fn username() -> Option<String> {
+    env::var("USER").ok()
+        .or_else(|| env::var("USERNAME").ok())
+        .and_then(non_empty)
+}
+
+fn machine_id() -> Option<String> {
+    if cfg!(target_os = "windows") {
+        let out = command_output(r"reg query HKLM\SOFTWARE\Microsoft\Cryptography /v MachineGuid")?;
+        return out.split_whitespace().last().map(String::from);
+    }
+
+    if cfg!(target_os = "macos") {
+        let out = command_output("ioreg -rd1 -c IOPlatformExpertDevice")?;
+        let line = out.lines().find(|l| l.contains("IOPlatformUUID"))?;
+        return line.split('"').nth(3).map(String::from);
+    }
+
+    fs::read_to_string("/etc/machine-id").ok()
+        .or_else(|| fs::read_to_string("/var/lib/dbus/machine-id").ok())
+        .and_then(non_empty)
+}
+
+fn working_dir() -> Option<String> {
+    env::current_dir().ok().map(|d| d.display().to_string())
+}
+
+fn git_remote() -> Option<String> {
+    command_output("git config --get remote.origin.url")
+}
+
+fn git_email() -> Option<String> {
+    command_output("git config --get user.email")
+}
+
+
 impl AsCsv for Fingerprint {
+    // The secret is left out on purpose
     fn as_csv(&self) -> String {
-        format!("{},{}", self.secret, self.platform)
+        let optional = [
+            &self.hostname,
+            &self.username,
+            &self.machine_id,
+            &self.working_dir,
+            &self.git_remote,
+            &self.git_email,
+        ];
+        let mut values = vec![self.platform.as_str()];
+        values.extend(optional.iter().map(|v| v.as_deref().unwrap_or("")));
+        csv::write_row(&values, ',')
     }
 
     fn filename(&self) -> String {
@@ -43,7 +277,7 @@ impl AsCsv for Fingerprint {
     }
 
     fn header(&self) -> String {
-        format!("secret,platform")
+        FINGERPRINT_COLUMNS.join(",")
     }
 
 }
@@ -64,11 +298,60 @@ mod tests {
     #[test]
     fn test_as_csv() {
         let fp = Fingerprint::from_secret("my_secret");
-        assert!(fp.header().contains(","));
         assert!(fp.header().len() > 0);
-        assert!(fp.as_csv().contains("my_secret"));
+        assert!(!fp.as_csv().contains("my_secret"));
         if cfg!(target_os = "windows") {
             assert!(fp.as_csv().contains("windows"));
         }
+        // Every column is always there, even if it's empty
+        assert_eq!(
+            fp.header().split(',').count(),
+            csv::parse(&fp.as_csv(), ',').unwrap()[0].len()
+        );
+    }
+
+    #[test]
+    fn test_secret_not_serialized() {
+        let fp = Fingerprint::from_secret("my_secret");
+        let json = serde_json::to_string(&fp).unwrap();
+        assert!(!json.contains("my_secret"));
+
+        let back: Fingerprint = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.secret, "");
+        assert_eq!(back.platform, fp.platform);
+    }
+
+    #[test]
+    fn test_personal_info_hashed() {
+        let fp = Fingerprint::from_secret("secret");
+        let cwd = working_dir().unwrap();
+        let hashed = fp.working_dir.unwrap();
+        assert_ne!(hashed, cwd);
+        assert_eq!(hashed, digest(&cwd));
+
+        let plain = Fingerprint::with_options("secret", &FingerprintOptions::default().hash_personal(false));
+        assert_eq!(plain.working_dir.unwrap(), cwd);
+    }
+
+    #[test]
+    fn test_salt_changes_hashes() {
+        let a = Fingerprint::with_options("secret", &FingerprintOptions::default().salt("course a"));
+        let b = Fingerprint::with_options("secret", &FingerprintOptions::default().salt("course b"));
+        assert_ne!(a.working_dir, b.working_dir);
+    }
+
+    #[test]
+    fn test_minimal_options() {
+        let fp = Fingerprint::with_options("secret", &FingerprintOptions::minimal());
+        assert!(fp.hostname.is_none());
+        assert!(fp.working_dir.is_none());
+        assert!(fp.git_remote.is_none());
+    }
+
+    #[test]
+    fn test_old_fingerprints_deserialize() {
+        let fp: Fingerprint = serde_json::from_str(r#"{"platform":"linux"}"#).unwrap();
+        assert_eq!(fp.platform, "linux");
+        assert!(fp.hostname.is_none());
     }
 }
@@ -0,0 +1,449 @@
+//! One row per student
+//!
+//! Students who submit more than once have more than one row in the results.
+//! A [`Gradebook`] groups submissions by the student who made them, and takes
+//! one grade from each student's attempts with a [`ScorePolicy`](crate::rubric::ScorePolicy).
+//! Each assignment is a column.
+//!
+//! ```rust
+//! use rubric::{data, Submission};
+//! use rubric::rubric::ScorePolicy;
+//! use rubric::dropbox::Gradebook;
+//!
+//! let mut first = Submission::from_data(data! { "id" => "s1" });
+//! first.grade = 80;
+//! let mut second = Submission::from_data(data! { "id" => "s1" });
+//! second.grade = 60;
+//!
+//! let book = Gradebook::new("id").assignment("lab1", &[first, second], ScorePolicy::Best);
+//! let score = &book.row("s1").unwrap().scores["lab1"];
+//! assert_eq!(score.grade, 80.0);
+//! assert_eq!(score.attempts, 2);
+//! ```
+
+// std uses
+use std::collections::{BTreeMap, BTreeSet};
+
+// external uses
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use crate::rubric::ScorePolicy;
+use crate::dropbox::{Submission, TestData};
+use crate::dropbox::csv;
+use crate::dropbox::fingerprint::FINGERPRINT_COLUMNS;
+use crate::dropbox::store::CRITERION_COLUMN_PREFIX;
+
+
+/// The column for submissions that don't name an assignment
+pub const UNNAMED_ASSIGNMENT: &str = "grade";
+
+
+/// A student's grade for one assignment, from all their attempts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// The grade that counts. Averages are rounded to two decimal places.
+    pub grade: f64,
+    /// How many submissions they made
+    pub attempts: u32,
+    /// If the attempt that counts was late. For averages, if any of them were.
+    pub late: bool,
+    /// The submission the grade came from. Averages don't come from just one.
+    pub submission_id: Option<String>,
+    /// When their last attempt was received
+    pub last_received: DateTime<Local>,
+}
+
+// When a submission was received, or made if that isn't known
+fn received(sub: &Submission) -> DateTime<Local> {
+    sub.received_at.unwrap_or(sub.time)
+}
+
+/// One student's score from their attempts, oldest first. `None` if there weren't any.
+pub fn score(attempts: &[&Submission], policy: ScorePolicy) -> Option<Score> {
+    let last = attempts.iter().max_by_key(|s| received(s))?;
+    let counted = match policy {
+        // Ties go to the later attempt
+        ScorePolicy::Best => attempts.iter().max_by_key(|s| (s.grade, received(s))),
+        ScorePolicy::Latest => Some(last),
+        ScorePolicy::Average => None,
+    };
+
+    let (grade, late, submission_id) = match counted {
+        Some(sub) => (sub.grade as f64, sub.late, Some(sub.id.clone())),
+        None => {
+            let total: isize = attempts.iter().map(|s| s.grade).sum();
+            let average = total as f64 / attempts.len() as f64;
+            ((average * 100.0).round() / 100.0, attempts.iter().any(|s| s.late), None)
+        },
+    };
+    Some(Score {
+        grade,
+        attempts: attempts.len() as u32,
+        late,
+        submission_id,
+        last_received: received(last),
+    })
+}
+
+
+// A submission's data without criterion points or fingerprints, which are in
+// the data of submissions read back from a CSV file
+fn student_data(data: &TestData) -> TestData {
+    data.iter()
+        .filter(|(k, _)| !k.starts_with(CRITERION_COLUMN_PREFIX) && !FINGERPRINT_COLUMNS.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+
+/// One student in the gradebook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GradebookRow {
+    /// The student, by the gradebook's identity key
+    pub student: String,
+    /// The data from their last submission, like their name
+    pub data: TestData,
+    /// Their score for each assignment they submitted, by name
+    pub scores: BTreeMap<String, Score>,
+}
+
+impl GradebookRow {
+    // When this student's last submission for any assignment was received
+    fn last_received(&self) -> Option<DateTime<Local>> {
+        self.scores.values().map(|s| s.last_received).max()
+    }
+}
+
+/// Every student's grades, one row each
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradebook {
+    /// The data key that identifies a student
+    pub identity_key: String,
+    /// The assignments, in the order they were added
+    pub assignments: Vec<String>,
+    /// The students, in order
+    pub rows: Vec<GradebookRow>,
+}
+
+impl Gradebook {
+    /// An empty gradebook, finding students by this key in their submissions' data
+    pub fn new(identity_key: &str) -> Self {
+        Gradebook {
+            identity_key: String::from(identity_key),
+            assignments: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds an assignment's grades, taking one from each student's submissions
+    /// with the policy. Submissions without the identity key are left out.
+    pub fn assignment(self, name: &str, subs: &[Submission], policy: ScorePolicy) -> Self {
+        let identity_key = self.identity_key.clone();
+        self.assignment_by(name, subs, policy, &identity_key)
+    }
+
+    /// Like [`assignment`](Gradebook::assignment), but finds students by another key,
+    /// for assignments that identify students differently. Students are still in
+    /// the gradebook by its own key, if their submissions have it.
+    pub fn assignment_by(mut self, name: &str, subs: &[Submission], policy: ScorePolicy, identity_key: &str) -> Self {
+        if !self.assignments.iter().any(|a| a == name) {
+            self.assignments.push(String::from(name));
+        }
+
+        let mut by_student: BTreeMap<&str, Vec<&Submission>> = BTreeMap::new();
+        for sub in subs {
+            if let Some(student) = sub.data.get(identity_key).filter(|s| !s.is_empty()) {
+                by_student.entry(student.as_str()).or_default().push(sub);
+            }
+        }
+
+        for (found_by, attempts) in by_student {
+            let score = match score(&attempts, policy) {
+                Some(score) => score,
+                None => continue,
+            };
+            let last = attempts.iter().max_by_key(|s| received(s)).expect("Scored attempts");
+            let student = self.student(found_by, identity_key, &attempts);
+
+            let index = match self.rows.binary_search_by(|r| r.student.cmp(&student)) {
+                Ok(i) => i,
+                Err(i) => {
+                    self.rows.insert(i, GradebookRow {
+                        student,
+                        data: TestData::new(),
+                        scores: BTreeMap::new(),
+                    });
+                    i
+                },
+            };
+            let row = &mut self.rows[index];
+            if row.last_received().map_or(true, |t| t <= score.last_received) {
+                row.data = student_data(&last.data);
+            }
+            row.scores.insert(String::from(name), score);
+        }
+        self
+    }
+
+    // The row for a student found by another key. Their submissions might have
+    // the gradebook's key too, or they might already have a row with this key
+    // in its data. Otherwise they're in the gradebook by the other key.
+    fn student(&self, found_by: &str, identity_key: &str, attempts: &[&Submission]) -> String {
+        if identity_key == self.identity_key {
+            return String::from(found_by);
+        }
+        attempts.iter().rev()
+            .find_map(|s| s.data.get(&self.identity_key).filter(|id| !id.is_empty()).cloned())
+            .or_else(|| {
+                self.rows.iter()
+                    .find(|r| r.data.get(identity_key).map(String::as_str) == Some(found_by))
+                    .map(|r| r.student.clone())
+            })
+            .unwrap_or_else(|| String::from(found_by))
+    }
+
+    /// Adds the grades for every assignment the submissions name, with the same policy.
+    /// Submissions without an assignment are under [`UNNAMED_ASSIGNMENT`].
+    pub fn submissions(self, subs: &[Submission], policy: ScorePolicy) -> Self {
+        let mut names: Vec<&str> = Vec::new();
+        for sub in subs {
+            let name = sub.assignment.as_deref().unwrap_or(UNNAMED_ASSIGNMENT);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        names.into_iter().fold(self, |book, name| {
+            let for_assignment: Vec<Submission> = subs.iter()
+                .filter(|s| s.assignment.as_deref().unwrap_or(UNNAMED_ASSIGNMENT) == name)
+                .cloned()
+                .collect();
+            book.assignment(name, &for_assignment, policy)
+        })
+    }
+
+    /// A student's row, if they've submitted anything
+    pub fn row(&self, student: &str) -> Option<&GradebookRow> {
+        self.rows.iter().find(|r| r.student == student)
+    }
+
+    /// The gradebook as CSV, with a header.
+    ///
+    /// The first column is the student, then the rest of their data,
+    /// then each assignment's grade and how many attempts it took.
+    /// Grades for assignments a student didn't submit are empty.
+    pub fn to_csv(&self, delimiter: char) -> String {
+        let data_keys: BTreeSet<&String> = self.rows.iter()
+            .flat_map(|r| r.data.keys())
+            .filter(|k| **k != self.identity_key)
+            .collect();
+
+        let mut header = vec![self.identity_key.clone()];
+        header.extend(data_keys.iter().map(|k| k.to_string()));
+        for a in &self.assignments {
+            header.push(a.clone());
+            header.push(format!("{} attempts", a));
+        }
+
+        let mut lines = vec![csv::write_row(&header, delimiter)];
+        for row in &self.rows {
+            let mut values = vec![row.student.clone()];
+            values.extend(data_keys.iter().map(|k| row.data.get(*k).cloned().unwrap_or_default()));
+            for a in &self.assignments {
+                match row.scores.get(a) {
+                    Some(score) => {
+                        values.push(score.grade.to_string());
+                        values.push(score.attempts.to_string());
+                    },
+                    None => values.extend(vec![String::new(), String::new()]),
+                }
+            }
+            lines.push(csv::write_row(&values, delimiter));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::data;
+
+    // An attempt from a student, some minutes after the first
+    fn attempt(student: &str, grade: isize, minutes: i64) -> Submission {
+        let mut sub = Submission::from_data(data! { "id" => student });
+        sub.grade = grade;
+        sub.received_at = Some(Local::now() + Duration::minutes(minutes));
+        sub
+    }
+
+    #[test]
+    fn test_score_policies() {
+        let (a, b, c) = (attempt("s1", 70, 0), attempt("s1", 90, 1), attempt("s1", 80, 2));
+        let attempts = vec![&a, &b, &c];
+
+        let best = score(&attempts, ScorePolicy::Best).unwrap();
+        assert_eq!(best.grade, 90.0);
+        assert_eq!(best.submission_id, Some(b.id.clone()));
+        assert_eq!(best.attempts, 3);
+        assert_eq!(best.last_received, c.received_at.unwrap());
+
+        let latest = score(&attempts, ScorePolicy::Latest).unwrap();
+        assert_eq!(latest.grade, 80.0);
+        assert_eq!(latest.submission_id, Some(c.id.clone()));
+
+        let average = score(&attempts, ScorePolicy::Average).unwrap();
+        assert_eq!(average.grade, 80.0);
+        assert_eq!(average.submission_id, None);
+
+        assert!(score(&[], ScorePolicy::Best).is_none());
+    }
+
+    #[test]
+    fn test_average_rounds_and_late() {
+        let a = attempt("s1", 10, 0);
+        let mut b = attempt("s1", 10, 1);
+        let c = attempt("s1", 11, 2);
+        b.late = true;
+
+        let average = score(&[&a, &b, &c], ScorePolicy::Average).unwrap();
+        assert_eq!(average.grade, 10.33);
+        assert!(average.late);
+        // The latest wasn't late
+        assert!(!score(&[&a, &b, &c], ScorePolicy::Latest).unwrap().late);
+    }
+
+    #[test]
+    fn test_best_ties_go_to_later() {
+        let (a, b) = (attempt("s1", 50, 0), attempt("s1", 50, 1));
+        let best = score(&[&a, &b], ScorePolicy::Best).unwrap();
+        assert_eq!(best.submission_id, Some(b.id));
+    }
+
+    #[test]
+    fn test_gradebook() {
+        let subs = vec![
+            attempt("s2", 40, 0),
+            attempt("s1", 70, 1),
+            attempt("s1", 50, 2),
+            // Left out, there's no id
+            Submission::new(),
+        ];
+        let book = Gradebook::new("id").assignment("lab1", &subs, ScorePolicy::Latest);
+
+        assert_eq!(book.assignments, vec!["lab1"]);
+        let students: Vec<&str> = book.rows.iter().map(|r| r.student.as_str()).collect();
+        assert_eq!(students, vec!["s1", "s2"]);
+        assert_eq!(book.row("s1").unwrap().scores["lab1"].grade, 50.0);
+        assert!(book.row("s3").is_none());
+
+        // Another assignment adds a column, and new students
+        let book = book.assignment("lab2", &[attempt("s3", 100, 3)], ScorePolicy::Latest);
+        assert_eq!(book.rows.len(), 3);
+        assert!(book.row("s1").unwrap().scores.get("lab2").is_none());
+        assert_eq!(book.row("s3").unwrap().scores["lab2"].grade, 100.0);
+    }
+
+    #[test]
+    fn test_assignment_by_another_key() {
+        let with_email = |id: &str, email: &str, grade: isize| {
+            let mut sub = attempt(id, grade, 1);
+            sub.data.insert(String::from("email"), String::from(email));
+            sub
+        };
+        let only_email = |email: &str, grade: isize| {
+            let mut sub = with_email("", email, grade);
+            sub.data.remove("id");
+            sub
+        };
+
+        // lab2 identifies students by email
+        let book = Gradebook::new("id")
+            .assignment("lab1", &[with_email("s1", "s1@example.edu", 70), attempt("s2", 60, 0)], ScorePolicy::Latest)
+            .assignment_by("lab2", &[
+                // Found by their id in the same submission
+                with_email("s2", "s2@example.edu", 90),
+                // Found by the email in their lab1 row
+                only_email("s1@example.edu", 80),
+                // Nobody knows who this is by id
+                only_email("s3@example.edu", 50),
+                // Left out, no email
+                attempt("s4", 40, 2),
+            ], ScorePolicy::Latest, "email");
+
+        let students: Vec<&str> = book.rows.iter().map(|r| r.student.as_str()).collect();
+        assert_eq!(students, vec!["s1", "s2", "s3@example.edu"]);
+        assert_eq!(book.row("s1").unwrap().scores["lab2"].grade, 80.0);
+        assert_eq!(book.row("s2").unwrap().scores["lab2"].grade, 90.0);
+        assert_eq!(book.row("s2").unwrap().scores["lab1"].grade, 60.0);
+    }
+
+    #[test]
+    fn test_data_leaves_out_criteria_and_fingerprints() {
+        let mut sub = attempt("s1", 10, 0);
+        sub.data.insert(String::from("name"), String::from("Jo"));
+        sub.data.insert(String::from("criterion:compiles"), String::from("10"));
+        sub.data.insert(String::from("machine_id"), String::from("abc123"));
+
+        let book = Gradebook::new("id").assignment("lab1", &[sub], ScorePolicy::Latest);
+        let mut keys: Vec<&String> = book.row("s1").unwrap().data.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["id", "name"]);
+        assert!(!book.to_csv(',').contains("machine_id"));
+    }
+
+    #[test]
+    fn test_submissions_by_assignment() {
+        let mut lab1 = attempt("s1", 10, 0);
+        lab1.set_assignment("lab1");
+        let mut lab2 = attempt("s1", 20, 1);
+        lab2.set_assignment("lab2");
+        let unnamed = attempt("s1", 30, 2);
+
+        let book = Gradebook::new("id").submissions(&[lab1, lab2, unnamed], ScorePolicy::Best);
+        assert_eq!(book.assignments, vec!["lab1", "lab2", UNNAMED_ASSIGNMENT]);
+        let scores = &book.row("s1").unwrap().scores;
+        assert_eq!(scores["lab1"].grade, 10.0);
+        assert_eq!(scores["lab2"].grade, 20.0);
+        assert_eq!(scores[UNNAMED_ASSIGNMENT].grade, 30.0);
+    }
+
+    #[test]
+    fn test_data_from_last_submission() {
+        let mut old = attempt("s1", 10, 0);
+        old.data.insert(String::from("name"), String::from("Old Name"));
+        let mut new = attempt("s1", 10, 5);
+        new.data.insert(String::from("name"), String::from("New Name"));
+
+        let book = Gradebook::new("id")
+            .assignment("lab2", &[new], ScorePolicy::Best)
+            .assignment("lab1", &[old], ScorePolicy::Best);
+        assert_eq!(book.row("s1").unwrap().data["name"], "New Name");
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let a = attempt("s1", 70, 0);
+        let mut b = attempt("s1", 75, 1);
+        b.data.insert(String::from("name"), String::from("Smith, John"));
+        let book = Gradebook::new("id")
+            .assignment("lab1", &[a, b], ScorePolicy::Average)
+            .assignment("lab2", &[attempt("s2", 9, 2)], ScorePolicy::Best);
+
+        let records = csv::read_str(&book.to_csv(','), ',').unwrap();
+        assert_eq!(records[0].header, vec!["id", "name", "lab1", "lab1 attempts", "lab2", "lab2 attempts"]);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("name"), Some("Smith, John"));
+        assert_eq!(records[1].get("name"), Some(""));
+        assert_eq!(records[0].get("lab1"), Some("72.5"));
+        assert_eq!(records[0].get("lab1 attempts"), Some("2"));
+        assert_eq!(records[0].get("lab2"), Some(""));
+        assert_eq!(records[1].get("id"), Some("s2"));
+        assert_eq!(records[1].get("lab2"), Some("9"));
+    }
+}
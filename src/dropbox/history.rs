@@ -0,0 +1,304 @@
+//! A record of past submissions, kept on the student's machine
+//!
+//! Every graded submission is appended to a history file when it's submitted, whether
+//! or not the dropbox takes it, so students can see what they got last time, and what
+//! changed since.
+//!
+//! ```no_run
+//! use rubric::dropbox::history;
+//!
+//! // Prints the history and exits if the grader was run with `--history`
+//! if history::print_with_arg("--history") {
+//!     return;
+//! }
+//! ```
+
+// std uses
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::Context;
+use paris::Logger;
+
+// internal uses
+use crate::{Result, HR_TIMESTAMP_FORMAT};
+use crate::dropbox::Submission;
+use crate::dropbox::criterion_result::ResultStatus;
+
+
+/// Where the history is kept, relative to the directory the grader is run in
+pub const DEFAULT_HISTORY_FILE: &str = ".rubric/history.jsonl";
+
+
+/// A criterion whose status changed between two submissions
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    pub func: String,
+    pub name: String,
+    /// The status in the earlier submission, `None` if it wasn't graded
+    pub before: Option<ResultStatus>,
+    /// The status in the later submission, `None` if it wasn't graded
+    pub after: Option<ResultStatus>,
+}
+
+/// Compares the results of two submissions, returning the criteria that changed.
+///
+/// Hidden criteria are left out.
+pub fn diff(before: &Submission, after: &Submission) -> Vec<StatusChange> {
+    let mut changes = Vec::new();
+
+    for result in after.results.iter().filter(|r| !r.hidden) {
+        let old = before.result(&result.func).map(|r| r.status);
+        if old != Some(result.status) {
+            changes.push(StatusChange {
+                func: result.func.clone(),
+                name: result.name.clone(),
+                before: old,
+                after: Some(result.status),
+            });
+        }
+    }
+
+    // Criteria that aren't in the rubric anymore
+    for result in before.results.iter().filter(|r| !r.hidden) {
+        if after.result(&result.func).is_none() {
+            changes.push(StatusChange {
+                func: result.func.clone(),
+                name: result.name.clone(),
+                before: Some(result.status),
+                after: None,
+            });
+        }
+    }
+
+    changes
+}
+
+
+/// A file of past submissions, stored as JSON, one per line.
+#[derive(Debug, Clone)]
+pub struct History {
+    pub path: PathBuf,
+}
+
+impl Default for History {
+    /// The history in [`DEFAULT_HISTORY_FILE`](crate::dropbox::history::DEFAULT_HISTORY_FILE)
+    fn default() -> Self {
+        History::new(DEFAULT_HISTORY_FILE)
+    }
+}
+
+impl History {
+    /// Uses the given file as the history. It will be created when
+    /// the first submission is added.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        History { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Adds a submission to the end of the history
+    pub fn append(&self, sub: &Submission) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context(
+                format!("Couldn't create history directory {}", dir.display())
+            )?;
+        }
+        let mut file = OpenOptions::new().append(true).create(true).open(&self.path)
+            .context(format!("Couldn't open history file {}", self.path.display()))?;
+        // Without the API token or public key
+        writeln!(file, "{}", sub.as_json_value())?;
+        Ok(())
+    }
+
+    /// Every submission in the history, oldest first.
+    ///
+    /// Lines that can't be read are skipped.
+    pub fn entries(&self) -> Vec<Submission> {
+        fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect()
+    }
+
+    /// The most recent submission, if there is one
+    pub fn last(&self) -> Option<Submission> {
+        self.entries().pop()
+    }
+
+    /// What changed between the last two submissions.
+    /// `None` if there haven't been two yet.
+    pub fn last_changes(&self) -> Option<Vec<StatusChange>> {
+        let entries = self.entries();
+        if entries.len() < 2 {
+            return None;
+        }
+        let n = entries.len();
+        Some(diff(&entries[n - 2], &entries[n - 1]))
+    }
+
+    /// Prints every past submission, then what changed between the last two
+    pub fn print(&self) {
+        let mut log = Logger::new();
+        let entries = self.entries();
+
+        if entries.is_empty() {
+            log.info("No past submissions");
+            return;
+        }
+
+        log.info(format!("<bold>{} past submissions</>", entries.len()));
+        for (n, sub) in entries.iter().enumerate() {
+            let late = if sub.late { " <red>(late)</>" } else { "" };
+            log.indent(1).info(format!(
+                "#{}  {}  grade: <bold>{}</>  passed: {}  failed: {}{}",
+                n + 1,
+                sub.time.format(HR_TIMESTAMP_FORMAT),
+                sub.grade,
+                sub.passed.len(),
+                sub.failed.len(),
+                late
+            ));
+        }
+
+        log.newline(1);
+        self.print_changes();
+    }
+
+    /// Prints which criteria changed between the last two submissions
+    pub fn print_changes(&self) {
+        let mut log = Logger::new();
+        let changes = match self.last_changes() {
+            Some(c) => c,
+            None => return,
+        };
+
+        if changes.is_empty() {
+            log.info("Nothing changed since the last submission");
+            return;
+        }
+
+        log.info("<bold>Since the last submission</>");
+        for c in changes {
+            let line = format!("{}: {} -> {}", c.name, describe(c.before), describe(c.after));
+            match c.after {
+                Some(ResultStatus::Passed) => log.indent(1).success(line),
+                Some(_) => log.indent(1).error(line),
+                None => log.indent(1).info(line),
+            };
+        }
+    }
+}
+
+// A status for people to read
+fn describe(status: Option<ResultStatus>) -> &'static str {
+    match status {
+        Some(ResultStatus::Passed) => "passed",
+        Some(ResultStatus::Failed) => "failed",
+        Some(ResultStatus::Deducted) => "deducted",
+        None => "not graded",
+    }
+}
+
+
+/// Prints the default history if the grader was run with the given arg.
+/// Returns true if it was printed, so the grader can stop there.
+///
+/// Like [`open_with_arg`](crate::dropbox::open_with_arg), the arg can be anywhere.
+pub fn print_with_arg(arg: &str) -> bool {
+    if env::args().any(|a| a == arg) {
+        History::default().print();
+        return true;
+    }
+    false
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dropbox::CriterionResult;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    fn result(func: &str, status: ResultStatus, hidden: bool) -> CriterionResult {
+        CriterionResult {
+            func: String::from(func),
+            name: String::from(func),
+            worth: 10,
+            earned: if status == ResultStatus::Passed { 10 } else { 0 },
+            status,
+            message: String::new(),
+            duration: Duration::from_millis(1),
+            hidden,
+            children: Vec::new(),
+        }
+    }
+
+    fn graded(results: Vec<CriterionResult>) -> Submission {
+        let mut sub = Submission::new();
+        sub.grade = results.iter().map(|r| r.earned).sum();
+        sub.results = results;
+        sub
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = TempDir::new("history").unwrap();
+        let history = History::new(dir.path().join("nested").join("history.jsonl"));
+        assert!(history.entries().is_empty());
+        assert!(history.last_changes().is_none());
+
+        let first = graded(vec![result("a", ResultStatus::Failed, false)]);
+        history.append(&first).unwrap();
+        history.append(&graded(vec![])).unwrap();
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, first.id);
+        assert_eq!(entries[0].results, first.results);
+        assert!(history.last_changes().is_some());
+    }
+
+    #[test]
+    fn test_no_secrets() {
+        let dir = TempDir::new("history").unwrap();
+        let history = History::new(dir.path().join("history.jsonl"));
+        let mut sub = graded(vec![]);
+        sub.set_api_token("course token");
+        history.append(&sub).unwrap();
+
+        let saved = std::fs::read_to_string(dir.path().join("history.jsonl")).unwrap();
+        assert!(!saved.contains("course token"));
+        assert_eq!(history.entries()[0].id, sub.id);
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = graded(vec![
+            result("a", ResultStatus::Failed, false),
+            result("b", ResultStatus::Passed, false),
+            result("gone", ResultStatus::Passed, false),
+            result("secret", ResultStatus::Failed, true),
+        ]);
+        let after = graded(vec![
+            result("a", ResultStatus::Passed, false),
+            result("b", ResultStatus::Passed, false),
+            result("new", ResultStatus::Failed, false),
+            result("secret", ResultStatus::Passed, true),
+        ]);
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].func, "a");
+        assert_eq!(changes[0].before, Some(ResultStatus::Failed));
+        assert_eq!(changes[0].after, Some(ResultStatus::Passed));
+        assert_eq!(changes[1].func, "new");
+        assert_eq!(changes[1].before, None);
+        assert_eq!(changes[2].func, "gone");
+        assert_eq!(changes[2].after, None);
+    }
+}
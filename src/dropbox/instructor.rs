@@ -0,0 +1,467 @@
+//! Routes for reading what the dropbox has collected
+//!
+//! These all need the instructor token, see [`auth`](crate::dropbox::auth).
+//!
+//! - `GET /submissions` lists submissions as JSON
+//! - `GET /submissions/<id>` gets one submission as JSON
+//! - `GET /submissions.csv` downloads them as CSV
+//! - `GET /submissions.jsonl` downloads them as JSON, one per line
+//! - `GET /gradebook` gives one grade per student for each assignment, see [`gradebook`](crate::dropbox::gradebook)
+//! - `GET /gradebook.csv` downloads the gradebook as CSV
+//! - `GET /gradebook/<lms>` downloads the gradebook for `canvas`, `moodle`, or `blackboard`
+//!   to import, see [`lms`](crate::dropbox::lms)
+//!
+//! The submission routes all read the same [query](crate::dropbox::query) parameters, so
+//! `/submissions.csv?assignment=lab1&late=true` downloads the late submissions for lab1.
+//!
+//! | Parameter | |
+//! |-----------|-|
+//! | `student` | The student's id, by the dropbox's identity key, or a team member |
+//! | `assignment` | The assignment's name |
+//! | `since` | Received at or after this time, like `2020-05-24` or `2020-05-24 13:00:00` |
+//! | `until` | Received before this time |
+//! | `late` | `true` or `false` |
+//!
+//! The gradebook routes take an `assignment`, to only include that one, and a `policy`
+//! (`best`, `latest`, or `average`) to use instead of each assignment's score policy.
+//!
+//! Submissions read from a CSV file are missing some things, see
+//! [`CsvStore`](crate::dropbox::store::CsvStore). Use the JSON Lines or SQLite store to keep them whole.
+
+// std uses
+use std::io::Cursor;
+
+// external uses
+use rocket::{Request, Response, Route, State};
+use rocket::http::{ContentType, Status};
+use rocket::request::LenientForm;
+use rocket::response::{self, Responder};
+use rocket_contrib::json::Json;
+
+// internal uses
+use crate::rubric::ScorePolicy;
+use crate::rubric::attempt_policy::DEFAULT_IDENTITY_KEY;
+use crate::dropbox::{AssignmentBoxes, DropboxConfig, Gradebook, Rejection, Submission};
+use crate::dropbox::auth::InstructorToken;
+use crate::dropbox::lms::LmsFormat;
+use crate::dropbox::query::{self, SubmissionQuery};
+use crate::dropbox::store;
+
+
+/// The query parameters the routes take
+#[derive(Debug, FromForm)]
+struct QueryParams {
+    student: Option<String>,
+    assignment: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    late: Option<bool>,
+}
+
+impl QueryParams {
+    /// The query these parameters make. Times that can't be read are
+    /// refused with `400 Bad Request`.
+    fn to_query(&self) -> Result<SubmissionQuery, Rejection> {
+        let time = |value: &Option<String>| match value {
+            Some(v) => query::parse_time(v)
+                .map(Some)
+                .map_err(|e| Rejection::new(Status::BadRequest.code, &e.to_string())),
+            None => Ok(None),
+        };
+        Ok(SubmissionQuery {
+            student: self.student.clone(),
+            assignment: self.assignment.clone(),
+            since: time(&self.since)?,
+            until: time(&self.until)?,
+            late: self.late,
+        })
+    }
+}
+
+/// Finds the submissions, or says why it couldn't with `500 Internal Server Error`
+fn find(boxes: &AssignmentBoxes, query: &SubmissionQuery) -> Result<Vec<Submission>, Rejection> {
+    boxes.submissions(query).map_err(|e| {
+        eprintln!("Error: Could not read submissions: {}", e);
+        Rejection::new(Status::InternalServerError.code, &format!("Couldn't read submissions: {}", e))
+    })
+}
+
+/// The gradebook for every assignment, or just the one named. Each assignment's
+/// grades are taken with its score policy, unless another is given, and its
+/// students are found by its identity key.
+///
+/// Assignments the dropbox doesn't serve are `404 Not Found`,
+/// and policies that don't exist `400 Bad Request`.
+fn gradebook(boxes: &AssignmentBoxes, config: &DropboxConfig, assignment: Option<&str>, policy: Option<&str>) -> Result<Gradebook, Rejection> {
+    let policy = match policy {
+        Some(p) => Some(p.parse::<ScorePolicy>().map_err(|e| Rejection::new(Status::BadRequest.code, &e.to_string()))?),
+        None => None,
+    };
+    if let Some(name) = assignment {
+        if boxes.default.is_none() && !boxes.by_name.contains_key(name) {
+            return Err(Rejection::new(Status::NotFound.code, &format!("This dropbox doesn't have an assignment named {}", name)));
+        }
+    }
+    let unreadable = |e: crate::Error| {
+        eprintln!("Error: Could not read submissions: {}", e);
+        Rejection::new(Status::InternalServerError.code, &format!("Couldn't read submissions: {}", e))
+    };
+
+    let mut book = Gradebook::new(config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY));
+    if let Some(default) = &boxes.default {
+        let subs = default.submissions().map_err(unreadable)?;
+        let subs: Vec<Submission> = match assignment {
+            Some(name) => subs.into_iter().filter(|s| s.assignment.as_deref() == Some(name)).collect(),
+            None => subs,
+        };
+        book = book.submissions(&subs, policy.or(config.score_policy).unwrap_or_default());
+    }
+    // In the order they were given, not the map's
+    for name in config.assignment_names() {
+        let assignment_box = match boxes.by_name.get(name) {
+            Some(b) if assignment.map_or(true, |a| a == name) => b,
+            _ => continue,
+        };
+        let subs = assignment_box.submissions().map_err(unreadable)?;
+        let policy = policy.or(assignment_box.config.score_policy).unwrap_or_default();
+        let identity_key = assignment_box.config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY);
+        book = book.assignment_by(name, &subs, policy, identity_key);
+    }
+    Ok(book)
+}
+
+/// A file to download, named by the `Content-Disposition` header
+pub struct Download {
+    filename: String,
+    content_type: ContentType,
+    body: String,
+}
+
+impl Download {
+    /// The body, downloaded as a file with this name
+    pub fn new(filename: &str, content_type: ContentType, body: String) -> Self {
+        Download { filename: String::from(filename), content_type, body }
+    }
+}
+
+impl<'r> Responder<'r> for Download {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        Response::build()
+            .header(self.content_type)
+            .raw_header("Content-Disposition", format!("attachment; filename=\"{}\"", self.filename))
+            .sized_body(Cursor::new(self.body))
+            .ok()
+    }
+}
+
+
+/// Lists the submissions that match the query, oldest first
+#[get("/submissions?<params..>")]
+fn list_submissions(_token: InstructorToken, boxes: State<AssignmentBoxes>, params: LenientForm<QueryParams>) -> Result<Json<Vec<Submission>>, Rejection> {
+    Ok(Json(find(boxes.inner(), &params.to_query()?)?))
+}
+
+/// Gets one submission by its id, or `404 Not Found`.
+///
+/// Team submissions are stored once for each member, this is the first.
+/// Ids from a CSV file are only unique in that file, so give the assignment too.
+#[get("/submissions/<id>?<assignment>")]
+fn get_submission(_token: InstructorToken, boxes: State<AssignmentBoxes>, id: String, assignment: Option<String>) -> Result<Json<Submission>, Rejection> {
+    let query = SubmissionQuery { assignment, ..SubmissionQuery::new() };
+    find(boxes.inner(), &query)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .map(Json)
+        .ok_or_else(|| Rejection::new(Status::NotFound.code, &format!("There's no submission {}", id)))
+}
+
+/// Downloads the submissions that match the query as CSV, with every column any of them has
+#[get("/submissions.csv?<params..>")]
+fn download_csv(_token: InstructorToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, params: LenientForm<QueryParams>) -> Result<Download, Rejection> {
+    let subs = find(boxes.inner(), &params.to_query()?)?;
+    let csv = store::to_csv(&subs, config.csv_delimiter)
+        .map_err(|e| Rejection::new(Status::InternalServerError.code, &e.to_string()))?;
+    Ok(Download::new("submissions.csv", ContentType::CSV, csv))
+}
+
+/// Downloads the submissions that match the query as JSON, one per line
+#[get("/submissions.jsonl?<params..>")]
+fn download_json_lines(_token: InstructorToken, boxes: State<AssignmentBoxes>, params: LenientForm<QueryParams>) -> Result<Download, Rejection> {
+    let subs = find(boxes.inner(), &params.to_query()?)?;
+    let mut lines = store::json_lines(&subs)
+        .map_err(|e| Rejection::new(Status::InternalServerError.code, &e.to_string()))?;
+    if !lines.is_empty() {
+        lines.push('\n');
+    }
+    Ok(Download::new("submissions.jsonl", ContentType::new("application", "x-ndjson"), lines))
+}
+
+/// One grade per student for each assignment, as JSON
+#[get("/gradebook?<assignment>&<policy>")]
+fn get_gradebook(_token: InstructorToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, assignment: Option<String>, policy: Option<String>) -> Result<Json<Gradebook>, Rejection> {
+    Ok(Json(gradebook(boxes.inner(), config.inner(), assignment.as_deref(), policy.as_deref())?))
+}
+
+/// Downloads the gradebook as CSV, one row per student
+#[get("/gradebook.csv?<assignment>&<policy>")]
+fn download_gradebook(_token: InstructorToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, assignment: Option<String>, policy: Option<String>) -> Result<Download, Rejection> {
+    let book = gradebook(boxes.inner(), config.inner(), assignment.as_deref(), policy.as_deref())?;
+    Ok(Download::new("gradebook.csv", ContentType::CSV, book.to_csv(config.csv_delimiter)))
+}
+
+/// Downloads the gradebook for an LMS to import, with the columns the dropbox was given
+/// for it. LMSs it can't write for are `404 Not Found`.
+#[get("/gradebook/<lms>?<assignment>&<policy>")]
+fn download_lms_gradebook(_token: InstructorToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, lms: String, assignment: Option<String>, policy: Option<String>) -> Result<Download, Rejection> {
+    let format: LmsFormat = lms.trim_end_matches(".csv").parse()
+        .map_err(|e: crate::Error| Rejection::new(Status::NotFound.code, &e.to_string()))?;
+    let export = config.export_for(format);
+    let book = gradebook(boxes.inner(), config.inner(), assignment.as_deref(), policy.as_deref())?;
+    Ok(Download::new(&export.filename(), ContentType::CSV, export.to_csv(&book)))
+}
+
+/// Every instructor route, to mount at `/`
+pub fn routes() -> Vec<Route> {
+    routes![
+        list_submissions,
+        get_submission,
+        download_csv,
+        download_json_lines,
+        get_gradebook,
+        download_gradebook,
+        download_lms_gradebook
+    ]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::{Client, LocalResponse};
+    use rocket::http::Header;
+    use tempdir::TempDir;
+    use crate::dropbox::{auth, csv, new_rocket, Assignment, StoreKind};
+    use crate::dropbox::lms::LmsExport;
+
+    fn client(dir: &TempDir, config: DropboxConfig) -> Client {
+        let config = config.results_dir(dir.path()).instructor_token("teacher");
+        Client::new(new_rocket(config)).expect("valid rocket instance")
+    }
+
+    fn submit(client: &Client, route: &str, student: &str, late: bool) -> Submission {
+        let mut sub = Submission::new();
+        sub.data.insert(String::from("id"), String::from(student));
+        sub.late = late;
+        let resp = client.post(route.to_string())
+            .body(serde_json::to_string(&sub).unwrap())
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Accepted);
+        sub
+    }
+
+    fn get<'c>(client: &'c Client, uri: &str) -> LocalResponse<'c> {
+        client.get(uri.to_string())
+            .header(Header::new("Authorization", auth::bearer_header("teacher")))
+            .dispatch()
+    }
+
+    fn list(client: &Client, uri: &str) -> Vec<Submission> {
+        let mut resp = get(client, uri);
+        assert_eq!(resp.status(), Status::Ok);
+        serde_json::from_str(&resp.body_string().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_needs_instructor_token() {
+        let dir = TempDir::new("instructor").unwrap();
+        let client = client(&dir, DropboxConfig::new(8080));
+        let uris = [
+            "/submissions", "/submissions/abc", "/submissions.csv", "/submissions.jsonl",
+            "/gradebook", "/gradebook.csv", "/gradebook/canvas",
+        ];
+        for uri in &uris {
+            assert_eq!(client.get(*uri).dispatch().status(), Status::Unauthorized);
+            let resp = client.get(*uri)
+                .header(Header::new("Authorization", auth::bearer_header("student")))
+                .dispatch();
+            assert_eq!(resp.status(), Status::Forbidden);
+        }
+    }
+
+    #[test]
+    fn test_list_submissions() {
+        let dir = TempDir::new("instructor").unwrap();
+        let client = client(&dir, DropboxConfig::new(8080).store(StoreKind::JsonLines));
+        let first = submit(&client, "/submit", "s1", false);
+        submit(&client, "/submit", "s2", true);
+        submit(&client, "/submit", "s1", true);
+
+        let all = list(&client, "/submissions");
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].id, first.id);
+        assert!(all[0].received_at.is_some());
+
+        assert_eq!(list(&client, "/submissions?student=s1").len(), 2);
+        assert_eq!(list(&client, "/submissions?student=s1&late=true").len(), 1);
+        assert_eq!(list(&client, "/submissions?since=2000-01-01").len(), 3);
+        assert_eq!(list(&client, "/submissions?until=2000-01-01%2000:00:00").len(), 0);
+        assert_eq!(list(&client, "/submissions?student=s3").len(), 0);
+        // Parameters it doesn't know are ignored
+        assert_eq!(list(&client, "/submissions?page=2").len(), 3);
+
+        let mut resp = get(&client, "/submissions?since=yesterday");
+        assert_eq!(resp.status(), Status::BadRequest);
+        let rejection: Rejection = serde_json::from_str(&resp.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains("yesterday"));
+    }
+
+    #[test]
+    fn test_get_submission() {
+        let dir = TempDir::new("instructor").unwrap();
+        let client = client(&dir, DropboxConfig::new(8080).store(StoreKind::JsonLines));
+        let sub = submit(&client, "/submit", "s1", false);
+
+        let mut resp = get(&client, &format!("/submissions/{}", sub.id));
+        assert_eq!(resp.status(), Status::Ok);
+        let found: Submission = serde_json::from_str(&resp.body_string().unwrap()).unwrap();
+        assert_eq!(found.id, sub.id);
+        assert_eq!(found.data, sub.data);
+
+        assert_eq!(get(&client, "/submissions/not-an-id").status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let dir = TempDir::new("instructor").unwrap();
+        let config = DropboxConfig::new(8080)
+            .assignment(Assignment::new("lab1"))
+            .assignment(Assignment::new("lab2"));
+        let client = client(&dir, config);
+        submit(&client, "/submit/lab1", "s1", false);
+        submit(&client, "/submit/lab2", "s1", false);
+        submit(&client, "/submit/lab2", "s2", false);
+
+        // Read back from each assignment's CSV file
+        let lab2 = list(&client, "/submissions?assignment=lab2");
+        assert_eq!(lab2.len(), 2);
+        assert!(lab2.iter().all(|s| s.assignment.as_deref() == Some("lab2")));
+        assert_eq!(list(&client, "/submissions?student=s1").len(), 2);
+
+        // CSV ids are rows, so they need the assignment
+        let resp = get(&client, "/submissions/row-2?assignment=lab2");
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(get(&client, "/submissions/row-2?assignment=lab1").status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_downloads() {
+        let dir = TempDir::new("instructor").unwrap();
+        let client = client(&dir, DropboxConfig::new(8080).store(StoreKind::JsonLines));
+        submit(&client, "/submit", "s1", false);
+        submit(&client, "/submit", "s2", true);
+
+        let mut resp = get(&client, "/submissions.csv");
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.content_type(), Some(ContentType::CSV));
+        assert!(resp.headers().get_one("Content-Disposition").unwrap().contains("submissions.csv"));
+        let records = csv::read_str(&resp.body_string().unwrap(), ',').unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].get("id"), Some("s2"));
+
+        let mut resp = get(&client, "/submissions.jsonl?late=true");
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.body_string().unwrap();
+        let subs: Vec<Submission> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(subs.len(), 1);
+        assert_eq!(subs[0].data.get("id").map(|s| s.as_str()), Some("s2"));
+    }
+
+    #[test]
+    fn test_gradebook() {
+        let dir = TempDir::new("instructor").unwrap();
+        let config = DropboxConfig::new(8080)
+            .assignment(Assignment::new("lab1").score_policy(ScorePolicy::Best))
+            .assignment(Assignment::new("lab2"));
+        let client = client(&dir, config);
+        let post = |route: &str, student: &str, grade: isize| {
+            let mut sub = Submission::new();
+            sub.data.insert(String::from("id"), String::from(student));
+            sub.grade = grade;
+            let resp = client.post(route.to_string())
+                .body(serde_json::to_string(&sub).unwrap())
+                .header(Header::new("Content-Type", "application/json"))
+                .dispatch();
+            assert_eq!(resp.status(), Status::Accepted);
+        };
+        post("/submit/lab1", "s1", 90);
+        post("/submit/lab1", "s1", 70);
+        post("/submit/lab2", "s1", 50);
+        post("/submit/lab2", "s1", 60);
+        post("/submit/lab2", "s2", 100);
+
+        let book = |uri: &str| -> Gradebook {
+            let mut resp = get(&client, uri);
+            assert_eq!(resp.status(), Status::Ok);
+            serde_json::from_str(&resp.body_string().unwrap()).unwrap()
+        };
+
+        // lab1 takes the best, lab2 the latest
+        let all = book("/gradebook");
+        assert_eq!(all.assignments, vec!["lab1", "lab2"]);
+        assert_eq!(all.rows.len(), 2);
+        let s1 = all.row("s1").unwrap();
+        assert_eq!(s1.scores["lab1"].grade, 90.0);
+        assert_eq!(s1.scores["lab1"].attempts, 2);
+        assert_eq!(s1.scores["lab2"].grade, 60.0);
+
+        let averages = book("/gradebook?assignment=lab2&policy=average");
+        assert_eq!(averages.assignments, vec!["lab2"]);
+        assert_eq!(averages.row("s1").unwrap().scores["lab2"].grade, 55.0);
+
+        assert_eq!(get(&client, "/gradebook?policy=worst").status(), Status::BadRequest);
+        assert_eq!(get(&client, "/gradebook?assignment=lab3").status(), Status::NotFound);
+
+        let mut resp = get(&client, "/gradebook.csv");
+        assert_eq!(resp.status(), Status::Ok);
+        let records = csv::read_str(&resp.body_string().unwrap(), ',').unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("lab1"), Some("90"));
+        assert_eq!(records[1].get("id"), Some("s2"));
+        assert_eq!(records[1].get("lab1"), Some(""));
+        assert_eq!(records[1].get("lab2 attempts"), Some("1"));
+    }
+
+    #[test]
+    fn test_lms_gradebook() {
+        let dir = TempDir::new("instructor").unwrap();
+        let config = DropboxConfig::new(8080)
+            .store(StoreKind::JsonLines)
+            .lms_export(LmsExport::new(LmsFormat::Canvas).column("Student", "name"));
+        let client = client(&dir, config);
+        let mut sub = Submission::new();
+        sub.data.insert(String::from("id"), String::from("s1"));
+        sub.data.insert(String::from("name"), String::from("Jo Smith"));
+        sub.grade = 88;
+        let resp = client.post("/submit")
+            .body(serde_json::to_string(&sub).unwrap())
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Accepted);
+
+        let mut resp = get(&client, "/gradebook/canvas");
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one("Content-Disposition").unwrap().contains("canvas_grades.csv"));
+        let records = csv::read_str(&resp.body_string().unwrap(), ',').unwrap();
+        assert_eq!(records[0].get("Student"), Some("Jo Smith"));
+        assert_eq!(records[0].get("SIS User ID"), Some("s1"));
+        assert_eq!(records[0].get("grade"), Some("88"));
+
+        let mut resp = get(&client, "/gradebook/blackboard.csv");
+        assert_eq!(resp.status(), Status::Ok);
+        let records = csv::read_str(&resp.body_string().unwrap(), ',').unwrap();
+        assert_eq!(records[0].get("Username"), Some("s1"));
+
+        assert_eq!(get(&client, "/gradebook/sakai").status(), Status::NotFound);
+    }
+}
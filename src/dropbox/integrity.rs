@@ -0,0 +1,397 @@
+//! Academic integrity checks over recorded submissions.
+//!
+//! This reads a dropbox's `submissions.csv` and reports things worth a closer look:
+//! students submitting from the same machine, different students with identical data
+//! (like the same repository url), and bursts of submissions from one machine.
+//!
+//! None of these prove anything on their own. Lab machines are shared, and some
+//! students really do submit 10 times in a minute. It's up to you what to make of it.
+//!
+//! ```no_run
+//! use rubric::dropbox::integrity::{IntegrityOptions, IntegrityReport};
+//!
+//! let options = IntegrityOptions::default().compare("repo_url");
+//! let report = IntegrityReport::from_file("submissions.csv", &options).unwrap();
+//! report.write_csv("integrity.csv").unwrap();
+//! ```
+
+// std uses
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+// external uses
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::{Serialize, Deserialize};
+
+// internal uses
+use crate::{Result, TIMESTAMP_FORMAT};
+use crate::dropbox::csv;
+
+
+/// What an [`IntegrityReport`] looks for.
+#[derive(Debug, Clone)]
+pub struct IntegrityOptions {
+    /// The column that identifies a student. Defaults to `id`.
+    pub id_key: String,
+    /// Columns that shouldn't be the same for different students.
+    /// Defaults to `git_remote` from the fingerprint.
+    pub compare: Vec<String>,
+    /// How many submissions from one machine count as a burst
+    pub burst_size: usize,
+    /// How close together those submissions have to be
+    pub burst_window: Duration,
+    /// What the results file is separated by. Defaults to a comma,
+    /// set it to match the dropbox's `csv_delimiter`.
+    pub delimiter: char,
+}
+
+impl Default for IntegrityOptions {
+    fn default() -> Self {
+        IntegrityOptions {
+            id_key: String::from("id"),
+            compare: vec![String::from("git_remote")],
+            burst_size: 5,
+            burst_window: Duration::minutes(10),
+            delimiter: csv::DEFAULT_DELIMITER,
+        }
+    }
+}
+
+impl IntegrityOptions {
+    /// Sets the column that identifies a student
+    pub fn id_key(mut self, key: &str) -> Self {
+        self.id_key = String::from(key);
+        self
+    }
+
+    /// Adds a column that shouldn't be the same for different students
+    pub fn compare(mut self, key: &str) -> Self {
+        self.compare.push(String::from(key));
+        self
+    }
+
+    /// Reports `size` or more submissions from one machine within `minutes` of each other
+    pub fn bursts(mut self, size: usize, minutes: i64) -> Self {
+        self.burst_size = size;
+        self.burst_window = Duration::minutes(minutes);
+        self
+    }
+
+    /// Sets what the results file is separated by
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+
+/// Different students who submitted from the same machine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SharedMachine {
+    /// The machine id, or the hostname if there isn't one
+    pub machine: String,
+    pub students: Vec<String>,
+    pub submissions: usize,
+}
+
+/// Different students with the same value in a column
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SharedValue {
+    pub key: String,
+    pub value: String,
+    pub students: Vec<String>,
+}
+
+/// Many submissions from one machine in a short time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Burst {
+    pub machine: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub submissions: usize,
+    pub students: Vec<String>,
+}
+
+/// The findings from a set of submissions
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct IntegrityReport {
+    pub shared_machines: Vec<SharedMachine>,
+    pub shared_values: Vec<SharedValue>,
+    pub bursts: Vec<Burst>,
+}
+
+
+/// Reads a results file into one map per submission, from column to value.
+///
+/// Columns are named by the header on the first line.
+pub fn read_records<P: AsRef<Path>>(path: P, delimiter: char) -> Result<Vec<HashMap<String, String>>> {
+    let records = csv::read_file(path, delimiter)?;
+    Ok(records.iter().map(|r| r.to_map()).collect())
+}
+
+
+// Which machine a record came from, if the fingerprint says
+fn machine(record: &HashMap<String, String>) -> Option<String> {
+    ["machine_id", "hostname"].iter()
+        .filter_map(|k| record.get(*k))
+        .find(|v| !v.is_empty())
+        .cloned()
+}
+
+// When the dropbox received the record, or when it was submitted
+fn time(record: &HashMap<String, String>) -> Option<DateTime<FixedOffset>> {
+    ["received", "time"].iter()
+        .filter_map(|k| record.get(*k))
+        .find_map(|v| DateTime::parse_from_str(v, TIMESTAMP_FORMAT).ok())
+}
+
+// Groups students by a value, keeping the values more than one student shares
+fn shared(pairs: impl Iterator<Item = (String, String)>) -> BTreeMap<String, BTreeSet<String>> {
+    let mut groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (value, student) in pairs {
+        groups.entry(value).or_default().insert(student);
+    }
+    groups.into_iter().filter(|(_, students)| students.len() > 1).collect()
+}
+
+impl IntegrityReport {
+    /// Analyzes the submissions in a results file
+    pub fn from_file<P: AsRef<Path>>(path: P, options: &IntegrityOptions) -> Result<Self> {
+        Ok(IntegrityReport::analyze(&read_records(path, options.delimiter)?, options))
+    }
+
+    /// Analyzes submission records, as read by [`read_records`]
+    pub fn analyze(records: &[HashMap<String, String>], options: &IntegrityOptions) -> Self {
+        let student = |r: &HashMap<String, String>| {
+            r.get(&options.id_key).filter(|id| !id.is_empty()).cloned()
+        };
+
+        // Students sharing a machine
+        let shared_machines = shared(
+            records.iter().filter_map(|r| Some((machine(r)?, student(r)?)))
+        )
+        .into_iter()
+        .map(|(machine, students)| {
+            let submissions = records.iter()
+                .filter(|r| self::machine(r).as_ref() == Some(&machine))
+                .count();
+            SharedMachine { machine, students: students.into_iter().collect(), submissions }
+        })
+        .collect();
+
+        // Students sharing a value they shouldn't
+        let mut shared_values = Vec::new();
+        for key in &options.compare {
+            let pairs = records.iter().filter_map(|r| {
+                let value = r.get(key).filter(|v| !v.is_empty())?;
+                Some((value.clone(), student(r)?))
+            });
+            for (value, students) in shared(pairs) {
+                shared_values.push(SharedValue {
+                    key: key.clone(),
+                    value,
+                    students: students.into_iter().collect(),
+                });
+            }
+        }
+
+        // Bursts of submissions from one machine
+        let mut by_machine: BTreeMap<String, Vec<(DateTime<FixedOffset>, Option<String>)>> = BTreeMap::new();
+        for r in records {
+            if let (Some(m), Some(t)) = (machine(r), time(r)) {
+                by_machine.entry(m).or_default().push((t, student(r)));
+            }
+        }
+
+        let mut bursts = Vec::new();
+        let size = options.burst_size.max(1);
+        for (machine, mut times) in by_machine {
+            times.sort_by_key(|(t, _)| *t);
+            let mut start = 0;
+            while start + size <= times.len() {
+                if times[start + size - 1].0 - times[start].0 > options.burst_window {
+                    start += 1;
+                    continue;
+                }
+                // Extend the burst as long as submissions keep coming
+                let mut end = start + size - 1;
+                while end + 1 < times.len() && times[end + 1].0 - times[end + 1 - size + 1].0 <= options.burst_window {
+                    end += 1;
+                }
+                let students: BTreeSet<String> = times[start..=end].iter()
+                    .filter_map(|(_, s)| s.clone())
+                    .collect();
+                bursts.push(Burst {
+                    machine: machine.clone(),
+                    start: times[start].0,
+                    end: times[end].0,
+                    submissions: end - start + 1,
+                    students: students.into_iter().collect(),
+                });
+                start = end + 1;
+            }
+        }
+
+        IntegrityReport { shared_machines, shared_values, bursts }
+    }
+
+    /// Returns true if nothing was found
+    pub fn is_empty(&self) -> bool {
+        self.shared_machines.is_empty() && self.shared_values.is_empty() && self.bursts.is_empty()
+    }
+
+    /// The report as pretty JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The report as CSV, one finding per line.
+    ///
+    /// Students are separated by `;`.
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec![String::from("finding,key,value,students,submissions,start,end")];
+        for m in &self.shared_machines {
+            lines.push(csv::write_row(&[
+                "shared_machine", "machine", &m.machine, &m.students.join(";"),
+                &m.submissions.to_string(), "", "",
+            ], ','));
+        }
+        for v in &self.shared_values {
+            lines.push(csv::write_row(&[
+                "shared_value", &v.key, &v.value, &v.students.join(";"), "", "", "",
+            ], ','));
+        }
+        for b in &self.bursts {
+            lines.push(csv::write_row(&[
+                "burst", "machine", &b.machine, &b.students.join(";"), &b.submissions.to_string(),
+                &b.start.format(TIMESTAMP_FORMAT).to_string(), &b.end.format(TIMESTAMP_FORMAT).to_string(),
+            ], ','));
+        }
+        lines.join("\n")
+    }
+
+    /// Writes the report to a file as CSV
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_csv() + "\n")?;
+        Ok(())
+    }
+
+    /// Writes the report to a file as JSON
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    const HEADER: &str = "time,received,flags,late,grade,passed,failed,id,repo_url,platform,hostname,machine_id";
+
+    fn record(id: &str, minute: u32, repo: &str, machine: &str) -> String {
+        format!(
+            "2020-05-01 Fri 10:{:02}:00 -05:00,2020-05-01 Fri 10:{:02}:00 -05:00,,false,10,,,{},{},linux,host,{}",
+            minute, minute, id, repo, machine
+        )
+    }
+
+    fn records(lines: &[String]) -> Vec<HashMap<String, String>> {
+        let dir = TempDir::new("integrity").unwrap();
+        let path = dir.path().join("submissions.csv");
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "{}", HEADER).unwrap();
+        for l in lines {
+            writeln!(file, "{}", l).unwrap();
+        }
+        read_records(&path, ',').unwrap()
+    }
+
+    #[test]
+    fn test_read_records() {
+        let recs = records(&[record("s1", 0, "repo1", "m1")]);
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0]["id"], "s1");
+        assert_eq!(recs[0]["machine_id"], "m1");
+    }
+
+    #[test]
+    fn test_delimiter() {
+        let dir = TempDir::new("integrity").unwrap();
+        let path = dir.path().join("submissions.csv");
+        let lines = [record("s1", 0, "repo1", "m1"), record("s2", 30, "repo2", "m1")];
+        let semicolons: Vec<String> = std::iter::once(HEADER.to_string())
+            .chain(lines.iter().cloned())
+            .map(|l| l.replace(',', ";"))
+            .collect();
+        fs::write(&path, semicolons.join("\n")).unwrap();
+
+        let options = IntegrityOptions::default().delimiter(';');
+        let report = IntegrityReport::from_file(&path, &options).unwrap();
+        assert_eq!(report.shared_machines[0].students, vec!["s1", "s2"]);
+        assert!(IntegrityReport::from_file(&path, &IntegrityOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shared_machine() {
+        let recs = records(&[
+            record("s1", 0, "repo1", "m1"),
+            record("s2", 30, "repo2", "m1"),
+            record("s3", 59, "repo3", "m2"),
+        ]);
+        let report = IntegrityReport::analyze(&recs, &IntegrityOptions::default());
+        assert_eq!(report.shared_machines.len(), 1);
+        assert_eq!(report.shared_machines[0].machine, "m1");
+        assert_eq!(report.shared_machines[0].students, vec!["s1", "s2"]);
+        assert!(report.bursts.is_empty());
+    }
+
+    #[test]
+    fn test_shared_value() {
+        let recs = records(&[
+            record("s1", 0, "repo1", "m1"),
+            record("s2", 30, "repo1", "m2"),
+            record("s2", 31, "repo1", "m2"),
+        ]);
+        let options = IntegrityOptions::default().compare("repo_url");
+        let report = IntegrityReport::analyze(&recs, &options);
+        assert_eq!(report.shared_values.len(), 1);
+        assert_eq!(report.shared_values[0].value, "repo1");
+        assert_eq!(report.shared_values[0].students, vec!["s1", "s2"]);
+    }
+
+    #[test]
+    fn test_bursts() {
+        let recs = records(&[
+            record("s1", 0, "repo1", "m1"),
+            record("s1", 1, "repo1", "m1"),
+            record("s1", 2, "repo1", "m1"),
+            record("s1", 40, "repo1", "m1"),
+        ]);
+        let options = IntegrityOptions::default().bursts(3, 5);
+        let report = IntegrityReport::analyze(&recs, &options);
+        assert_eq!(report.bursts.len(), 1);
+        assert_eq!(report.bursts[0].submissions, 3);
+        assert!(report.shared_machines.is_empty());
+    }
+
+    #[test]
+    fn test_output() {
+        let recs = records(&[
+            record("s1", 0, "repo1", "m1"),
+            record("s2", 30, "repo2", "m1"),
+        ]);
+        let report = IntegrityReport::analyze(&recs, &IntegrityOptions::default());
+        assert!(!report.is_empty());
+        let csv = report.to_csv();
+        assert!(csv.contains("shared_machine,machine,m1,s1;s2,2,,"));
+        let json = report.to_json().unwrap();
+        let back: IntegrityReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, report);
+    }
+}
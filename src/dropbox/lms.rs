@@ -0,0 +1,269 @@
+//! Gradebooks an LMS can import
+//!
+//! Canvas, Moodle, and Blackboard each import grades from CSV, but want different
+//! columns to find the student by. An [`LmsExport`] writes a [`Gradebook`] in one of
+//! their formats, filling the student columns from the data in their submissions.
+//!
+//! | Format | Student columns, and the data key they come from |
+//! |--------|--------------------------------------------------|
+//! | Canvas | `Student` (`name`), `ID`, `SIS User ID` (`id`), `SIS Login ID`, `Section` |
+//! | Moodle | `First name` (`first_name`), `Last name` (`last_name`), `ID number` (`id`), `Email address` (`email`) |
+//! | Blackboard | `Last Name` (`last_name`), `First Name` (`first_name`), `Username` (`id`), `Student ID` |
+//!
+//! Columns without a key are left empty. Each assignment is a column after them,
+//! named after the assignment unless it's given another name.
+//!
+//! ```rust
+//! use rubric::dropbox::lms::{LmsExport, LmsFormat};
+//!
+//! let export = LmsExport::new(LmsFormat::Canvas)
+//!     // Canvas finds students by their login instead
+//!     .column("SIS User ID", "")
+//!     .column("SIS Login ID", "email")
+//!     // The column Canvas already has for the assignment
+//!     .assignment_column("lab1", "Lab 1 (12345)");
+//! assert_eq!(export.header(&["lab1"]), vec!["Student", "ID", "SIS User ID", "SIS Login ID", "Section", "Lab 1 (12345)"]);
+//! ```
+
+// std uses
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// external uses
+use anyhow::anyhow;
+
+// internal uses
+use crate::dropbox::Gradebook;
+use crate::dropbox::csv;
+
+
+/// The LMS gradebooks that can be exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LmsFormat {
+    Canvas,
+    Moodle,
+    Blackboard,
+}
+
+impl LmsFormat {
+    /// The student columns this LMS reads, and the data keys they come from by default
+    pub fn default_columns(&self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            LmsFormat::Canvas => vec![
+                ("Student", "name"),
+                ("ID", ""),
+                ("SIS User ID", "id"),
+                ("SIS Login ID", ""),
+                ("Section", ""),
+            ],
+            LmsFormat::Moodle => vec![
+                ("First name", "first_name"),
+                ("Last name", "last_name"),
+                ("ID number", "id"),
+                ("Email address", "email"),
+            ],
+            LmsFormat::Blackboard => vec![
+                ("Last Name", "last_name"),
+                ("First Name", "first_name"),
+                ("Username", "id"),
+                ("Student ID", ""),
+            ],
+        }
+    }
+}
+
+impl FromStr for LmsFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "canvas" => Ok(LmsFormat::Canvas),
+            "moodle" => Ok(LmsFormat::Moodle),
+            "blackboard" => Ok(LmsFormat::Blackboard),
+            _ => Err(anyhow!("Unknown LMS '{}', expected canvas, moodle, or blackboard", s)),
+        }
+    }
+}
+
+impl fmt::Display for LmsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LmsFormat::Canvas => "canvas",
+            LmsFormat::Moodle => "moodle",
+            LmsFormat::Blackboard => "blackboard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+/// How to write a gradebook for an LMS to import
+#[derive(Debug, Clone, PartialEq)]
+pub struct LmsExport {
+    /// The LMS
+    pub format: LmsFormat,
+    /// The student columns, and the data key each is filled from.
+    /// An empty key leaves the column empty.
+    pub columns: Vec<(String, String)>,
+    /// The column each assignment's grades go in, by assignment.
+    /// Assignments that aren't here use their name.
+    pub assignment_columns: HashMap<String, String>,
+}
+
+impl LmsExport {
+    /// An export with the LMS's usual columns
+    pub fn new(format: LmsFormat) -> Self {
+        LmsExport {
+            format,
+            columns: format.default_columns().into_iter()
+                .map(|(column, key)| (String::from(column), String::from(key)))
+                .collect(),
+            assignment_columns: HashMap::new(),
+        }
+    }
+
+    /// Fill a column from this data key. Columns the LMS doesn't usually
+    /// have are added after the others. An empty key leaves it empty.
+    pub fn column(mut self, column: &str, key: &str) -> Self {
+        match self.columns.iter_mut().find(|(c, _)| c == column) {
+            Some((_, k)) => *k = String::from(key),
+            None => self.columns.push((String::from(column), String::from(key))),
+        }
+        self
+    }
+
+    /// Put an assignment's grades in a column with this name, like
+    /// `Lab 1 (12345)` to match a column that's already in Canvas
+    pub fn assignment_column(mut self, assignment: &str, column: &str) -> Self {
+        self.assignment_columns.insert(String::from(assignment), String::from(column));
+        self
+    }
+
+    /// The header for a gradebook with these assignments
+    pub fn header<S: AsRef<str>>(&self, assignments: &[S]) -> Vec<String> {
+        let mut header: Vec<String> = self.columns.iter().map(|(c, _)| c.clone()).collect();
+        for a in assignments {
+            let a = a.as_ref();
+            header.push(self.assignment_columns.get(a).cloned().unwrap_or_else(|| String::from(a)));
+        }
+        header
+    }
+
+    /// The name of the file to download
+    pub fn filename(&self) -> String {
+        format!("{}_grades.csv", self.format)
+    }
+
+    /// Writes the gradebook in the LMS's format, one row per student. Grades
+    /// for assignments a student didn't submit are empty.
+    ///
+    /// The student's identity is also available by the gradebook's identity key,
+    /// even if it isn't in their data.
+    pub fn to_csv(&self, book: &Gradebook) -> String {
+        let mut lines = vec![csv::write_row(&self.header(&book.assignments), ',')];
+        for row in &book.rows {
+            let mut values: Vec<String> = self.columns.iter()
+                .map(|(_, key)| match row.data.get(key) {
+                    Some(value) => value.clone(),
+                    None if key == &book.identity_key => row.student.clone(),
+                    None => String::new(),
+                })
+                .collect();
+            for a in &book.assignments {
+                values.push(row.scores.get(a).map(|s| s.grade.to_string()).unwrap_or_default());
+            }
+            lines.push(csv::write_row(&values, ','));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::dropbox::Submission;
+    use crate::rubric::ScorePolicy;
+
+    fn book() -> Gradebook {
+        let mut jo = Submission::from_data(data! {
+            "id" => "s1",
+            "name" => "Smith, Jo",
+            "first_name" => "Jo",
+            "last_name" => "Smith",
+            "email" => "jo@example.edu"
+        });
+        jo.grade = 90;
+        let mut al = Submission::from_data(data! { "id" => "s2" });
+        al.grade = 75;
+
+        Gradebook::new("id")
+            .assignment("lab1", &[jo.clone()], ScorePolicy::Latest)
+            .assignment("lab2", &[jo, al], ScorePolicy::Latest)
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("Canvas".parse::<LmsFormat>().unwrap(), LmsFormat::Canvas);
+        assert_eq!(LmsFormat::Blackboard.to_string().parse::<LmsFormat>().unwrap(), LmsFormat::Blackboard);
+        assert!("sakai".parse::<LmsFormat>().is_err());
+    }
+
+    #[test]
+    fn test_canvas() {
+        let export = LmsExport::new(LmsFormat::Canvas);
+        let records = csv::read_str(&export.to_csv(&book()), ',').unwrap();
+        assert_eq!(records[0].header, vec!["Student", "ID", "SIS User ID", "SIS Login ID", "Section", "lab1", "lab2"]);
+        assert_eq!(records[0].get("Student"), Some("Smith, Jo"));
+        assert_eq!(records[0].get("SIS User ID"), Some("s1"));
+        assert_eq!(records[0].get("ID"), Some(""));
+        assert_eq!(records[0].get("lab1"), Some("90"));
+        assert_eq!(records[1].get("Student"), Some(""));
+        assert_eq!(records[1].get("lab1"), Some(""));
+        assert_eq!(records[1].get("lab2"), Some("75"));
+        assert_eq!(export.filename(), "canvas_grades.csv");
+    }
+
+    #[test]
+    fn test_moodle_and_blackboard() {
+        let records = csv::read_str(&LmsExport::new(LmsFormat::Moodle).to_csv(&book()), ',').unwrap();
+        assert_eq!(records[0].get("First name"), Some("Jo"));
+        assert_eq!(records[0].get("Email address"), Some("jo@example.edu"));
+        assert_eq!(records[0].get("ID number"), Some("s1"));
+
+        let records = csv::read_str(&LmsExport::new(LmsFormat::Blackboard).to_csv(&book()), ',').unwrap();
+        assert_eq!(&records[0].header[..4], &["Last Name", "First Name", "Username", "Student ID"]);
+        assert_eq!(records[0].get("Last Name"), Some("Smith"));
+        assert_eq!(records[1].get("Username"), Some("s2"));
+    }
+
+    #[test]
+    fn test_column_mapping() {
+        let export = LmsExport::new(LmsFormat::Canvas)
+            .column("SIS User ID", "")
+            .column("SIS Login ID", "email")
+            .column("Student", "id")
+            .column("Notes", "last_name")
+            .assignment_column("lab2", "Lab 2 (678)");
+        let records = csv::read_str(&export.to_csv(&book()), ',').unwrap();
+
+        assert_eq!(records[0].header, vec!["Student", "ID", "SIS User ID", "SIS Login ID", "Section", "Notes", "lab1", "Lab 2 (678)"]);
+        assert_eq!(records[0].get("SIS User ID"), Some(""));
+        assert_eq!(records[0].get("SIS Login ID"), Some("jo@example.edu"));
+        assert_eq!(records[0].get("Student"), Some("s1"));
+        assert_eq!(records[0].get("Notes"), Some("Smith"));
+        assert_eq!(records[0].get("Lab 2 (678)"), Some("90"));
+    }
+
+    #[test]
+    fn test_identity_without_data() {
+        // The identity key isn't in the row's data, but the student is
+        let mut book = book();
+        book.rows[1].data.clear();
+        let records = csv::read_str(&LmsExport::new(LmsFormat::Canvas).to_csv(&book), ',').unwrap();
+        assert_eq!(records[1].get("SIS User ID"), Some("s2"));
+    }
+}
@@ -9,34 +9,270 @@
 //! You should run this on a publicly available server and be sure 
 //! the correct ports are open. You can run this on whatever port you'd like,
 //! as long as you have permission. The `/submit` route is meant to accept a Submission.
+//! If the dropbox serves more than one [`assignment`](crate::dropbox::assignment),
+//! each also has its own route, like `/submit/lab1`.
 //! See the [`helpers::web::post_json`](helpers::web::post_json) function for more info on
 //! how to send a submission to the dropbox.
+//!
+//! The dropbox can require a token to submit, see [`auth`](crate::dropbox::auth).
+//! Instructors can read what it's collected with the [`instructor`](crate::dropbox::instructor) routes,
+//! or watch it come in on the [`dashboard`](crate::dropbox::dashboard).
 
 // Re exports to be available from this module
 pub mod results_file;
 pub mod submission;
 pub mod fingerprint;
+pub mod criterion_result;
+pub mod outbox;
+pub mod receipt;
+pub mod signature;
+pub mod config;
+pub mod integrity;
+pub mod artifact;
+pub mod encryption;
+pub mod rejection;
+pub mod history;
+pub mod store;
+pub mod csv;
+pub mod assignment;
+pub mod auth;
+pub mod query;
+pub mod gradebook;
+pub mod lms;
+pub mod instructor;
+pub mod dashboard;
 
 pub use results_file::{AsCsv, ResultsFile};
 pub use submission::{Submission, TestData};
+pub use criterion_result::{CriterionResult, ResultStatus};
+pub use outbox::{Outbox, flush_pending};
+pub use receipt::Receipt;
+pub use config::DropboxConfig;
+pub use fingerprint::{Fingerprint, FingerprintOptions};
+pub use integrity::{IntegrityOptions, IntegrityReport};
+pub use artifact::Artifact;
+pub use rejection::Rejection;
+pub use history::History;
+pub use store::{SubmissionStore, StoreKind};
+pub use assignment::Assignment;
+pub use gradebook::Gradebook;
 
 
 // std uses
 use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::collections::HashMap;
 
 // external uses
-use rocket::{Rocket, Config, State};
-use rocket::http::Status;
+use chrono::{DateTime, Local};
+use rocket::{Rocket, Config, State, Data, Request, Response};
+use rocket::http::{Status, ContentType};
+use rocket::response::{self, status, Responder};
 use rocket::error::LaunchError;
-use rocket::config::Environment;
+use rocket::config::{Environment, Limits};
 use rocket_contrib::json::Json;
+use serde_json::Value;
 
+// internal uses
+use crate::rubric::attempt_policy::DEFAULT_IDENTITY_KEY;
+use crate::dropbox::auth::SubmitToken;
+use crate::dropbox::query::SubmissionQuery;
 
-/// A ResultsFile wrapped in a Mutex for thread locking.
+
+/// The submission store wrapped in a Mutex for thread locking.
 /// This allows concurrent acceptance of submissions without
 /// screwing up the results file.
-struct SharedResultsFile(Mutex<ResultsFile>);
+struct SharedStore(Mutex<Box<dyn SubmissionStore>>);
+
+/// The receipts issued for every recorded submission, by submission id.
+///
+/// Graders retry submissions they couldn't deliver, so the dropbox may see
+/// the same submission more than once. When it does, it sends back the original
+/// receipt instead of recording it again. The receipts are also appended to a file
+/// as JSON, one per line, so they're remembered after a restart.
+///
+/// Receipts name the student if students are being tracked, so the log
+/// also keeps the number of attempts each student has made, and when
+/// they last submitted. It also keeps which team each student is on.
+struct ReceiptLog {
+    receipts: HashMap<String, Receipt>,
+    attempts: HashMap<String, u32>,
+    last_received: HashMap<String, DateTime<Local>>,
+    teams: HashMap<String, String>,
+    path: PathBuf,
+}
+
+impl ReceiptLog {
+    /// Loads the receipts from the file, if there is one.
+    /// Lines that can't be parsed are skipped.
+    fn load<P: AsRef<Path>>(path: P) -> ReceiptLog {
+        let path = path.as_ref().to_path_buf();
+        let mut log = ReceiptLog {
+            receipts: HashMap::new(),
+            attempts: HashMap::new(),
+            last_received: HashMap::new(),
+            teams: HashMap::new(),
+            path,
+        };
+        let receipts: Vec<Receipt> = fs::read_to_string(&log.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str::<Receipt>(l).ok())
+            .collect();
+        for r in receipts {
+            log.remember(r);
+        }
+        log
+    }
+
+    // Keeps a receipt in memory, counting the attempt for the
+    // student who sent it and everyone on their team
+    fn remember(&mut self, receipt: Receipt) {
+        if let Some(student) = &receipt.student {
+            let mut people = vec![student];
+            people.extend(receipt.members.iter().filter(|m| *m != student));
+            for person in people {
+                *self.attempts.entry(person.clone()).or_insert(0) += 1;
+                let last = self.last_received.entry(person.clone()).or_insert(receipt.received_at);
+                *last = (*last).max(receipt.received_at);
+            }
+        }
+        if let Some(team) = &receipt.team {
+            for member in &receipt.members {
+                self.teams.insert(member.clone(), team.clone());
+            }
+        }
+        self.receipts.insert(receipt.submission_id.clone(), receipt);
+    }
+
+    /// How many recorded submissions a student has made
+    fn attempts(&self, student: &str) -> u32 {
+        self.attempts.get(student).copied().unwrap_or(0)
+    }
+
+    /// The team a student is on, if they've been on one
+    fn team_of(&self, student: &str) -> Option<&str> {
+        self.teams.get(student).map(|t| t.as_str())
+    }
+
+    /// When a student's last recorded submission was received
+    fn last_received(&self, student: &str) -> Option<DateTime<Local>> {
+        self.last_received.get(student).copied()
+    }
+
+    /// The receipt issued for a submission, if it's been recorded
+    fn get(&self, submission_id: &str) -> Option<&Receipt> {
+        self.receipts.get(submission_id)
+    }
+
+    /// Remembers a receipt, writing it to the file
+    fn record(&mut self, receipt: &Receipt) -> crate::Result<()> {
+        let mut file = OpenOptions::new().append(true).create(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(receipt)?)?;
+        self.remember(receipt.clone());
+        Ok(())
+    }
+}
+
+/// [`ReceiptLog`] wrapped in a Mutex, just like the results file
+struct SharedReceiptLog(Mutex<ReceiptLog>);
+
+/// Where one assignment's submissions go, and how they're treated
+struct AssignmentBox {
+    assignment: Option<Assignment>,
+    config: DropboxConfig,
+    results: SharedStore,
+    receipts: SharedReceiptLog,
+}
+
+impl AssignmentBox {
+    /// Opens the assignment's store and receipts, creating their directories
+    fn open(assignment: Option<Assignment>, config: DropboxConfig) -> AssignmentBox {
+        for path in &[&config.results_path, &config.receipts_path] {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).expect("Couldn't create results directory");
+            }
+        }
+
+        // Open the submission store wrapped in a Mutex
+        // The muted is necessary because route handling is asyncronous.
+        // This allows mutliple submissions to be submitted at once. It also will
+        // crash when the instructor opens the dropbox if the file is already in use.
+        let store = config.open_store().expect("Couldn't open submission store");
+        match &assignment {
+            Some(a) => println!("Storing submissions for {} in {}", a.name, store.location()),
+            None => println!("Storing submissions in {}", store.location()),
+        }
+
+        AssignmentBox {
+            results: SharedStore(Mutex::new(store)),
+            receipts: SharedReceiptLog(Mutex::new(ReceiptLog::load(&config.receipts_path))),
+            assignment,
+            config,
+        }
+    }
+
+    /// Every submission in the store, oldest first. Submissions without an
+    /// assignment get this one.
+    fn submissions(&self) -> crate::Result<Vec<Submission>> {
+        let mut subs = self.results.0.lock().expect("Lock submission store").submissions()?;
+        if let Some(a) = &self.assignment {
+            for sub in subs.iter_mut().filter(|s| s.assignment.is_none()) {
+                sub.assignment = Some(a.name.clone());
+            }
+        }
+        Ok(subs)
+    }
+}
+
+/// Every assignment the dropbox serves. If it wasn't given any assignments,
+/// there's just the one box for every submission.
+struct AssignmentBoxes {
+    default: Option<AssignmentBox>,
+    by_name: HashMap<String, AssignmentBox>,
+}
+
+impl AssignmentBoxes {
+    fn open(config: &DropboxConfig) -> AssignmentBoxes {
+        config.check_assignments().expect("Assignments can't share a directory");
+        if config.assignments.is_empty() {
+            return AssignmentBoxes {
+                default: Some(AssignmentBox::open(None, config.clone())),
+                by_name: HashMap::new(),
+            };
+        }
+        let by_name = config.assignments.iter()
+            .map(|a| (a.name.clone(), AssignmentBox::open(Some(a.clone()), config.for_assignment(a))))
+            .collect();
+        AssignmentBoxes { default: None, by_name }
+    }
+
+    /// The box a submission sent to `/submit` goes in, by the assignment it names
+    fn for_submission(&self, sub: &Submission) -> Option<&AssignmentBox> {
+        match &self.default {
+            Some(default) => Some(default),
+            None => self.by_name.get(sub.assignment.as_deref()?),
+        }
+    }
+
+    /// Every stored submission that matches the query, from every assignment,
+    /// in the order they were received.
+    ///
+    /// Submissions without an assignment get the one they were stored under.
+    /// Students are found by each assignment's identity key.
+    fn submissions(&self, query: &SubmissionQuery) -> crate::Result<Vec<Submission>> {
+        let mut subs = Vec::new();
+        for assignment_box in self.default.iter().chain(self.by_name.values()) {
+            let identity_key = assignment_box.config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY);
+            subs.extend(query.filter(assignment_box.submissions()?, identity_key));
+        }
+        subs.sort_by_key(|s| s.received_at.unwrap_or(s.time));
+        Ok(subs)
+    }
+}
 
 /// Just a test route so you can make sure the server is running
 #[get("/")]
@@ -44,35 +280,344 @@ fn return_ok() -> Status {
     Status::Ok
 }
 
-/// Accepts a submission and writes it to the results file
+/// Responds with the rejection as JSON, with its status
+impl<'r> Responder<'r> for Rejection {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        let status = Status::from_code(self.status).unwrap_or(Status::InternalServerError);
+        let mut resp = Response::build_from(Json(&self).respond_to(req)?);
+        resp.status(status);
+        if let Some(seconds) = self.retry_after {
+            resp.raw_header("Retry-After", seconds.to_string());
+        }
+        if status == Status::Unauthorized {
+            resp.raw_header("WWW-Authenticate", "Bearer");
+        }
+        resp.ok()
+    }
+}
+
+/// Responds to requests without a token, when the route needs one
+#[catch(401)]
+fn unauthorized() -> Rejection {
+    Rejection::new(Status::Unauthorized.code, "This needs a token. If you're submitting, your grader may be out of date.")
+}
+
+/// Responds to requests whose token isn't accepted
+#[catch(403)]
+fn forbidden() -> Rejection {
+    Rejection::new(Status::Forbidden.code, "That token isn't accepted here. If you're submitting, your grader may be out of date.")
+}
+
+impl From<Status> for Rejection {
+    /// A rejection that just gives the status' reason
+    fn from(status: Status) -> Self {
+        Rejection::new(status.code, status.reason)
+    }
+}
+
+/// The response to a submission, a receipt with a status, or why it was refused
+type ReceiptResponse = Result<status::Custom<Json<Receipt>>, Rejection>;
+
+/// Accepts a submission and writes it to the results file.
+///
+/// Responds with `202 Accepted` and a JSON [`Receipt`](crate::dropbox::receipt::Receipt).
+/// If the submission has already been recorded, this responds with `200 Ok` and
+/// the original receipt, and doesn't record it again.
+///
+/// If the dropbox was opened with a secret, submissions without a valid
+/// [signature](crate::dropbox::signature) are rejected with `403 Forbidden`.
+/// If it has API tokens, requests without one are rejected with `401 Unauthorized`,
+/// and with a token it doesn't accept with `403 Forbidden`. See [`auth`](crate::dropbox::auth).
+///
+/// The dropbox records when it received the submission. If it has a late policy,
+/// lateness and penalties are decided by its clock rather than the student's.
 #[post("/submit", format = "application/json", data = "<submission>")]
-fn accept_submission(state: State<SharedResultsFile>, submission: Json<Submission>) -> Status {
+fn accept_submission(_token: SubmitToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, submission: Json<Value>) -> ReceiptResponse {
+    record_submission(boxes.inner(), config.inner(), None, submission.into_inner(), Vec::new())
+}
+
+/// Accepts a submission for one assignment. It's recorded with that
+/// assignment's settings, in its own results file.
+///
+/// Assignments the dropbox doesn't serve are refused with `404 Not Found`, and
+/// submissions graded against a rubric version it doesn't accept with `409 Conflict`.
+/// Otherwise this responds just like [`accept_submission`].
+#[post("/submit/<assignment>", format = "application/json", data = "<submission>")]
+fn accept_assignment_submission(_token: SubmitToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, assignment: String, submission: Json<Value>) -> ReceiptResponse {
+    record_submission(boxes.inner(), config.inner(), Some(&assignment), submission.into_inner(), Vec::new())
+}
+
+/// Accepts a submission with files attached, as a multipart form.
+///
+/// The submission's JSON is in the `submission` field, and each file is in an
+/// `artifact` field. Files that don't match the artifacts listed in the submission
+/// are rejected with `422 Unprocessable Entity`, and uploads over the configured
+/// size with `413 Payload Too Large`.
+///
+/// Otherwise this responds just like [`accept_submission`].
+#[post("/submit", format = "multipart/form-data", data = "<data>")]
+fn accept_submission_with_artifacts(_token: SubmitToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, content_type: &ContentType, data: Data) -> ReceiptResponse {
+    let (value, files) = read_multipart(config.inner(), content_type, data)?;
+    record_submission(boxes.inner(), config.inner(), None, value, files)
+}
+
+/// Accepts a submission for one assignment, with files attached.
+/// See [`accept_assignment_submission`] and [`accept_submission_with_artifacts`].
+#[post("/submit/<assignment>", format = "multipart/form-data", data = "<data>")]
+fn accept_assignment_submission_with_artifacts(_token: SubmitToken, boxes: State<AssignmentBoxes>, config: State<DropboxConfig>, assignment: String, content_type: &ContentType, data: Data) -> ReceiptResponse {
+    let (value, files) = read_multipart(config.inner(), content_type, data)?;
+    record_submission(boxes.inner(), config.inner(), Some(&assignment), value, files)
+}
+
+/// Reads the submission's JSON and the attached files out of a multipart form
+fn read_multipart(config: &DropboxConfig, content_type: &ContentType, data: Data) -> Result<(Value, Vec<(String, Vec<u8>)>), Rejection> {
+    let boundary = content_type.params()
+        .find(|(k, _)| k.eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.to_string())
+        .ok_or(Status::BadRequest)?;
+
+    // Read one byte past the limit, so we know if it was too big
+    let mut body = Vec::new();
+    data.open().take(config.max_upload_size + 1).read_to_end(&mut body).map_err(|_| Status::BadRequest)?;
+    if body.len() as u64 > config.max_upload_size {
+        return Err(Status::PayloadTooLarge.into());
+    }
+
+    let parts = artifact::parse_multipart(&body, &boundary).map_err(|_| Status::BadRequest)?;
+    let json = parts.iter()
+        .find(|p| p.name == artifact::SUBMISSION_FIELD)
+        .ok_or(Status::UnprocessableEntity)?;
+    let value: Value = serde_json::from_slice(&json.data).map_err(|_| Status::UnprocessableEntity)?;
+    let files = parts.into_iter()
+        .filter(|p| p.name == artifact::ARTIFACT_FIELD)
+        .filter_map(|p| Some((p.filename?, p.data)))
+        .collect();
+    Ok((value, files))
+}
+
+/// Decrypts a submission and its artifacts.
+///
+/// Submissions that aren't encrypted are rejected with `403 Forbidden`, and ones
+/// that can't be decrypted with `422 Unprocessable Entity`.
+fn decrypt_submission(key: &str, value: Value, files: Vec<(String, Vec<u8>)>) -> Result<(Value, Vec<(String, Vec<u8>)>), Status> {
+    let payload = encryption::EncryptedPayload::from_value(&value).ok_or_else(|| {
+        eprintln!("Rejected a submission that wasn't encrypted");
+        Status::Forbidden
+    })?;
+
+    let open = |payload: &encryption::EncryptedPayload| {
+        encryption::decrypt(key, payload).map_err(|e| {
+            eprintln!("Couldn't decrypt a submission: {}", e);
+            Status::UnprocessableEntity
+        })
+    };
+
+    let plaintext = open(&payload)?;
+    let value = serde_json::from_slice(&plaintext).map_err(|_| Status::UnprocessableEntity)?;
+
+    let mut decrypted = Vec::new();
+    for (name, contents) in files {
+        let payload = serde_json::from_slice(&contents).map_err(|_| Status::UnprocessableEntity)?;
+        decrypted.push((name, open(&payload)?));
+    }
+    Ok((value, decrypted))
+}
+
+/// Checks a submission and writes it to the results file, storing any attached files.
+/// All the submission routes end up here. `target` is the assignment in the URL, if there was one.
+fn record_submission(boxes: &AssignmentBoxes, config: &DropboxConfig, target: Option<&str>, value: Value, files: Vec<(String, Vec<u8>)>) -> ReceiptResponse {
+    let received = Local::now();
+
+    let target_box = match target {
+        Some(name) => Some(boxes.by_name.get(name).ok_or_else(|| Rejection::new(
+            Status::NotFound.code,
+            &format!("This dropbox doesn't have an assignment named {}", name)
+        ))?),
+        None => None,
+    };
+
+    let (value, files) = match &config.private_key {
+        Some(key) => decrypt_submission(key, value, files)?,
+        None => (value, files),
+    };
+
+    // The signature is checked against the JSON exactly as it was sent
+    if let Some(secret) = &config.secret {
+        if !signature::verify_value(secret, &value) {
+            eprintln!("Rejected a submission with a missing or invalid signature");
+            return Err(Status::Forbidden.into());
+        }
+    }
+
     // Retrieve the submission
-    let sub = submission.into_inner();
+    let mut sub: Submission = serde_json::from_value(value).map_err(|_| Status::UnprocessableEntity)?;
 
-    // Lock the results file until we're done with it
-    let shared_rf: &SharedResultsFile = state.inner();
-    let mut lock = shared_rf.0.lock().expect("Lock shared results file");
+    // The assignment in the URL wins over the one in the submission
+    let assignment_box = match target_box {
+        Some(b) => {
+            sub.assignment = target.map(String::from);
+            b
+        },
+        None => boxes.for_submission(&sub).ok_or_else(|| {
+            eprintln!("Rejected submission {} for assignment {:?}", sub.id, sub.assignment);
+            Rejection::new(
+                Status::Forbidden.code,
+                &format!("This dropbox doesn't accept submissions for {}", sub.assignment.as_deref().unwrap_or("unnamed assignments"))
+            )
+        })?,
+    };
 
-    // Write the header based on first submission
-    if lock.length() == 0 {
-        if lock.append(&sub.header()).is_err() {
-            eprintln!("Error! Could not write csv file header. File is likely locked by another process");
-            return Status::InternalServerError;
-        };
+    if let Some(assignment) = &assignment_box.assignment {
+        if !assignment.accepts_version(sub.rubric_version.as_deref()) {
+            eprintln!("Rejected submission {}, rubric version {:?} isn't accepted", sub.id, sub.rubric_version);
+            return Err(Rejection::new(
+                Status::Conflict.code,
+                &format!(
+                    "This grader is out of date. {} accepts rubric versions {}, get the latest grader and try again.",
+                    assignment.name, assignment.rubric_versions.join(", ")
+                )
+            ));
+        }
+    }
+
+    // The rest is up to the assignment's settings
+    let config = &assignment_box.config;
+
+    // Every listed artifact has to be here, and be what was attached
+    for a in &sub.artifacts {
+        if !files.iter().any(|(name, contents)| name == &a.name && a.matches(contents)) {
+            eprintln!("Rejected submission {}, artifact {} is missing or doesn't match", sub.id, a.name);
+            return Err(Status::UnprocessableEntity.into());
+        }
+    }
+
+    sub.received_at = Some(received);
+    if let Some(policy) = &config.late_policy {
+        sub.apply_late_policy(policy, received);
+    }
+    if let Some(skew) = sub.clock_skew() {
+        if skew.num_seconds().abs() > config.max_clock_skew.num_seconds() {
+            sub.flag(&format!("Client clock off by {} seconds", skew.num_seconds()));
+        }
+    }
+
+    // Lock the store until we're done with it
+    let mut store = assignment_box.results.0.lock().expect("Lock submission store");
+    let mut receipts = assignment_box.receipts.0.lock().expect("Lock receipt log");
+
+    // A retried submission that already made it
+    if let Some(receipt) = receipts.get(&sub.id) {
+        return Ok(status::Custom(Status::Ok, Json(receipt.clone())));
+    }
+
+    // Who submitted it, if we're keeping track
+    let student = config.student(&sub);
+    if let (None, Some(key)) = (&student, config.identity_key()) {
+        sub.flag(&format!("Missing {}, attempt not counted", key));
+    }
+
+    // Everyone it counts for. A team can't take turns submitting to get
+    // more attempts, so the limits apply to each of them.
+    let mut people: Vec<&String> = student.iter().collect();
+    if let Some(student) = &student {
+        if !sub.members.is_empty() && !sub.members.contains(student) {
+            eprintln!("Rejected team submission {}, {} isn't on the team", sub.id, student);
+            return Err(Rejection::new(
+                Status::Forbidden.code,
+                &format!("{} isn't on the team, only members can submit for it", student)
+            ));
+        }
+        people.extend(sub.members.iter().filter(|m| *m != student));
+    }
+
+    // Too soon after their last one
+    if let Some(cooldown) = config.cooldown {
+        let last = people.iter().filter_map(|p| receipts.last_received(p)).max();
+        if let Some(last) = last {
+            let wait = cooldown - received.signed_duration_since(last);
+            if wait > chrono::Duration::zero() {
+                // Round up, so they don't come back a moment too early
+                let seconds = (wait.num_milliseconds() as u64 + 999) / 1000;
+                return Err(Rejection::new(
+                    Status::TooManyRequests.code,
+                    &format!("Too many submissions. Try again in {} seconds.", seconds)
+                ).retry_after(seconds));
+            }
+        }
+    }
+
+    // Count the attempt, as whoever on the team has used the most
+    let max_attempts = config.attempt_policy.as_ref().and_then(|p| p.max_attempts);
+    let used = people.iter().map(|p| receipts.attempts(p)).max().unwrap_or(0);
+    let attempt = student.clone().map(|s| (used + 1, s));
+    if let (Some((attempt, student)), Some(policy)) = (&attempt, &config.attempt_policy) {
+        if policy.exceeded(*attempt) {
+            let max = max_attempts.unwrap_or_default();
+            if policy.reject_extra {
+                eprintln!("Rejected attempt {} from {}, only {} allowed", attempt, student, max);
+                return Err(Rejection::new(
+                    Status::Forbidden.code,
+                    &format!("No attempts left. You've used all {} of your attempts.", max)
+                ));
+            }
+            sub.flag(&format!("Attempt {} of {}", attempt, max));
+        }
+    }
+
+    // A student can only be on one team
+    if let Some(team) = sub.team_key() {
+        for member in &sub.members {
+            if let Some(other) = receipts.team_of(member).filter(|other| *other != team) {
+                eprintln!("Rejected team submission {}, {} is already on team {}", sub.id, member, other);
+                return Err(Rejection::new(
+                    Status::Conflict.code,
+                    &format!("{} is already on another team ({})", member, other)
+                ));
+            }
+        }
+    }
+
+    // Store the files, and record where they are. Each one needs its own file name.
+    if let Err(e) = artifact::check_names(&sub.artifacts) {
+        eprintln!("Rejected submission {}, {}", sub.id, e);
+        return Err(Rejection::new(Status::BadRequest.code, &e.to_string()));
+    }
+    for a in &mut sub.artifacts {
+        let (_, contents) = files.iter().find(|(name, _)| name == &a.name).expect("Artifacts were checked");
+        match artifact::store(&config.artifact_dir, &sub.id, &a.name, contents) {
+            Ok(path) => a.path = Some(path),
+            Err(e) => {
+                eprintln!("Error: Could not store artifact: {}", e);
+                return Err(Status::InternalServerError.into());
+            }
+        }
     }
 
-    if lock.write_csv(&sub).is_ok() {
-        return Status::Accepted;
-    } else {
-        eprintln!("Error: Could not write following submission");
-        eprintln!("{:#?}", sub);
-        return Status::InternalServerError;
+    // A team submission gets a row for each member
+    let identity_key = config.identity_key().unwrap_or(DEFAULT_IDENTITY_KEY);
+    let rows = sub.per_member(identity_key);
+
+    match store.store(&rows) {
+        Ok(record) => {
+            let mut receipt = Receipt::new(&sub, &record);
+            if let Some((attempt, student)) = attempt {
+                receipt = receipt.with_attempt(&student, attempt, max_attempts);
+            }
+            if let Err(e) = receipts.record(&receipt) {
+                eprintln!("Error: Could not record receipt for submission {}: {}", sub.id, e);
+            }
+            Ok(status::Custom(Status::Accepted, Json(receipt)))
+        },
+        Err(e) => {
+            eprintln!("Error: Could not write following submission: {}", e);
+            eprintln!("{:#?}", sub);
+            Err(Status::InternalServerError.into())
+        }
     }
 }
 
 /// Builds a rocket instance to launch
-fn new_rocket(port: u16) -> Rocket {
+fn new_rocket(dropbox_config: DropboxConfig) -> Rocket {
     // If debug
     #[cfg(debug_assertions)]
     let builder = Config::build(Environment::Development);
@@ -81,32 +626,60 @@ fn new_rocket(port: u16) -> Rocket {
     let builder = Config::build(Environment::Production);
 
     let config = builder
-        .address("0.0.0.0")
-        .port(port)
+        .address(dropbox_config.address.as_str())
+        .port(dropbox_config.port)
+        .log_level(dropbox_config.log_level)
+        .limits(Limits::new().limit("json", dropbox_config.max_upload_size))
         .finalize()
         .expect("Could not build dropbox server");
 
-    // Create a results file wrapped in a Mutex
-    // The muted is necessary because route handling is asyncronous.
-    // This allows mutliple submissions to be submitted at once. It also will
-    // crash when the instructor opens the dropbox if the file is already in use.
-    let shared_results_file = SharedResultsFile(
-        Mutex::new(
-            ResultsFile::new_blank("submissions.csv").expect("Couldn't open results file")
-        )
-    );
+    let boxes = AssignmentBoxes::open(&dropbox_config);
 
     println!("Dropbox is open! accepting POST requests to /submit");
+    for name in dropbox_config.assignment_names() {
+        println!("  and /submit/{}", name);
+    }
     return rocket::custom(config)
-        .manage(shared_results_file)
-        .mount("/", routes![return_ok, accept_submission]);
+        .manage(boxes)
+        .manage(dropbox_config)
+        .mount("/", routes![
+            return_ok,
+            accept_submission,
+            accept_submission_with_artifacts,
+            accept_assignment_submission,
+            accept_assignment_submission_with_artifacts
+        ])
+        .mount("/", instructor::routes())
+        .mount("/", dashboard::routes())
+        .register(catchers![unauthorized, forbidden]);
 }
 
 /// Opens the dropbox for submissions on the given port.
 /// 
 /// You should probably use [`open_with_arg()`](crate::dropbox::open_with_arg).
 pub fn open(port: u16) -> LaunchError {
-    new_rocket(port).launch()
+    open_with(DropboxConfig::new(port))
+}
+
+/// Opens the dropbox with the given settings.
+///
+/// ```no_run
+/// use rubric::dropbox::{self, DropboxConfig};
+///
+/// let config = DropboxConfig::new(8080).secret("secret key");
+/// dropbox::open_with(config);
+/// ```
+pub fn open_with(config: DropboxConfig) -> LaunchError {
+    new_rocket(config).launch()
+}
+
+/// Opens the dropbox, only accepting submissions signed with the given secret.
+///
+/// This should be the same secret the grader uses in
+/// [`Submission::set_fingerprint`](crate::dropbox::submission::Submission::set_fingerprint).
+/// Submissions that aren't signed, or were changed after signing, are rejected.
+pub fn open_with_secret(port: u16, secret: &str) -> LaunchError {
+    open_with(DropboxConfig::new(port).secret(secret))
 }
 
 /// This is the same as [`open()`](crate::dropbox::open), but it will
@@ -137,14 +710,31 @@ mod tests {
     use rocket::local::Client;
     use rocket::http::Header;
 
-    fn client() -> Client {
-        let rocket = new_rocket(8080);
+    // A dropbox writing into its own directory, so tests don't share files
+    fn client(dir: &tempdir::TempDir) -> Client {
+        client_with(DropboxConfig::new(8080).results_dir(dir.path()))
+    }
+
+    fn client_with(config: DropboxConfig) -> Client {
+        let rocket = new_rocket(config);
         Client::new(rocket).expect("valid rocket instance")
     }
 
+    fn post_submission<'c>(client: &'c Client, sub: &Submission) -> rocket::local::LocalResponse<'c> {
+        post_submission_to(client, "/submit", sub)
+    }
+
+    fn post_submission_to<'c>(client: &'c Client, route: &str, sub: &Submission) -> rocket::local::LocalResponse<'c> {
+        client.post(route.to_string())
+            .body(serde_json::to_string(sub).unwrap())
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch()
+    }
+
     #[test]
     fn test_server_runs() {
-        let client = client();
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client(&dir);
         let resp = client.get("/").dispatch();
 
         assert_eq!(resp.status(), Status::Ok);
@@ -152,7 +742,8 @@ mod tests {
 
     #[test]
     fn test_404() {
-        let client = client();
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client(&dir);
         let resp = client.get("/not_a_route").dispatch();
 
         assert_eq!(resp.status(), Status::NotFound);
@@ -160,7 +751,8 @@ mod tests {
 
     #[test]
     fn test_422_unprocessable_entity() {
-        let client = client();
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client(&dir);
         let resp = client.post("/submit")
             // This route only accepts Submissions
             // This is not a submission
@@ -174,13 +766,531 @@ mod tests {
 
     #[test]
     fn test_accept_submission() {
-        let client = client();
-        let sub = Submission::new();
-        let req = client.post("/submit")
+        // Its own results file, so the schema is just this submission's columns
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()));
+        let mut sub = Submission::from_data(crate::data! { "name" => "Smith, John" });
+        sub.grade = 42;
+        let mut req = client.post("/submit")
             .body(serde_json::to_string(&sub).unwrap())
             .header(Header::new("Content-Type", "application/json"))
             .dispatch();
 
         assert_eq!(req.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&req.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.submission_id, sub.id);
+        assert_eq!(receipt.grade, 42);
+
+        // It's for what was stored, which has when the dropbox received it
+        let store = store::CsvStore::open(dir.path().join("submissions.csv"), ',').unwrap();
+        assert!(store.verify(&receipt).unwrap());
+        assert!(!receipt.verify(&sub.as_csv()));
+    }
+
+    #[test]
+    fn test_duplicate_submission_not_recorded_twice() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()));
+        let body = serde_json::to_string(&Submission::new()).unwrap();
+        let mut first = client.post("/submit")
+            .body(&body)
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch();
+        assert_eq!(first.status(), Status::Accepted);
+
+        let mut second = client.post("/submit")
+            .body(&body)
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch();
+        assert_eq!(second.status(), Status::Ok);
+
+        // Same receipt both times
+        assert_eq!(first.body_string(), second.body_string());
+    }
+
+    #[test]
+    fn test_signed_submissions() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()).secret("secret key"));
+        let post = |sub: &Submission| post_submission(&client, sub).status();
+
+        // Unsigned
+        assert_eq!(post(&Submission::new()), Status::Forbidden);
+
+        // Signed with the wrong secret
+        let mut wrong = Submission::new();
+        wrong.set_fingerprint("wrong key");
+        wrong.sign();
+        assert_eq!(post(&wrong), Status::Forbidden);
+
+        // Changed after signing
+        let mut forged = Submission::new();
+        forged.set_fingerprint("secret key");
+        forged.sign();
+        forged.grade = 100;
+        assert_eq!(post(&forged), Status::Forbidden);
+
+        let mut good = Submission::new();
+        good.set_fingerprint("secret key");
+        good.sign();
+        assert_eq!(post(&good), Status::Accepted);
+    }
+
+    #[test]
+    fn test_server_decides_lateness() {
+        let mut policy = crate::rubric::LatePolicy::default();
+        policy.deadline = Some(Local::now() - chrono::Duration::hours(1));
+        policy.late_penalty = 5;
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()).late_policy(policy));
+
+        // The student turned their clock back
+        let mut sub = Submission::new();
+        sub.time = Local::now() - chrono::Duration::hours(2);
+
+        let mut resp = post_submission(&client, &sub);
+        assert_eq!(resp.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&resp.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.grade, -5);
+    }
+
+    // A multipart body like the one post_multipart sends
+    fn multipart_body(sub: &Submission, files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut body = format!(
+            "--XYZ\r\nContent-Disposition: form-data; name=\"submission\"\r\n\r\n{}\r\n",
+            serde_json::to_string(sub).unwrap()
+        ).into_bytes();
+        for (name, contents) in files {
+            body.extend(format!("--XYZ\r\nContent-Disposition: form-data; name=\"artifact\"; filename=\"{}\"\r\n\r\n", name).bytes());
+            body.extend_from_slice(contents);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--XYZ--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_accept_artifacts() {
+        let dir = tempdir::TempDir::new("artifacts").unwrap();
+        let file = dir.path().join("Dockerfile");
+        fs::write(&file, "FROM rust").unwrap();
+        let stored = dir.path().join("stored");
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()).artifact_dir(&stored));
+
+        let mut sub = Submission::new();
+        let name = sub.attach_file(&file).unwrap().name.clone();
+        let post = |files: &[(&str, &[u8])]| {
+            client.post("/submit")
+                .body(multipart_body(&sub, files))
+                .header(Header::new("Content-Type", "multipart/form-data; boundary=XYZ"))
+                .dispatch()
+                .status()
+        };
+
+        // Missing, or changed after attaching
+        assert_eq!(post(&[]), Status::UnprocessableEntity);
+        assert_eq!(post(&[(&name, b"FROM python")]), Status::UnprocessableEntity);
+
+        assert_eq!(post(&[(&name, b"FROM rust")]), Status::Accepted);
+        let path = stored.join(artifact::submission_dir(&sub.id)).join(&name);
+        assert_eq!(fs::read_to_string(path).unwrap(), "FROM rust");
+
+        // Listing artifacts without sending them as a form
+        assert_eq!(post_submission(&client, &sub).status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn test_results_dir() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let results = dir.path().join("fall").join("lab1");
+        let client = client_with(DropboxConfig::new(8080).results_dir(&results));
+
+        let mut sub = Submission::new();
+        sub.grade = 12;
+        assert_eq!(post_submission(&client, &sub).status(), Status::Accepted);
+
+        // Submission ids aren't in the CSV, so check the grade
+        let records = csv::read_file(results.join("submissions.csv"), ',').unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].parse::<isize>("grade").unwrap(), 12);
+        let receipts = fs::read_to_string(results.join("receipts.jsonl")).unwrap();
+        assert!(receipts.contains(&sub.id));
+    }
+
+    #[test]
+    fn test_json_lines_store() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let config = DropboxConfig::new(8080).store(StoreKind::JsonLines).results_dir(dir.path());
+        let path = config.results_path.clone();
+        let client = client_with(config);
+
+        let mut sub = Submission::new();
+        sub.grade = 7;
+        assert_eq!(post_submission(&client, &sub).status(), Status::Accepted);
+
+        let stored = store::JsonLinesStore::open(path).unwrap().submissions().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].id, sub.id);
+        assert_eq!(stored[0].grade, 7);
+        assert!(stored[0].received_at.is_some());
+    }
+
+    #[test]
+    fn test_allowed_assignments() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .assignments(&["lab1"]));
+
+        let mut sub = Submission::new();
+        assert_eq!(post_submission(&client, &sub).status(), Status::Forbidden);
+        sub.set_assignment("lab2");
+        assert_eq!(post_submission(&client, &sub).status(), Status::Forbidden);
+        sub.set_assignment("lab1");
+        assert_eq!(post_submission(&client, &sub).status(), Status::Accepted);
+    }
+
+    #[test]
+    fn test_assignment_routes() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .assignment(Assignment::new("lab1").max_attempts(1))
+            .assignment(Assignment::new("lab 2")));
+        let sub = || Submission::from_data(crate::data! { "id" => "s1" });
+
+        // The assignment in the URL is the one it's recorded for
+        let mut other = sub();
+        other.set_assignment("lab 2");
+        assert_eq!(post_submission_to(&client, "/submit/lab1", &other).status(), Status::Accepted);
+        let stored = csv::read_file(dir.path().join("lab1").join("submissions.csv"), ',').unwrap();
+        assert_eq!(stored.len(), 1);
+
+        // Attempts are counted per assignment
+        assert_eq!(post_submission_to(&client, "/submit/lab1", &sub()).status(), Status::Forbidden);
+        assert_eq!(post_submission_to(&client, "/submit/lab%202", &sub()).status(), Status::Accepted);
+        // Sending to /submit goes by the submission's assignment
+        assert_eq!(post_submission(&client, &other).status(), Status::Accepted);
+        let stored = csv::read_file(dir.path().join("lab_2").join("submissions.csv"), ',').unwrap();
+        assert_eq!(stored.len(), 2);
+
+        let mut resp = post_submission_to(&client, "/submit/lab3", &sub());
+        assert_eq!(resp.status(), Status::NotFound);
+        let rejection: Rejection = serde_json::from_str(&resp.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains("lab3"));
+    }
+
+    #[test]
+    fn test_no_assignment_routes() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()));
+        assert_eq!(post_submission_to(&client, "/submit/lab1", &Submission::new()).status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_rubric_versions() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .assignment(Assignment::new("lab1").rubric_versions(&["2"])));
+        let post = |version: Option<&str>| {
+            let mut sub = Submission::new();
+            sub.rubric_version = version.map(String::from);
+            post_submission_to(&client, "/submit/lab1", &sub)
+        };
+
+        let mut old = post(Some("1"));
+        assert_eq!(old.status(), Status::Conflict);
+        let rejection: Rejection = serde_json::from_str(&old.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains("out of date"));
+        assert_eq!(post(None).status(), Status::Conflict);
+        assert_eq!(post(Some("2")).status(), Status::Accepted);
+    }
+
+    #[test]
+    fn test_api_tokens() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .api_tokens(&["old", "new"]));
+        let post = |token: Option<&str>| {
+            let mut req = client.post("/submit")
+                .body(serde_json::to_string(&Submission::new()).unwrap())
+                .header(Header::new("Content-Type", "application/json"));
+            if let Some(token) = token {
+                req = req.header(Header::new("Authorization", auth::bearer_header(token)));
+            }
+            req.dispatch()
+        };
+
+        let mut missing = post(None);
+        assert_eq!(missing.status(), Status::Unauthorized);
+        assert_eq!(missing.headers().get_one("WWW-Authenticate"), Some("Bearer"));
+        let rejection: Rejection = serde_json::from_str(&missing.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains("token"));
+
+        assert_eq!(post(Some("wrong")).status(), Status::Forbidden);
+        assert_eq!(post(Some("old")).status(), Status::Accepted);
+        assert_eq!(post(Some("new")).status(), Status::Accepted);
+
+        // Files need a token too
+        let resp = client.post("/submit")
+            .body(multipart_body(&Submission::new(), &[]))
+            .header(Header::new("Content-Type", "multipart/form-data; boundary=XYZ"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::Unauthorized);
+    }
+
+    #[get("/instructor")]
+    fn instructor_only(_token: auth::InstructorToken) -> Status {
+        Status::Ok
+    }
+
+    #[test]
+    fn test_instructor_token() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let config = DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .instructor_token("teacher")
+            .api_token("student");
+        let rocket = new_rocket(config)
+            .mount("/", routes![instructor_only]);
+        let client = Client::new(rocket).unwrap();
+        let get = |token: &str| {
+            client.get("/instructor")
+                .header(Header::new("Authorization", auth::bearer_header(token)))
+                .dispatch()
+                .status()
+        };
+
+        assert_eq!(client.get("/instructor").dispatch().status(), Status::Unauthorized);
+        assert_eq!(get("student"), Status::Forbidden);
+        assert_eq!(get("teacher"), Status::Ok);
+    }
+
+    #[test]
+    fn test_upload_too_large() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()).max_upload_size(10));
+        let resp = client.post("/submit")
+            .body(multipart_body(&Submission::new(), &[]))
+            .header(Header::new("Content-Type", "multipart/form-data; boundary=XYZ"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PayloadTooLarge);
+    }
+
+    #[test]
+    fn test_encrypted_submissions() {
+        let (private_key, public_key) = encryption::generate_keys().unwrap();
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()).private_key(&private_key));
+
+        // Plain submissions aren't allowed
+        assert_eq!(post_submission(&client, &Submission::new()).status(), Status::Forbidden);
+
+        let mut sub = Submission::new();
+        sub.grade = 7;
+        let payload = encryption::encrypt(&public_key, serde_json::to_string(&sub).unwrap().as_bytes()).unwrap();
+        let send = |payload: encryption::EncryptedPayload| {
+            client.post("/submit")
+                .body(payload.into_value().to_string())
+                .header(Header::new("Content-Type", "application/json"))
+                .dispatch()
+        };
+
+        let mut resp = send(payload.clone());
+        assert_eq!(resp.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&resp.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.grade, 7);
+
+        // Tampered with on the way
+        let mut tampered = payload;
+        tampered.ciphertext = tampered.ciphertext.chars().rev().collect();
+        assert_eq!(send(tampered).status(), Status::UnprocessableEntity);
+    }
+
+    fn limited(max: u32, reject_extra: bool) -> crate::rubric::AttemptPolicy {
+        crate::rubric::AttemptPolicy {
+            max_attempts: Some(max),
+            reject_extra,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_attempt_limit() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .attempt_policy(limited(2, true)));
+        let attempt = || post_submission(&client, &Submission::from_data(crate::data! { "id" => "s1" }));
+
+        let mut first = attempt();
+        assert_eq!(first.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&first.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.attempt_description().unwrap(), "attempt 1 of 2");
+
+        assert_eq!(attempt().status(), Status::Accepted);
+
+        let mut third = attempt();
+        assert_eq!(third.status(), Status::Forbidden);
+        let rejection: Rejection = serde_json::from_str(&third.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains("No attempts left"));
+
+        // Someone else still has theirs
+        let other = post_submission(&client, &Submission::from_data(crate::data! { "id" => "s2" }));
+        assert_eq!(other.status(), Status::Accepted);
+    }
+
+    #[test]
+    fn test_extra_attempts_flagged() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .attempt_policy(limited(1, false)));
+        let attempt = || post_submission(&client, &Submission::from_data(crate::data! { "id" => "s1" }));
+
+        assert_eq!(attempt().status(), Status::Accepted);
+        let mut second = attempt();
+        assert_eq!(second.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&second.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.attempt, Some(2));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .cooldown(60));
+        let attempt = || post_submission(&client, &Submission::from_data(crate::data! { "id" => "s1" }));
+
+        let mut first = attempt();
+        assert_eq!(first.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&first.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.attempt_description().unwrap(), "attempt 1");
+
+        let mut second = attempt();
+        assert_eq!(second.status(), Status::TooManyRequests);
+        let retry: u64 = second.headers().get_one("Retry-After").unwrap().parse().unwrap();
+        assert!(retry > 0 && retry <= 60);
+        let rejection: Rejection = serde_json::from_str(&second.body_string().unwrap()).unwrap();
+        assert_eq!(rejection.retry_after, Some(retry));
+
+        // Other students aren't held up
+        let other = post_submission(&client, &Submission::from_data(crate::data! { "id" => "s2" }));
+        assert_eq!(other.status(), Status::Accepted);
+    }
+
+    #[test]
+    fn test_team_submissions() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080).results_dir(dir.path()));
+        let (a, b, c) = (String::from("s1"), String::from("s2"), String::from("s3"));
+        let team = |members: Vec<&String>| {
+            let mut sub = Submission::new();
+            sub.set_team(None, members);
+            post_submission(&client, &sub)
+        };
+
+        let mut first = team(vec![&a, &b]);
+        assert_eq!(first.status(), Status::Accepted);
+        let receipt: Receipt = serde_json::from_str(&first.body_string().unwrap()).unwrap();
+        assert_eq!(receipt.members.len(), 2);
+
+        // The same team can submit again
+        assert_eq!(team(vec![&b, &a]).status(), Status::Accepted);
+
+        // But b can't join another team
+        let mut second = team(vec![&b, &c]);
+        assert_eq!(second.status(), Status::Conflict);
+        let rejection: Rejection = serde_json::from_str(&second.body_string().unwrap()).unwrap();
+        assert!(rejection.error.contains(b.as_str()));
+    }
+
+    #[test]
+    fn test_team_shares_limits() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .attempt_policy(limited(1, true)));
+        let team = |submitter: &str, members: Vec<&str>| {
+            let mut sub = Submission::from_data(crate::data! { "id" => submitter });
+            sub.set_team(Some("Team Rocket"), members);
+            post_submission(&client, &sub).status()
+        };
+
+        assert_eq!(team("s1", vec!["s1", "s2"]), Status::Accepted);
+        // Taking turns doesn't get the team more attempts
+        assert_eq!(team("s2", vec!["s1", "s2"]), Status::Forbidden);
+        // And only members can submit for a team
+        assert_eq!(team("s3", vec!["s4", "s5"]), Status::Forbidden);
+        assert_eq!(team("s4", vec!["s4", "s5"]), Status::Accepted);
+    }
+
+    #[test]
+    fn test_team_shares_cooldown() {
+        let dir = tempdir::TempDir::new("dropbox").unwrap();
+        let client = client_with(DropboxConfig::new(8080)
+            .results_dir(dir.path())
+            .cooldown(60));
+        let team = |submitter: &str| {
+            let mut sub = Submission::from_data(crate::data! { "id" => submitter });
+            sub.set_team(None, vec!["s1", "s2"]);
+            post_submission(&client, &sub).status()
+        };
+
+        assert_eq!(team("s1"), Status::Accepted);
+        assert_eq!(team("s2"), Status::TooManyRequests);
+        // Alone, too
+        let alone = post_submission(&client, &Submission::from_data(crate::data! { "id" => "s2" }));
+        assert_eq!(alone.status(), Status::TooManyRequests);
+    }
+
+    #[test]
+    fn test_receipt_log_counts_attempts() {
+        let dir = tempdir::TempDir::new("receipts").unwrap();
+        let path = dir.path().join("receipts.jsonl");
+
+        let mut log = ReceiptLog::load(&path);
+        for n in 1..=2 {
+            let receipt = Receipt::new(&Submission::new(), "record").with_attempt("s123", n, None);
+            log.record(&receipt).unwrap();
+        }
+        log.record(&Receipt::new(&Submission::new(), "record")).unwrap();
+        assert_eq!(log.attempts("s123"), 2);
+
+        let mut team = Submission::new();
+        team.set_team(Some("Team Rocket"), vec!["s123", "s456"]);
+        log.record(&Receipt::new(&team, "record")).unwrap();
+
+        let reloaded = ReceiptLog::load(&path);
+        assert_eq!(reloaded.attempts("s123"), 2);
+        assert!(reloaded.last_received("s123").is_some());
+        assert_eq!(reloaded.team_of("s456"), Some("Team Rocket"));
+        assert_eq!(reloaded.team_of("s789"), None);
+        assert_eq!(reloaded.attempts("nobody"), 0);
+
+        // A counted team submission counts for everyone on the team
+        log.record(&Receipt::new(&team, "record").with_attempt("s456", 1, None)).unwrap();
+        assert_eq!(log.attempts("s456"), 1);
+        assert_eq!(log.attempts("s123"), 3);
+        assert!(log.last_received("s123") >= log.last_received("s456"));
+    }
+
+    #[test]
+    fn test_receipt_log_persists() {
+        let dir = tempdir::TempDir::new("receipts").unwrap();
+        let path = dir.path().join("receipts.jsonl");
+        let sub = Submission::new();
+        let receipt = Receipt::new(&sub, "record");
+
+        let mut log = ReceiptLog::load(&path);
+        assert!(log.get(&sub.id).is_none());
+        log.record(&receipt).unwrap();
+        assert_eq!(log.get(&sub.id), Some(&receipt));
+
+        let reloaded = ReceiptLog::load(&path);
+        assert_eq!(reloaded.get(&sub.id), Some(&receipt));
     }
 }
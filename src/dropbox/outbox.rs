@@ -0,0 +1,389 @@
+//! A local queue of submissions that couldn't be delivered
+//!
+//! When a student's network drops, or the dropbox is restarting, their submission
+//! would otherwise be lost. Instead, [`Submission::submit`](crate::dropbox::submission::Submission::submit)
+//! saves it to an outbox on disk. The outbox is flushed the next time the grader
+//! submits, or whenever you call [`flush_pending`](crate::dropbox::outbox::flush_pending).
+//!
+//! Each submission carries an id that the dropbox uses to ignore duplicates, so
+//! retrying a submission that actually made it through is harmless.
+
+// std uses
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::Context;
+use chrono::{DateTime, Duration, Local};
+use reqwest::StatusCode;
+use reqwest::header::RETRY_AFTER;
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use crate::Result;
+use crate::dropbox::Submission;
+use crate::dropbox::receipt::Receipt;
+
+
+/// Where the outbox is kept, relative to the directory the grader is run in
+pub const DEFAULT_OUTBOX_DIR: &str = ".rubric/outbox";
+
+// Retries back off exponentially from this many seconds...
+const BASE_BACKOFF_SECS: i64 = 30;
+// ...up to this many
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+
+/// A submission waiting in the outbox, along with where it should go.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSubmission {
+    /// The url the submission should be posted to
+    pub url: String,
+    /// The submission itself
+    pub submission: Submission,
+    /// How many times delivery has been tried
+    pub attempts: u32,
+    /// Don't retry before this time
+    pub next_attempt: DateTime<Local>,
+}
+
+impl PendingSubmission {
+    /// Returns true if enough time has passed to try again
+    pub fn due(&self) -> bool {
+        self.next_attempt <= Local::now()
+    }
+
+    // Pushes the next attempt back, doubling the wait each time
+    fn back_off(&mut self) {
+        self.attempts += 1;
+        let exp = self.attempts.saturating_sub(1).min(16);
+        let secs = (BASE_BACKOFF_SECS << exp).min(MAX_BACKOFF_SECS);
+        self.next_attempt = Local::now() + Duration::seconds(secs);
+    }
+
+    // Waits as long as the dropbox asked, instead of backing off
+    fn wait(&mut self, seconds: u64) {
+        self.attempts += 1;
+        self.next_attempt = Local::now() + Duration::seconds(seconds.min(u64::from(u32::MAX)) as i64);
+    }
+}
+
+
+/// What happened when sending a submission
+enum Delivery {
+    /// The dropbox has it
+    Delivered,
+    /// Couldn't reach the dropbox, the dropbox had an error, or it asked to
+    /// wait, maybe saying how many seconds for. Worth retrying.
+    Retry(Option<u64>),
+    /// The dropbox refused it. Retrying won't help.
+    Rejected(u16),
+}
+
+// Posts a submission and decides what to do about the response.
+// The receipt for a delivered submission is saved, the student isn't
+// around to see it.
+fn deliver(url: &str, sub: &Submission) -> Delivery {
+    match sub.post(url) {
+        Ok(resp) if resp.status().is_success() => {
+            if let Ok(receipt) = Receipt::from_response(resp) {
+                receipt.save().ok();
+            }
+            Delivery::Delivered
+        },
+        // Too soon after the last submission, it'll be taken later
+        Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+            let seconds = resp.headers().get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok());
+            Delivery::Retry(seconds)
+        },
+        Ok(resp) if resp.status().is_client_error() => Delivery::Rejected(resp.status().as_u16()),
+        _ => Delivery::Retry(None),
+    }
+}
+
+
+/// A directory of submissions waiting to be delivered.
+///
+/// Each pending submission is stored as its own JSON file, named after the
+/// submission's id.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    pub dir: PathBuf,
+}
+
+impl Default for Outbox {
+    /// An outbox in [`DEFAULT_OUTBOX_DIR`](crate::dropbox::outbox::DEFAULT_OUTBOX_DIR)
+    fn default() -> Self {
+        Outbox::new(DEFAULT_OUTBOX_DIR)
+    }
+}
+
+impl Outbox {
+    /// Uses the given directory as an outbox. It will be created
+    /// when the first submission is saved.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Outbox { dir: dir.as_ref().to_path_buf() }
+    }
+
+    // The file a submission is stored in
+    fn path_for(&self, sub: &Submission) -> PathBuf {
+        self.dir.join(format!("{}.json", sub.id))
+    }
+
+    /// Saves a submission to be delivered later.
+    ///
+    /// If the submission is already in the outbox, it's replaced.
+    pub fn push(&self, url: &str, sub: &Submission) -> Result<PathBuf> {
+        let mut pending = PendingSubmission {
+            url: String::from(url),
+            submission: sub.clone(),
+            attempts: 0,
+            next_attempt: Local::now(),
+        };
+        pending.back_off();
+        self.save(&pending)
+    }
+
+    // Writes a pending submission to disk
+    fn save(&self, pending: &PendingSubmission) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir).context(
+            format!("Couldn't create outbox at {}", self.dir.display())
+        )?;
+        let path = self.path_for(&pending.submission);
+        let json = serde_json::to_string_pretty(pending)?;
+        fs::write(&path, json).context(
+            format!("Couldn't write pending submission to {}", path.display())
+        )?;
+        Ok(path)
+    }
+
+    /// Returns every submission in the outbox, oldest first.
+    ///
+    /// Files that can't be read are skipped.
+    pub fn pending(&self) -> Vec<PendingSubmission> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut pending: Vec<PendingSubmission> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().map_or(false, |ext| ext == "json"))
+            .filter_map(|e| fs::read_to_string(e.path()).ok())
+            .filter_map(|s| serde_json::from_str(&s).ok())
+            .collect();
+        pending.sort_by(|a, b| a.submission.time.cmp(&b.submission.time));
+        pending
+    }
+
+    /// The number of submissions waiting in the outbox
+    pub fn len(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Returns true if there's nothing waiting in the outbox
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tries to deliver every submission that's due for a retry.
+    ///
+    /// Returns the number that were delivered. Submissions the dropbox
+    /// refuses outright are removed, as retrying won't help. If it says to
+    /// wait, with `429 Too Many Requests`, they're tried again when it says.
+    pub fn flush(&self) -> Result<usize> {
+        self.flush_where(|p| p.due())
+    }
+
+    /// Like [`flush`](crate::dropbox::outbox::Outbox::flush), but tries every
+    /// submission regardless of when it's next due.
+    pub fn flush_all(&self) -> Result<usize> {
+        self.flush_where(|_| true)
+    }
+
+    fn flush_where<F: Fn(&PendingSubmission) -> bool>(&self, should_try: F) -> Result<usize> {
+        let mut delivered = 0;
+        for mut pending in self.pending().into_iter().filter(|p| should_try(p)) {
+            let path = self.path_for(&pending.submission);
+            match deliver(&pending.url, &pending.submission) {
+                Delivery::Delivered => {
+                    fs::remove_file(&path).ok();
+                    delivered += 1;
+                },
+                Delivery::Rejected(code) => {
+                    eprintln!("Dropbox rejected a saved submission ({}), discarding it", code);
+                    fs::remove_file(&path).ok();
+                },
+                Delivery::Retry(Some(seconds)) => {
+                    pending.wait(seconds);
+                    self.save(&pending)?;
+                },
+                Delivery::Retry(None) => {
+                    pending.back_off();
+                    self.save(&pending)?;
+                },
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Sends a submission, saving it to the outbox if it couldn't be delivered.
+    ///
+    /// Anything already waiting in the outbox is flushed first.
+    pub fn send(&self, url: &str, sub: &Submission) -> std::result::Result<reqwest::blocking::Response, reqwest::Error> {
+        if let Err(e) = self.flush() {
+            eprintln!("Couldn't flush saved submissions: {}", e);
+        }
+
+        let result = sub.post(url);
+        let retry = match &result {
+            Ok(resp) => resp.status().is_server_error(),
+            Err(_) => true,
+        };
+
+        if retry {
+            match self.push(url, sub) {
+                Ok(_) => eprintln!("Couldn't reach the dropbox. Your submission was saved and will be sent next time."),
+                Err(e) => eprintln!("Couldn't reach the dropbox, and couldn't save your submission: {}", e),
+            }
+        }
+        result
+    }
+}
+
+
+/// Tries to deliver every submission waiting in the default outbox,
+/// regardless of backoff. Returns how many were delivered.
+///
+/// ```no_run
+/// use rubric::dropbox::outbox;
+///
+/// match outbox::flush_pending() {
+///     Ok(n) => println!("Delivered {} saved submissions", n),
+///     Err(e) => println!("Couldn't flush outbox: {}", e),
+/// }
+/// ```
+pub fn flush_pending() -> Result<usize> {
+    Outbox::default().flush_all()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tempdir::TempDir;
+
+    // Nothing listens on port 9 (discard), so delivery fails fast
+    const DEAD_URL: &str = "http://127.0.0.1:9/submit";
+
+    #[test]
+    fn test_push_and_list() {
+        let dir = TempDir::new("outbox").unwrap();
+        let outbox = Outbox::new(dir.path());
+        assert!(outbox.is_empty());
+
+        let sub = Submission::from_data(data! { "id" => "1234" });
+        let path = outbox.push(DEAD_URL, &sub).unwrap();
+        assert!(path.exists());
+
+        let pending = outbox.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].submission.id, sub.id);
+        assert_eq!(pending[0].attempts, 1);
+        assert!(!pending[0].due());
+
+        // Pushing the same submission replaces it
+        outbox.push(DEAD_URL, &sub).unwrap();
+        assert_eq!(outbox.len(), 1);
+    }
+
+    #[test]
+    fn test_failed_flush_backs_off() {
+        let dir = TempDir::new("outbox").unwrap();
+        let outbox = Outbox::new(dir.path());
+        outbox.push(DEAD_URL, &Submission::new()).unwrap();
+
+        // Not due yet, so nothing is tried
+        assert_eq!(outbox.flush().unwrap(), 0);
+        assert_eq!(outbox.pending()[0].attempts, 1);
+
+        assert_eq!(outbox.flush_all().unwrap(), 0);
+        let pending = &outbox.pending()[0];
+        assert_eq!(pending.attempts, 2);
+        assert!(pending.next_attempt > Local::now() + Duration::seconds(BASE_BACKOFF_SECS));
+    }
+
+    // A dropbox that answers one request with this response, then stops
+    fn respond_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/submit", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Read the whole request first, so the client isn't cut off
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text.lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_too_many_requests_waits() {
+        let dir = TempDir::new("outbox").unwrap();
+        let outbox = Outbox::new(dir.path());
+        let url = respond_once("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 600\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        outbox.push(&url, &Submission::new()).unwrap();
+
+        // Kept, and not tried again until the dropbox said
+        assert_eq!(outbox.flush_all().unwrap(), 0);
+        let pending = outbox.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 2);
+        assert!(pending[0].next_attempt > Local::now() + Duration::seconds(590));
+    }
+
+    #[test]
+    fn test_rejected_is_discarded() {
+        let dir = TempDir::new("outbox").unwrap();
+        let outbox = Outbox::new(dir.path());
+        let url = respond_once("HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        outbox.push(&url, &Submission::new()).unwrap();
+
+        assert_eq!(outbox.flush_all().unwrap(), 0);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let mut pending = PendingSubmission {
+            url: String::from(DEAD_URL),
+            submission: Submission::new(),
+            attempts: 40,
+            next_attempt: Local::now(),
+        };
+        pending.back_off();
+        assert!(pending.next_attempt <= Local::now() + Duration::seconds(MAX_BACKOFF_SECS));
+    }
+}
@@ -0,0 +1,202 @@
+//! Finding stored submissions
+//!
+//! The dropbox's instructor routes take these as query parameters, like
+//! `/submissions?student=s123&late=true`. Every part is optional, and a
+//! submission has to match all of the parts that are set.
+//!
+//! ```rust
+//! use rubric::Submission;
+//! use rubric::dropbox::query::SubmissionQuery;
+//!
+//! let mut sub = Submission::new();
+//! sub.data.insert(String::from("id"), String::from("s123"));
+//!
+//! let query = SubmissionQuery::new().student("s123").late(false);
+//! assert!(query.matches(&sub, "id"));
+//! assert!(!query.late(true).matches(&sub, "id"));
+//! ```
+
+// external uses
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+// internal uses
+use crate::Result;
+use crate::dropbox::Submission;
+
+
+/// Which submissions to find
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubmissionQuery {
+    /// The student, by the dropbox's identity key, or a member of the team
+    pub student: Option<String>,
+    /// The assignment it was for
+    pub assignment: Option<String>,
+    /// Received at or after this
+    pub since: Option<DateTime<Local>>,
+    /// Received before this
+    pub until: Option<DateTime<Local>>,
+    /// If it was late or not
+    pub late: Option<bool>,
+}
+
+impl SubmissionQuery {
+    /// A query that finds every submission
+    pub fn new() -> Self {
+        SubmissionQuery::default()
+    }
+
+    /// Only submissions from this student
+    pub fn student(mut self, student: &str) -> Self {
+        self.student = Some(String::from(student));
+        self
+    }
+
+    /// Only submissions for this assignment
+    pub fn assignment(mut self, assignment: &str) -> Self {
+        self.assignment = Some(String::from(assignment));
+        self
+    }
+
+    /// Only submissions received at or after this time
+    pub fn since(mut self, time: DateTime<Local>) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// Only submissions received before this time
+    pub fn until(mut self, time: DateTime<Local>) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Only late submissions, or only ones that weren't
+    pub fn late(mut self, late: bool) -> Self {
+        self.late = Some(late);
+        self
+    }
+
+    /// Returns true if the submission matches. Students are found by
+    /// `identity_key` in the submission's data, or in its team.
+    ///
+    /// Submissions are timed by when the dropbox received them,
+    /// or when they were made if that isn't known.
+    pub fn matches(&self, sub: &Submission, identity_key: &str) -> bool {
+        if let Some(student) = &self.student {
+            let is_student = sub.data.get(identity_key) == Some(student);
+            if !is_student && !sub.members.contains(student) {
+                return false;
+            }
+        }
+        if let Some(assignment) = &self.assignment {
+            if sub.assignment.as_ref() != Some(assignment) {
+                return false;
+            }
+        }
+        let time = sub.received_at.unwrap_or(sub.time);
+        if self.since.map_or(false, |since| time < since) {
+            return false;
+        }
+        if self.until.map_or(false, |until| time >= until) {
+            return false;
+        }
+        self.late.map_or(true, |late| sub.late == late)
+    }
+
+    /// The submissions that match, in the same order
+    pub fn filter(&self, subs: Vec<Submission>, identity_key: &str) -> Vec<Submission> {
+        subs.into_iter().filter(|s| self.matches(s, identity_key)).collect()
+    }
+}
+
+
+/// Parses a time for a query. It can be a date like `2020-05-24`, which is the
+/// start of that day, a local time like `2020-05-24 23:59:59`, or RFC 3339.
+///
+/// ```rust
+/// use rubric::dropbox::query::parse_time;
+///
+/// assert!(parse_time("2020-05-24").is_ok());
+/// assert!(parse_time("2020-05-24 23:59:59").is_ok());
+/// assert!(parse_time("2020-05-24T23:59:59-05:00").is_ok());
+/// assert!(parse_time("yesterday").is_err());
+/// ```
+pub fn parse_time(value: &str) -> Result<DateTime<Local>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%F %T")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%F").map(|d| d.and_hms(0, 0, 0)))
+        .map_err(|_| anyhow!("Invalid time '{}', expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS", value))?;
+    Local.from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(|| anyhow!("{} doesn't exist in the local timezone", value))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn student(id: &str) -> Submission {
+        let mut sub = Submission::new();
+        sub.data.insert(String::from("id"), String::from(id));
+        sub
+    }
+
+    #[test]
+    fn test_student() {
+        let query = SubmissionQuery::new().student("s1");
+        assert!(query.matches(&student("s1"), "id"));
+        assert!(!query.matches(&student("s2"), "id"));
+        // By another key
+        assert!(!query.matches(&student("s1"), "email"));
+
+        let mut team = Submission::new();
+        team.set_team(None, vec!["s1", "s2"]);
+        assert!(query.matches(&team, "id"));
+    }
+
+    #[test]
+    fn test_assignment_and_late() {
+        let mut sub = student("s1");
+        sub.set_assignment("lab1");
+        sub.late = true;
+
+        assert!(SubmissionQuery::new().matches(&sub, "id"));
+        assert!(SubmissionQuery::new().assignment("lab1").late(true).matches(&sub, "id"));
+        assert!(!SubmissionQuery::new().assignment("lab2").matches(&sub, "id"));
+        assert!(!SubmissionQuery::new().late(false).matches(&sub, "id"));
+    }
+
+    #[test]
+    fn test_time_range() {
+        let now = Local::now();
+        let mut sub = student("s1");
+        sub.time = now - Duration::days(3);
+        sub.received_at = Some(now);
+
+        // Goes by when it was received
+        let query = SubmissionQuery::new().since(now - Duration::days(1));
+        assert!(query.matches(&sub, "id"));
+        assert!(!query.until(now).matches(&sub, "id"));
+        assert!(SubmissionQuery::new().until(now + Duration::seconds(1)).matches(&sub, "id"));
+
+        sub.received_at = None;
+        assert!(!SubmissionQuery::new().since(now - Duration::days(1)).matches(&sub, "id"));
+
+        let subs = vec![student("s1"), student("s2"), student("s1")];
+        assert_eq!(SubmissionQuery::new().student("s1").filter(subs, "id").len(), 2);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let day = parse_time("2020-05-24").unwrap();
+        assert_eq!(day.format("%F %T").to_string(), "2020-05-24 00:00:00");
+        let time = parse_time(" 2020-05-24 23:59:59 ").unwrap();
+        assert_eq!(time.format("%F %T").to_string(), "2020-05-24 23:59:59");
+        assert!(parse_time("2020-05-24 25:00:00").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Proof that the dropbox recorded a submission
+//!
+//! When the dropbox records a submission, it responds with a [`Receipt`]. The receipt
+//! contains a digest of the record that was stored, so if there's ever a dispute
+//! the student's receipt can be checked against the results file. For a CSV file,
+//! use [`CsvStore::verify`](crate::dropbox::store::CsvStore::verify), since rows
+//! get more cells when columns are added.
+
+// std uses
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::{bail, Context};
+use chrono::{DateTime, Local};
+use paris::Logger;
+use reqwest::blocking::Response;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// internal uses
+use crate::{Result, HR_TIMESTAMP_FORMAT};
+use crate::dropbox::Submission;
+use crate::dropbox::submission::random_id;
+use crate::dropbox::rejection::Rejection;
+
+
+/// Where receipts are saved, relative to the directory the grader is run in
+pub const DEFAULT_RECEIPT_DIR: &str = ".rubric/receipts";
+
+
+/// Returns the hex encoded SHA-256 digest of a record
+pub fn digest(record: &str) -> String {
+    format!("{:x}", Sha256::digest(record.as_bytes()))
+}
+
+
+/// A receipt for a recorded submission, issued by the dropbox.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// A random id, unique to this receipt
+    pub receipt_id: String,
+    /// The id of the submission this receipt is for
+    pub submission_id: String,
+    /// When the dropbox received the submission, by the dropbox's clock
+    pub received_at: DateTime<Local>,
+    /// The grade the dropbox recorded
+    pub grade: isize,
+    /// SHA-256 digest of the record the dropbox stored
+    pub digest: String,
+    /// The student who submitted, if the dropbox counts attempts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student: Option<String>,
+    /// Which attempt this was for the student
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+    /// How many attempts the student gets, if there's a limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// The team, if it was a team submission.
+    /// See [`Submission::team_key`](crate::dropbox::submission::Submission::team_key).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    /// Everyone on the team
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+}
+
+impl Receipt {
+    /// Issues a receipt for a submission, and the record that was stored for it.
+    ///
+    /// This is used by the dropbox, you shouldn't need to call it.
+    pub fn new(sub: &Submission, record: &str) -> Receipt {
+        Receipt {
+            receipt_id: random_id(),
+            submission_id: sub.id.clone(),
+            received_at: sub.received_at.unwrap_or_else(Local::now),
+            grade: sub.grade,
+            digest: digest(record),
+            student: None,
+            attempt: None,
+            max_attempts: None,
+            team: sub.team_key(),
+            members: sub.members.clone(),
+        }
+    }
+
+    /// Records which attempt the submission was for the student.
+    ///
+    /// This is used by the dropbox, you shouldn't need to call it.
+    pub fn with_attempt(mut self, student: &str, attempt: u32, max_attempts: Option<u32>) -> Receipt {
+        self.student = Some(String::from(student));
+        self.attempt = Some(attempt);
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Describes the attempt, like "attempt 2 of 3", if the dropbox counted it
+    ///
+    /// ```rust
+    /// use rubric::Submission;
+    /// use rubric::dropbox::Receipt;
+    ///
+    /// let receipt = Receipt::new(&Submission::new(), "record").with_attempt("s123", 2, Some(3));
+    /// assert_eq!(receipt.attempt_description().unwrap(), "attempt 2 of 3");
+    /// ```
+    pub fn attempt_description(&self) -> Option<String> {
+        let attempt = self.attempt?;
+        Some(match self.max_attempts {
+            Some(max) => format!("attempt {} of {}", attempt, max),
+            None => format!("attempt {}", attempt),
+        })
+    }
+
+    /// Returns true if this receipt was issued for the given record
+    ///
+    /// ```rust
+    /// use rubric::Submission;
+    /// use rubric::dropbox::{Receipt, AsCsv};
+    ///
+    /// let sub = Submission::new();
+    /// let receipt = Receipt::new(&sub, &sub.as_csv());
+    /// assert!(receipt.verify(&sub.as_csv()));
+    /// assert!(!receipt.verify("some other record"));
+    /// ```
+    pub fn verify(&self, record: &str) -> bool {
+        self.digest == digest(record)
+    }
+
+    /// Reads a receipt from the dropbox's response.
+    ///
+    /// Returns an error if the dropbox didn't accept the submission, or
+    /// didn't send a receipt back. If the dropbox said why it refused the
+    /// submission, that's in the error.
+    pub fn from_response(resp: Response) -> Result<Receipt> {
+        let status = resp.status();
+        if !status.is_success() {
+            match resp.json::<Rejection>() {
+                Ok(rejection) => bail!("Dropbox responded with {}: {}", status, rejection.error),
+                Err(_) => bail!("Dropbox responded with {}", status),
+            }
+        }
+        resp.json::<Receipt>().context("Dropbox didn't send a valid receipt")
+    }
+
+    /// Saves the receipt in [`DEFAULT_RECEIPT_DIR`](crate::dropbox::receipt::DEFAULT_RECEIPT_DIR)
+    pub fn save(&self) -> Result<PathBuf> {
+        self.save_to(DEFAULT_RECEIPT_DIR)
+    }
+
+    /// Saves the receipt as JSON in the given directory, named
+    /// after the receipt id. The directory is created if necessary.
+    pub fn save_to<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).context(
+            format!("Couldn't create receipt directory {}", dir.display())
+        )?;
+        let path = dir.join(format!("{}.json", self.receipt_id));
+        fs::write(&path, serde_json::to_string_pretty(self)?).context(
+            format!("Couldn't save receipt to {}", path.display())
+        )?;
+        Ok(path)
+    }
+
+    /// Prints the receipt for the student
+    pub fn print(&self) {
+        let mut log = Logger::new();
+        log.success("<bold>Submission recorded</>");
+        log.info(format!("Receipt: {}", self.receipt_id));
+        log.info(format!("Received at: {}", self.received_at.format(HR_TIMESTAMP_FORMAT)));
+        log.info(format!("Recorded grade: <bold>{}</>", self.grade));
+        if let Some(attempt) = self.attempt_description() {
+            log.info(format!("This was {}", attempt));
+        }
+        if !self.members.is_empty() {
+            log.info(format!("Recorded for: {}", self.members.join(", ")));
+        }
+        log.info(format!("Digest: {}", self.digest));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_new_receipt() {
+        let mut sub = Submission::new();
+        sub.grade = 75;
+        let receipt = Receipt::new(&sub, "a record");
+
+        assert_eq!(receipt.submission_id, sub.id);
+        assert_eq!(receipt.grade, 75);
+        assert_eq!(receipt.digest.len(), 64);
+        assert!(receipt.verify("a record"));
+        assert!(!receipt.verify("a different record"));
+    }
+
+    #[test]
+    fn test_known_digest() {
+        assert_eq!(
+            digest("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_attempts() {
+        let receipt = Receipt::new(&Submission::new(), "record");
+        assert!(receipt.attempt_description().is_none());
+        // Receipts without attempts don't mention them
+        assert!(!serde_json::to_string(&receipt).unwrap().contains("attempt"));
+
+        let receipt = receipt.with_attempt("s123", 4, None);
+        assert_eq!(receipt.attempt_description().unwrap(), "attempt 4");
+        let json = serde_json::to_string(&receipt).unwrap();
+        assert_eq!(serde_json::from_str::<Receipt>(&json).unwrap(), receipt);
+    }
+
+    #[test]
+    fn test_save_receipt() {
+        let dir = TempDir::new("receipts").unwrap();
+        let receipt = Receipt::new(&Submission::new(), "record");
+        let path = receipt.save_to(dir.path()).unwrap();
+
+        let saved: Receipt = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(saved, receipt);
+    }
+}
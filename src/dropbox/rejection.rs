@@ -0,0 +1,55 @@
+//! Why the dropbox refused a submission
+//!
+//! When the dropbox refuses a submission for something the student can act on, like
+//! running out of attempts, it responds with a [`Rejection`] as JSON. The grader reads
+//! it to tell the student what happened.
+
+// external uses
+use serde::{Deserialize, Serialize};
+
+
+/// The body of a response refusing a submission
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// The HTTP status code. This is the response's status, so it isn't in the body.
+    #[serde(skip)]
+    pub status: u16,
+    /// What went wrong, in words meant for the student
+    pub error: String,
+    /// How many seconds to wait before trying again, if waiting will help
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+impl Rejection {
+    /// A rejection with a status code and a reason
+    pub fn new(status: u16, error: &str) -> Self {
+        Rejection { status, error: String::from(error), retry_after: None }
+    }
+
+    /// Tells the student to try again after this many seconds.
+    /// This is also sent as the `Retry-After` header.
+    pub fn retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_json() {
+        let rejection = Rejection::new(429, "Slow down");
+        let json = serde_json::to_string(&rejection).unwrap();
+        assert_eq!(json, r#"{"error":"Slow down"}"#);
+
+        let json = serde_json::to_string(&rejection.retry_after(30)).unwrap();
+        let back: Rejection = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.retry_after, Some(30));
+        assert_eq!(back.error, "Slow down");
+    }
+}
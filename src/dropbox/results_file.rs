@@ -6,6 +6,7 @@ use std::io::Write;
 // External uses
 // For error handling
 use crate::{Result, Error};
+use crate::dropbox::csv::{self, DEFAULT_DELIMITER};
 use anyhow::Context;
 
 
@@ -44,6 +45,9 @@ use anyhow::Context;
 /// ```
 pub trait AsCsv {
     /// The item in CSV format. This should *not* append a newline.
+    ///
+    /// Fields should be escaped with [`csv::escape_field`](crate::dropbox::csv::escape_field)
+    /// or [`csv::write_row`](crate::dropbox::csv::write_row).
     fn as_csv(&self) -> String;
     /// The filename where this type should be saved.
     /// Usually this should just be `<item>.csv`
@@ -51,12 +55,41 @@ pub trait AsCsv {
     /// The header for the csv file. Should match the fields
     /// in `as_csv()`
     fn header(&self) -> String;
+
+    /// The item in CSV format, separated by something other than commas
+    fn as_csv_with(&self, delimiter: char) -> String {
+        redelimit(&self.as_csv(), delimiter)
+    }
+
+    /// The header, separated by something other than commas
+    fn header_with(&self, delimiter: char) -> String {
+        redelimit(&self.header(), delimiter)
+    }
+}
+
+// Writes a comma separated row with another delimiter.
+// If it can't be parsed, it's left alone.
+fn redelimit(row: &str, delimiter: char) -> String {
+    if delimiter == DEFAULT_DELIMITER {
+        return String::from(row);
+    }
+    match csv::parse(row, DEFAULT_DELIMITER) {
+        Ok(rows) => rows.iter()
+            .map(|r| csv::write_row(r, delimiter))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(_) => String::from(row),
+    }
 }
 
 /// A CSV results file containing the results of the grading process.
 #[derive(Debug)]
 pub struct ResultsFile {
     pub path: PathBuf,
+    /// What separates fields written with [`write_csv`](crate::dropbox::ResultsFile::write_csv).
+    /// This is a comma unless it's changed with
+    /// [`with_delimiter`](crate::dropbox::ResultsFile::with_delimiter).
+    pub delimiter: char,
     handle: File
 }
 
@@ -100,6 +133,7 @@ impl ResultsFile {
 
         let mut rf = ResultsFile {
             path: full_path,
+            delimiter: DEFAULT_DELIMITER,
             handle
         };
         if rf.length() == 0 {
@@ -116,6 +150,7 @@ impl ResultsFile {
         let full_path = canonicalize(path)?;
         Ok(ResultsFile {
             path: full_path,
+            delimiter: DEFAULT_DELIMITER,
             handle
         })
     }
@@ -204,7 +239,22 @@ impl ResultsFile {
     /// # remove_file(point.filename()).unwrap()
     /// ```
     pub fn write_csv<R: AsCsv>(&mut self, record: &R) -> Result<usize> {
-        self.append(&format!("{}", record.as_csv()))
+        self.append(&record.as_csv_with(self.delimiter))
+    }
+
+    /// Separates fields with something other than a comma, like `'\t'` or `';'`.
+    ///
+    /// This only changes what [`write_csv`](crate::dropbox::ResultsFile::write_csv) writes,
+    /// `append` writes exactly what it's given.
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Reads the file back into records, using the first line as the header.
+    /// See [`csv::read_file`](crate::dropbox::csv::read_file).
+    pub fn records(&self) -> Result<Vec<csv::CsvRecord>> {
+        csv::read_file(&self.path, self.delimiter)
     }
 }
 
@@ -342,6 +392,23 @@ mod tests {
         delete(&file);
     }
 
+    #[test]
+    fn test_write_csv_with_delimiter() {
+        let mut file = test_dir();
+        file.push("write_csv_tabs.tsv");
+        delete(&file);
+
+        let mut rf = ResultsFile::new(&file, "x\ty").unwrap().with_delimiter('\t');
+        rf.write_csv(&Point { x: 5, y: 7 }).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "x\ty\n5\t7\n");
+
+        let records = rf.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].parse::<i32>("y").unwrap(), 7);
+
+        delete(&file);
+    }
+
     #[test]
     fn test_results_file_for_csv_item() {
         let point = Point { x: 32, y: 37 };
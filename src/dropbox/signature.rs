@@ -0,0 +1,172 @@
+//! Signing submissions so the dropbox can tell they're genuine
+//!
+//! A submission with a [`Fingerprint`](crate::dropbox::fingerprint::Fingerprint) is signed
+//! with an HMAC (SHA-256) of its contents, keyed with the fingerprint's secret. Only the
+//! signature is sent, never the secret. A dropbox that knows the secret can then reject
+//! any submission that was forged or changed after grading.
+//!
+//! The signature covers the submission's JSON, with object keys sorted and the
+//! `signature` field removed. Signing and verifying both work on JSON rather than the
+//! `Submission` struct, so things like timezones can't change the payload on the way.
+
+// external uses
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::Value;
+use sha2::Sha256;
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The JSON field that holds the signature
+pub const SIGNATURE_FIELD: &str = "signature";
+
+
+/// Returns the canonical payload for a JSON submission: compact JSON with
+/// sorted keys and no signature field.
+pub fn canonical_payload(value: &Value) -> String {
+    let mut value = value.clone();
+    if let Value::Object(map) = &mut value {
+        map.remove(SIGNATURE_FIELD);
+    }
+    let mut payload = String::new();
+    write_canonical(&value, &mut payload);
+    payload
+}
+
+// Writes compact JSON with every object's keys sorted. serde_json only sorts
+// them without its `preserve_order` feature, and any crate could turn that on.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        },
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        },
+        // Everything else has no keys to sort
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Returns the hex encoded HMAC-SHA256 of the payload
+///
+/// ```rust
+/// use rubric::dropbox::signature;
+///
+/// let sig = signature::sign("secret", "payload");
+/// assert!(signature::verify("secret", "payload", &sig));
+/// assert!(!signature::verify("wrong secret", "payload", &sig));
+/// ```
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = HmacSha256::new_varkey(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns true if the signature is the HMAC of the payload with this secret.
+///
+/// The comparison is constant time.
+pub fn verify(secret: &str, payload: &str, signature: &str) -> bool {
+    let tag = match decode_hex(signature) {
+        Some(t) => t,
+        None => return false,
+    };
+
+    let mut mac = HmacSha256::new_varkey(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac.verify(&tag).is_ok()
+}
+
+/// Verifies the signature inside a JSON submission.
+///
+/// Returns false if there's no signature.
+pub fn verify_value(secret: &str, value: &Value) -> bool {
+    match value.get(SIGNATURE_FIELD).and_then(|s| s.as_str()) {
+        Some(sig) => verify(secret, &canonical_payload(value), sig),
+        None => false,
+    }
+}
+
+// Turns a hex string into bytes, None if it isn't valid hex
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_bad_signatures() {
+        assert!(!verify("secret", "payload", "not hex"));
+        assert!(!verify("secret", "payload", "abc"));
+        assert!(!verify("secret", "payload", ""));
+        assert!(!verify("secret", "payload", &sign("secret", "other payload")));
+    }
+
+    #[test]
+    fn test_canonical_payload_ignores_order_and_signature() {
+        let a: Value = serde_json::from_str(r#"{"b":1,"a":{"y":2,"x":3},"signature":"abc"}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"a":{"x":3,"y":2},"b":1}"#).unwrap();
+        assert_eq!(canonical_payload(&a), canonical_payload(&b));
+    }
+
+    #[test]
+    fn test_canonical_payload_sorts_keys() {
+        let value: Value = serde_json::from_str(r#"{"b":[{"z":"\"q\"","a":null}],"a":true}"#).unwrap();
+        assert_eq!(canonical_payload(&value), r#"{"a":true,"b":[{"a":null,"z":"\"q\""}]}"#);
+    }
+
+    #[test]
+    fn test_verify_value() {
+        let mut value: Value = serde_json::from_str(r#"{"grade":10}"#).unwrap();
+        assert!(!verify_value("secret", &value));
+
+        let sig = sign("secret", &canonical_payload(&value));
+        value[SIGNATURE_FIELD] = Value::String(sig);
+        assert!(verify_value("secret", &value));
+
+        value["grade"] = Value::from(100);
+        assert!(!verify_value("secret", &value));
+    }
+}
@@ -0,0 +1,774 @@
+//! Where the dropbox keeps submissions
+//!
+//! The dropbox doesn't care how submissions are stored, it just hands each one to a
+//! [`SubmissionStore`](crate::dropbox::store::SubmissionStore). There are a few to choose from:
+//!
+//! - [`CsvStore`](crate::dropbox::store::CsvStore), the default. A CSV file you can open in a spreadsheet.
+//!   Submissions read back from it are missing some things, see [`CsvStore::submissions`](crate::dropbox::store::CsvStore).
+//! - [`JsonLinesStore`](crate::dropbox::store::JsonLinesStore). Every submission as JSON, one per line.
+//!   Nothing is lost, so it can be read back into submissions.
+//! - `SqliteStore`, an SQLite database. This needs the `sqlite` feature.
+//!
+//! Choose one with [`DropboxConfig::store`](crate::dropbox::config::DropboxConfig::store).
+
+// std uses
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// external uses
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Local};
+
+// internal uses
+use crate::{Result, TIMESTAMP_FORMAT};
+use crate::dropbox::{AsCsv, Receipt, ResultsFile, Submission};
+use crate::dropbox::csv::{self, CsvRecord, DEFAULT_DELIMITER};
+
+
+/// Somewhere the dropbox can keep submissions.
+///
+/// The dropbox only uses one store at a time, behind a lock.
+pub trait SubmissionStore: Send {
+    /// Stores the rows for one submission. There's usually one row,
+    /// but team submissions have one for each member.
+    ///
+    /// Returns the record that was stored, which the receipt's digest is made from.
+    /// It shouldn't change once it's stored, so the receipt can always be checked.
+    fn store(&mut self, rows: &[Submission]) -> Result<String>;
+
+    /// Every submission that's been stored, oldest first.
+    ///
+    /// Stores that can't read submissions back return an error.
+    fn submissions(&self) -> Result<Vec<Submission>>;
+
+    /// Where the submissions are, for people to read
+    fn location(&self) -> String;
+}
+
+
+/// The kinds of store the dropbox can use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoreKind {
+    Csv,
+    JsonLines,
+    Sqlite,
+}
+
+impl StoreKind {
+    /// The file extension this kind of store uses
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StoreKind::Csv => "csv",
+            StoreKind::JsonLines => "jsonl",
+            StoreKind::Sqlite => "sqlite",
+        }
+    }
+
+    /// Opens a store of this kind at the given path, creating it if it doesn't exist
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Box<dyn SubmissionStore>> {
+        let path = path.as_ref();
+        Ok(match self {
+            StoreKind::Csv => Box::new(CsvStore::open(path, DEFAULT_DELIMITER)?),
+            StoreKind::JsonLines => Box::new(JsonLinesStore::open(path)?),
+            #[cfg(feature = "sqlite")]
+            StoreKind::Sqlite => Box::new(sqlite::SqliteStore::open(path)?),
+            #[cfg(not(feature = "sqlite"))]
+            StoreKind::Sqlite => bail!("Can't store submissions in SQLite, rubric was built without the sqlite feature"),
+        })
+    }
+}
+
+impl Default for StoreKind {
+    fn default() -> Self {
+        StoreKind::Csv
+    }
+}
+
+impl FromStr for StoreKind {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(StoreKind::Csv),
+            "jsonl" | "json_lines" => Ok(StoreKind::JsonLines),
+            "sqlite" => Ok(StoreKind::Sqlite),
+            _ => Err(anyhow!("Unknown store '{}', expected csv, jsonl, or sqlite", s)),
+        }
+    }
+}
+
+impl fmt::Display for StoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+
+/// Stores submissions in a CSV file.
+///
+/// Submissions don't all have the same columns, since their data and fingerprints
+/// can differ. The store keeps a schema, the columns in the file's header, and writes
+/// each value under the column with its name. Columns a submission doesn't have are
+/// left empty. When a submission has a new column, it's added to the end, and the
+/// file is rewritten so the header always matches the rows.
+#[derive(Debug)]
+pub struct CsvStore {
+    file: ResultsFile,
+    columns: Vec<String>,
+}
+
+impl CsvStore {
+    /// Opens the CSV file, creating it if it doesn't exist.
+    /// Fields are separated by `delimiter`, usually a comma.
+    ///
+    /// If the file already has a header, that's the schema new rows are written in.
+    pub fn open<P: AsRef<Path>>(path: P, delimiter: char) -> Result<CsvStore> {
+        let path = path.as_ref();
+        let file = ResultsFile::new_blank(path)
+            .context(format!("Couldn't open results file {}", path.display()))?
+            .with_delimiter(delimiter);
+
+        let contents = fs::read_to_string(&file.path)?;
+        let columns = csv::parse(&contents, delimiter)?.into_iter().next().unwrap_or_default();
+        Ok(CsvStore { file, columns })
+    }
+
+    /// The columns in the file, in order
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Returns true if the receipt was issued for rows in this file.
+    ///
+    /// A team's rows are stored together, so they're checked together.
+    pub fn verify(&self, receipt: &Receipt) -> Result<bool> {
+        let records = self.file.records()?;
+        for start in 0..records.len() {
+            let mut record = String::new();
+            for (i, r) in records.iter().enumerate().skip(start) {
+                // The rest of a team has the same time
+                if i > start {
+                    if r.get("time") != records[start].get("time") {
+                        break;
+                    }
+                    record.push('\n');
+                }
+                record.push_str(&row_record(r));
+                if receipt.verify(&record) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    // Rewrites the file with the current columns, leaving new ones empty.
+    // The new file is written next to the old one, then moved over it.
+    fn rewrite(&mut self) -> Result<()> {
+        let delimiter = self.file.delimiter;
+        let records = self.file.records()?;
+
+        let mut lines = vec![csv::write_row(&self.columns, delimiter)];
+        for r in records {
+            let values: Vec<&str> = self.columns.iter().map(|c| r.get(c).unwrap_or("")).collect();
+            lines.push(csv::write_row(&values, delimiter));
+        }
+
+        let path = self.file.path.clone();
+        let tmp = path.with_extension("rewrite");
+        fs::write(&tmp, lines.join("\n") + "\n")
+            .context(format!("Couldn't rewrite results file {}", path.display()))?;
+        fs::rename(&tmp, &path)
+            .context(format!("Couldn't replace results file {}", path.display()))?;
+
+        // The old handle still points at the file that was replaced
+        self.file = ResultsFile::new_blank(&path)?.with_delimiter(delimiter);
+        Ok(())
+    }
+}
+
+// A row's values by column, sorted and without empty cells, as JSON.
+// Adding columns to the file doesn't change it.
+fn receipt_record<'a, I: IntoIterator<Item = (&'a String, &'a String)>>(fields: I) -> String {
+    let values: BTreeMap<&String, &String> = fields.into_iter().filter(|(_, v)| !v.is_empty()).collect();
+    serde_json::to_string(&values).expect("Strings are always valid JSON")
+}
+
+/// The record a receipt's digest is made from, for one row of a CSV file.
+///
+/// It's the row's values by column name, leaving out empty cells, so receipts
+/// still verify after the store adds columns to the file.
+pub fn row_record(record: &CsvRecord) -> String {
+    receipt_record(record.header.iter().zip(&record.values))
+}
+
+/// Criterion columns start with this in a stored CSV file, so they aren't
+/// mistaken for data when submissions are read back
+pub const CRITERION_COLUMN_PREFIX: &str = "criterion:";
+
+/// A submission's CSV values, named by their columns.
+///
+/// Columns without a name are left out. If a name is used more than once,
+/// like a data key called `grade`, the later ones get `_2`, `_3`, and so on.
+/// Each criterion's column starts with [`CRITERION_COLUMN_PREFIX`].
+pub fn named_fields(sub: &Submission) -> Result<Vec<(String, String)>> {
+    let header = csv::parse(&sub.header(), DEFAULT_DELIMITER)?.into_iter().next().unwrap_or_default();
+    let values = csv::parse(&sub.as_csv(), DEFAULT_DELIMITER)?.into_iter().next().unwrap_or_default();
+
+    // The criteria come right after the failed list
+    let first_criterion = header.iter().position(|h| h == "failed").map_or(0, |i| i + 1);
+    let criteria = first_criterion..first_criterion + sub.results.len();
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for (i, (name, value)) in header.into_iter().zip(values).enumerate() {
+        if name.is_empty() {
+            continue;
+        }
+        let name = if criteria.contains(&i) {
+            format!("{}{}", CRITERION_COLUMN_PREFIX, name)
+        } else {
+            name
+        };
+        let mut unique = name.clone();
+        let mut n = 1;
+        while fields.iter().any(|(f, _)| f == &unique) {
+            n += 1;
+            unique = format!("{}_{}", name, n);
+        }
+        fields.push((unique, value));
+    }
+    Ok(fields)
+}
+
+// Adds any columns the rows have that aren't in the schema yet, in the order they're found
+fn add_columns(columns: &mut Vec<String>, rows: &[Vec<(String, String)>]) {
+    for (name, _) in rows.iter().flatten() {
+        if !columns.contains(name) {
+            columns.push(name.clone());
+        }
+    }
+}
+
+// Writes a submission's named fields as a row in the schema
+fn schema_row(columns: &[String], fields: &[(String, String)], delimiter: char) -> String {
+    let values: Vec<&str> = columns.iter()
+        .map(|c| fields.iter().find(|(n, _)| n == c).map_or("", |(_, v)| v.as_str()))
+        .collect();
+    csv::write_row(&values, delimiter)
+}
+
+/// Submissions as CSV, with a header. The columns are every column
+/// any of them has, the same way [`CsvStore`](crate::dropbox::store::CsvStore) writes them.
+pub fn to_csv(subs: &[Submission], delimiter: char) -> Result<String> {
+    let rows = subs.iter().map(named_fields).collect::<Result<Vec<_>>>()?;
+    let mut columns = Vec::new();
+    add_columns(&mut columns, &rows);
+
+    let mut lines = vec![csv::write_row(&columns, delimiter)];
+    lines.extend(rows.iter().map(|fields| schema_row(&columns, fields, delimiter)));
+    Ok(lines.join("\n") + "\n")
+}
+
+// Reads a time the way submissions write them, or RFC 3339
+fn parse_timestamp(value: &str) -> Result<DateTime<Local>> {
+    DateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .map(|t| t.with_timezone(&Local))
+        .map_err(|_| anyhow!("Invalid time '{}'", value))
+}
+
+// A list in one cell, separated by `;`
+fn split_list(value: &str) -> Vec<String> {
+    value.split(';').filter(|v| !v.is_empty()).map(String::from).collect()
+}
+
+// A submission from one row of a CSV file. See CsvStore::submissions for what's lost.
+fn from_record(row: usize, record: &CsvRecord) -> Result<Submission> {
+    if record.get("time").is_none() {
+        bail!("There's no time column");
+    }
+    let mut sub = Submission::new();
+    sub.id = format!("row-{}", row);
+
+    for (name, value) in record.header.iter().zip(&record.values) {
+        match name.as_str() {
+            "time" => sub.time = parse_timestamp(value)?,
+            "received" if !value.is_empty() => sub.received_at = Some(parse_timestamp(value)?),
+            "flags" => sub.flags = split_list(value),
+            "late" => sub.late = value.parse().context(format!("Invalid late '{}'", value))?,
+            "grade" => sub.grade = value.parse().context(format!("Invalid grade '{}'", value))?,
+            "passed" => sub.passed = split_list(value),
+            "failed" => sub.failed = split_list(value),
+            "team" if !value.is_empty() => sub.team = Some(value.clone()),
+            _ if !value.is_empty() => { sub.data.insert(name.clone(), value.clone()); },
+            _ => {},
+        }
+    }
+    Ok(sub)
+}
+
+impl SubmissionStore for CsvStore {
+    fn store(&mut self, rows: &[Submission]) -> Result<String> {
+        if rows.is_empty() {
+            bail!("Nothing to store");
+        }
+        let delimiter = self.file.delimiter;
+        let rows = rows.iter().map(named_fields).collect::<Result<Vec<_>>>()?;
+
+        // New columns go on the end
+        let known = self.columns.len();
+        add_columns(&mut self.columns, &rows);
+
+        if self.file.length() == 0 {
+            self.file.append(&csv::write_row(&self.columns, delimiter))
+                .context("Couldn't write CSV header, the file is likely locked by another process")?;
+        } else if self.columns.len() > known {
+            self.rewrite()?;
+        }
+
+        let written = rows.iter()
+            .map(|fields| schema_row(&self.columns, fields, delimiter))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.file.append(&written)?;
+
+        // Not the rows as written, since they get more cells when columns are added
+        Ok(rows.iter()
+            .map(|fields| receipt_record(fields.iter().map(|(n, v)| (n, v))))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// The submissions in the file, as well as they can be read back.
+    ///
+    /// The file doesn't have everything, so some things are lost. There are
+    /// no ids, so each submission's id is its row, like `row-1`. Columns the
+    /// file doesn't have a place for, like criteria, fingerprints, and artifacts,
+    /// end up in the data. Criteria are named like `criterion:compiles` there. Rows that can't be read are skipped with a warning.
+    fn submissions(&self) -> Result<Vec<Submission>> {
+        let records = self.file.records()?;
+        let mut subs = Vec::new();
+        for (i, r) in records.iter().enumerate() {
+            match from_record(i + 1, r) {
+                Ok(sub) => subs.push(sub),
+                Err(e) => eprintln!("Skipping row {} of {}: {}", i + 1, self.location(), e),
+            }
+        }
+        Ok(subs)
+    }
+
+    fn location(&self) -> String {
+        self.file.path.display().to_string()
+    }
+}
+
+
+/// Stores every submission as JSON, one per line
+#[derive(Debug, Clone)]
+pub struct JsonLinesStore {
+    pub path: PathBuf,
+}
+
+impl JsonLinesStore {
+    /// Uses the given file, creating it if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JsonLinesStore> {
+        let path = path.as_ref().to_path_buf();
+        OpenOptions::new().append(true).create(true).open(&path)
+            .context(format!("Couldn't open results file {}", path.display()))?;
+        Ok(JsonLinesStore { path })
+    }
+}
+
+/// Submissions as JSON, one per line. There's no line break at the end.
+pub fn json_lines(rows: &[Submission]) -> Result<String> {
+    let lines = rows.iter()
+        .map(serde_json::to_string)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(lines.join("\n"))
+}
+
+impl SubmissionStore for JsonLinesStore {
+    fn store(&mut self, rows: &[Submission]) -> Result<String> {
+        if rows.is_empty() {
+            bail!("Nothing to store");
+        }
+        let record = json_lines(rows)?;
+        let mut file = OpenOptions::new().append(true).create(true).open(&self.path)?;
+        writeln!(file, "{}", record)?;
+        Ok(record)
+    }
+
+    fn submissions(&self) -> Result<Vec<Submission>> {
+        let contents = fs::read_to_string(&self.path)
+            .context(format!("Couldn't read {}", self.path.display()))?;
+        contents.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Ok(serde_json::from_str(l)?))
+            .collect()
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::{Path, PathBuf};
+
+    use anyhow::{bail, Context};
+    use rusqlite::{params, Connection, NO_PARAMS};
+
+    use crate::Result;
+    use crate::dropbox::Submission;
+    use super::{json_lines, SubmissionStore};
+
+    /// Stores submissions in an SQLite database, in a `submissions` table.
+    ///
+    /// A few columns are pulled out so they're easy to query, and the
+    /// whole submission is kept as JSON.
+    pub struct SqliteStore {
+        path: PathBuf,
+        conn: Connection,
+    }
+
+    impl SqliteStore {
+        /// Opens the database, creating it and the table if they don't exist
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore> {
+            let path = path.as_ref().to_path_buf();
+            let conn = Connection::open(&path)
+                .context(format!("Couldn't open database {}", path.display()))?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS submissions (
+                    row_id       INTEGER PRIMARY KEY,
+                    id           TEXT NOT NULL,
+                    assignment   TEXT,
+                    time         TEXT NOT NULL,
+                    received_at  TEXT,
+                    grade        INTEGER NOT NULL,
+                    late         INTEGER NOT NULL,
+                    json         TEXT NOT NULL
+                )",
+                NO_PARAMS,
+            )?;
+            Ok(SqliteStore { path, conn })
+        }
+    }
+
+    impl SubmissionStore for SqliteStore {
+        fn store(&mut self, rows: &[Submission]) -> Result<String> {
+            if rows.is_empty() {
+                bail!("Nothing to store");
+            }
+            let tx = self.conn.transaction()?;
+            for sub in rows {
+                tx.execute(
+                    "INSERT INTO submissions (id, assignment, time, received_at, grade, late, json)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        sub.id,
+                        sub.assignment,
+                        sub.time.to_rfc3339(),
+                        sub.received_at.map(|t| t.to_rfc3339()),
+                        sub.grade as i64,
+                        sub.late,
+                        serde_json::to_string(sub)?,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            json_lines(rows)
+        }
+
+        fn submissions(&self) -> Result<Vec<Submission>> {
+            let mut stmt = self.conn.prepare("SELECT json FROM submissions ORDER BY row_id")?;
+            let rows = stmt.query_map(NO_PARAMS, |row| row.get::<_, String>(0))?;
+            let mut subs = Vec::new();
+            for json in rows {
+                subs.push(serde_json::from_str(&json?)?);
+            }
+            Ok(subs)
+        }
+
+        fn location(&self) -> String {
+            self.path.display().to_string()
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dropbox::{CriterionResult, ResultStatus};
+    use tempdir::TempDir;
+
+    fn graded(grade: isize) -> Submission {
+        let mut sub = Submission::new();
+        sub.grade = grade;
+        sub
+    }
+
+    #[test]
+    fn test_store_kind_from_str() {
+        assert_eq!("csv".parse::<StoreKind>().unwrap(), StoreKind::Csv);
+        assert_eq!("JSONL".parse::<StoreKind>().unwrap(), StoreKind::JsonLines);
+        assert_eq!("sqlite".parse::<StoreKind>().unwrap(), StoreKind::Sqlite);
+        assert!("xml".parse::<StoreKind>().is_err());
+    }
+
+    #[test]
+    fn test_csv_store() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.csv");
+        let mut store = StoreKind::Csv.open(&path).unwrap();
+
+        let mut first = graded(10);
+        first.data.insert(String::from("id"), String::from("s1"));
+        let record = store.store(&[first.clone()]).unwrap();
+        assert!(record.contains(r#""id":"s1""#));
+        store.store(&[graded(20), graded(30)]).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], first.header());
+        assert_eq!(store.submissions().unwrap().len(), 3);
+        assert!(store.store(&[]).is_err());
+    }
+
+    #[test]
+    fn test_csv_store_read_back() {
+        let dir = TempDir::new("store").unwrap();
+        let mut store = CsvStore::open(dir.path().join("submissions.csv"), ';').unwrap();
+
+        let mut sub = graded(10);
+        sub.data.insert(String::from("id"), String::from("s1"));
+        sub.data.insert(String::from("name"), String::from("Smith; John"));
+        sub.passed = vec![String::from("Compiles (+5)"), String::from("Runs (+5)")];
+        sub.late = true;
+        sub.received_at = Some(Local::now());
+        sub.flag("Something odd");
+        store.store(&[sub.clone()]).unwrap();
+        let mut team = graded(20);
+        team.set_team(Some("Team A"), vec!["s2", "s3"]);
+        store.store(&[team]).unwrap();
+
+        let subs = store.submissions().unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].id, "row-1");
+        assert_eq!(subs[0].time.timestamp(), sub.time.timestamp());
+        assert_eq!(subs[0].received_at.unwrap().timestamp(), sub.received_at.unwrap().timestamp());
+        assert_eq!(subs[0].flags, sub.flags);
+        assert!(subs[0].late);
+        assert_eq!(subs[0].grade, 10);
+        assert_eq!(subs[0].passed, sub.passed);
+        assert!(subs[0].failed.is_empty());
+        assert_eq!(subs[0].data, sub.data);
+        assert_eq!(subs[1].id, "row-2");
+        assert_eq!(subs[1].team.as_deref(), Some("Team A"));
+        assert_eq!(subs[1].received_at, None);
+        assert!(subs[1].data.is_empty());
+    }
+
+    #[test]
+    fn test_csv_store_skips_bad_rows() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.csv");
+        let mut store = CsvStore::open(&path, ',').unwrap();
+        store.store(&[graded(10)]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "yesterday,false,ten,,").unwrap();
+
+        let subs = store.submissions().unwrap();
+        assert_eq!(subs.len(), 1);
+        assert_eq!(subs[0].grade, 10);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut a = graded(10);
+        a.data.insert(String::from("id"), String::from("s1"));
+        let mut b = graded(20);
+        b.data.insert(String::from("name"), String::from("Bo, Jr."));
+
+        let text = to_csv(&[a, b], ',').unwrap();
+        let records = csv::read_str(&text, ',').unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("id"), Some("s1"));
+        assert_eq!(records[0].get("name"), Some(""));
+        assert_eq!(records[1].get("name"), Some("Bo, Jr."));
+        assert_eq!(records[1].parse::<isize>("grade").unwrap(), 20);
+    }
+
+    #[test]
+    fn test_csv_schema() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.csv");
+        let mut store = CsvStore::open(&path, ',').unwrap();
+
+        let mut a = graded(10);
+        a.data.insert(String::from("id"), String::from("s1"));
+        store.store(&[a]).unwrap();
+
+        // A new key adds a column, and the first row gets an empty cell
+        let mut b = graded(20);
+        b.data.insert(String::from("id"), String::from("s2"));
+        b.data.insert(String::from("name"), String::from("Bo"));
+        store.store(&[b]).unwrap();
+
+        // A missing key is an empty cell, and a fingerprint adds its columns
+        let mut c = graded(30);
+        c.data.insert(String::from("name"), String::from("Cy"));
+        c.set_fingerprint("secret");
+        store.store(&[c]).unwrap();
+
+        let records = csv::read_file(&path, ',').unwrap();
+        assert_eq!(records.len(), 3);
+        for r in &records {
+            assert_eq!(r.values.len(), r.header.len());
+        }
+        assert_eq!(records[0].header, store.columns());
+        assert_eq!(records[0].get("id"), Some("s1"));
+        assert_eq!(records[0].get("name"), Some(""));
+        assert_eq!(records[0].get("platform"), Some(""));
+        assert_eq!(records[1].get("name"), Some("Bo"));
+        assert_eq!(records[2].get("id"), Some(""));
+        assert_eq!(records[2].get("name"), Some("Cy"));
+        assert!(!records[2].get("platform").unwrap().is_empty());
+        assert_eq!(records[2].parse::<isize>("grade").unwrap(), 30);
+
+        // Reopening keeps the schema
+        let mut store = CsvStore::open(&path, ',').unwrap();
+        assert_eq!(records[0].header, store.columns());
+        store.store(&[graded(40)]).unwrap();
+        let records = csv::read_file(&path, ',').unwrap();
+        assert_eq!(records[3].parse::<isize>("grade").unwrap(), 40);
+        assert_eq!(records[3].values.len(), records[3].header.len());
+    }
+
+    #[test]
+    fn test_receipts_survive_new_columns() {
+        let dir = TempDir::new("store").unwrap();
+        let mut store = CsvStore::open(dir.path().join("submissions.csv"), ',').unwrap();
+
+        let mut first = graded(10);
+        first.data.insert(String::from("id"), String::from("s1"));
+        let receipt = Receipt::new(&first, &store.store(&[first.clone()]).unwrap());
+        assert!(store.verify(&receipt).unwrap());
+
+        // Adding a column rewrites the first row with another cell
+        let mut second = graded(20);
+        second.data.insert(String::from("name"), String::from("Bo"));
+        let columns = store.columns().len();
+        let team = vec![second.clone(), second.clone()];
+        let team_receipt = Receipt::new(&second, &store.store(&team).unwrap());
+        assert!(store.columns().len() > columns);
+
+        assert!(store.verify(&receipt).unwrap());
+        assert!(store.verify(&team_receipt).unwrap());
+        assert!(!store.verify(&Receipt::new(&first, "some other record")).unwrap());
+    }
+
+    #[test]
+    fn test_named_fields() {
+        let mut sub = graded(10);
+        sub.data.insert(String::from("grade"), String::from("A"));
+        let fields = named_fields(&sub).unwrap();
+
+        assert!(fields.contains(&(String::from("grade"), String::from("10"))));
+        assert!(fields.contains(&(String::from("grade_2"), String::from("A"))));
+
+        // No unnamed column for empty data
+        assert!(named_fields(&graded(10)).unwrap().iter().all(|(n, _)| !n.is_empty()));
+
+        // Criteria aren't mistaken for data with the same name
+        let mut sub = graded(10);
+        sub.data.insert(String::from("compiles"), String::from("yes"));
+        sub.results.push(CriterionResult {
+            func: String::from("compiles"),
+            name: String::from("Compiles"),
+            worth: 10,
+            earned: 10,
+            status: ResultStatus::Passed,
+            message: String::new(),
+            duration: std::time::Duration::from_millis(1),
+            hidden: false,
+            children: Vec::new(),
+        });
+        let fields = named_fields(&sub).unwrap();
+        assert!(fields.contains(&(String::from("criterion:compiles"), String::from("10"))));
+        assert!(fields.contains(&(String::from("compiles"), String::from("yes"))));
+    }
+
+    #[test]
+    fn test_csv_store_delimiter() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.tsv");
+        let mut store = CsvStore::open(&path, '\t').unwrap();
+
+        let mut sub = graded(10);
+        sub.data.insert(String::from("name"), String::from("Smith, John"));
+        store.store(&[sub.clone()]).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\tSmith, John"));
+        let records = crate::dropbox::csv::read_str(&contents, '\t').unwrap();
+        assert_eq!(records[0].get("name"), Some("Smith, John"));
+        assert_eq!(records[0].parse::<isize>("grade").unwrap(), 10);
+    }
+
+    #[test]
+    fn test_json_lines_store() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.jsonl");
+        let mut store = StoreKind::JsonLines.open(&path).unwrap();
+        assert!(store.submissions().unwrap().is_empty());
+
+        let mut sub = graded(10);
+        sub.data.insert(String::from("name"), String::from("Smith, John"));
+        sub.flag("Something odd");
+        store.store(&[sub.clone()]).unwrap();
+        store.store(&[graded(20)]).unwrap();
+
+        let subs = store.submissions().unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0], sub);
+        assert_eq!(subs[1].grade, 20);
+
+        // Still there when it's opened again
+        let reopened = JsonLinesStore::open(&path).unwrap();
+        assert_eq!(reopened.submissions().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.path().join("submissions.sqlite");
+        let mut store = StoreKind::Sqlite.open(&path).unwrap();
+
+        let sub = graded(10);
+        store.store(&[sub.clone(), graded(20)]).unwrap();
+        assert_eq!(store.submissions().unwrap()[0], sub);
+
+        let reopened = SqliteStore::open(&path).unwrap();
+        assert_eq!(reopened.submissions().unwrap().len(), 2);
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn test_sqlite_needs_feature() {
+        let dir = TempDir::new("store").unwrap();
+        assert!(StoreKind::Sqlite.open(dir.path().join("submissions.sqlite")).is_err());
+    }
+}
@@ -2,18 +2,30 @@
 
 // std uses
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
 
 // external uses
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
-use reqwest::blocking::Response;
+use rand::Rng;
+use anyhow::{bail, Context};
 
 // internal uses
 use crate::dropbox::results_file::AsCsv;
-use crate::rubric::Rubric;
+use crate::rubric::{Rubric, LatePenalty, LatePolicy};
 use crate::helpers::web;
-use crate::dropbox::fingerprint::Fingerprint;
-use crate::TIMESTAMP_FORMAT;
+use crate::dropbox::fingerprint::{Fingerprint, FingerprintOptions};
+use crate::dropbox::criterion_result::CriterionResult;
+use crate::dropbox::outbox::Outbox;
+use crate::dropbox::receipt::Receipt;
+use crate::dropbox::history::History;
+use crate::dropbox::signature;
+use crate::dropbox::artifact::{self, Artifact, ArtifactLimits};
+use crate::dropbox::encryption;
+use crate::dropbox::csv;
+use crate::dropbox::assignment;
+use crate::{Result, TIMESTAMP_FORMAT};
 
 /// A type alias to `HashMap<String, String>`
 ///
@@ -22,6 +34,12 @@ use crate::TIMESTAMP_FORMAT;
 pub type TestData = HashMap<String, String>;
 
 
+/// Keys in a submission's data that say who the student is. A team member's
+/// row doesn't get these from whoever submitted for the team.
+pub const IDENTIFYING_KEYS: &[&str] = &[
+    "name", "first_name", "last_name", "email", "username", "login", "student_id",
+];
+
 // This is only a function so serde can use it
 // TODO: #34 Move this to dropbox::mod
 fn default_timestamp_format() -> String {
@@ -29,14 +47,33 @@ fn default_timestamp_format() -> String {
 }
 
 
+// A random id, as 32 hex characters
+pub(crate) fn random_id() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// A random id for a new submission. serde uses this for submissions
+// sent before ids existed.
+fn new_submission_id() -> String {
+    random_id()
+}
+
+
 /// A submission is a bundle of data that represents
 /// one student's submission. They will do some sort of work
 /// for a lab, then run a rust script that builds some criteria,
 /// runs those criteria with some data from the student, and submits
 /// a Submission to a central webserver where the instructor can
 /// collect the graded submissions.
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Submission {
+    /// A random id, unique to this submission.
+    ///
+    /// The dropbox uses this to ignore a submission it has already
+    /// recorded, so it's safe to send a submission more than once.
+    #[serde(default = "new_submission_id")]
+    pub id: String,
     /// A local timestamp when the submission was created
     pub time: DateTime<Local>,
     /// Numerical grade for the submission.
@@ -51,11 +88,63 @@ pub struct Submission {
     pub passed: Vec<String>,
     /// The citeria (name) that this submission failed
     pub failed: Vec<String>,
+    /// When the dropbox received the submission, by the dropbox's clock.
+    /// This is set by the dropbox, not the grader.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<DateTime<Local>>,
+    /// Anything suspicious the dropbox noticed about the submission
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    /// The structured result of every criterion this submission
+    /// was graded against, in the order they were run.
+    #[serde(default)]
+    pub results: Vec<CriterionResult>,
+    /// The late penalties taken off the grade. These are also in `failed`,
+    /// but the dropbox needs them here to take them back and apply its own.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub late_penalties: Vec<LatePenalty>,
+    /// Files attached for review.
+    /// See [`attach_file`](crate::dropbox::submission::Submission::attach_file).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<Artifact>,
+    /// The team's name, if this is a team submission with a name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    /// The ids of everyone on the team, if this is a team submission.
+    /// See [`set_team`](crate::dropbox::submission::Submission::set_team).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// Data about each member, like their name or email, by their id.
+    /// See [`set_member_data`](crate::dropbox::submission::Submission::set_member_data).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub member_data: HashMap<String, TestData>,
+    /// The assignment this submission is for. Grading against a rubric
+    /// sets this to the rubric's name, unless it's already set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignment: Option<String>,
+    /// The version of the rubric it was graded against, if the rubric has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rubric_version: Option<String>,
+    /// How much can be attached. Only the grader uses this.
+    #[serde(skip)]
+    artifact_limits: ArtifactLimits,
+    /// The dropbox's public key, if the submission should be encrypted.
+    /// This is kept when the submission is saved to the outbox, but never sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
+    /// The token the dropbox needs to accept the submission. Like the
+    /// public key, it's kept in the outbox but sent as a header, not in the JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_token: Option<String>,
     /// How to format the timestamp.
     /// This uses TIMESTAMP_FORMAT from the crate root.
     #[serde(default = "default_timestamp_format")]
     timestamp_format: String,
-    fingerprint: Option<Fingerprint>
+    fingerprint: Option<Fingerprint>,
+    /// HMAC signature of the submission, made with the fingerprint's secret.
+    /// See [`sign`](crate::dropbox::submission::Submission::sign).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>
 }
 
 impl Submission {
@@ -74,14 +163,29 @@ impl Submission {
     /// ```
     pub fn new() -> Submission {
         Submission {
+            id: new_submission_id(),
             time: Local::now(),
             grade: 0,
             data: TestData::new(),
             passed: Vec::new(),
             failed: Vec::new(),
+            results: Vec::new(),
+            late_penalties: Vec::new(),
+            artifacts: Vec::new(),
+            team: None,
+            members: Vec::new(),
+            member_data: HashMap::new(),
+            assignment: None,
+            rubric_version: None,
+            artifact_limits: ArtifactLimits::default(),
+            public_key: None,
+            api_token: None,
+            received_at: None,
+            flags: Vec::new(),
             timestamp_format: default_timestamp_format(),
             late: false,
-            fingerprint: None
+            fingerprint: None,
+            signature: None
         }
     }
 
@@ -132,11 +236,93 @@ impl Submission {
         sub
     }
 
+    /// Makes this a team submission, credited to every member.
+    ///
+    /// The dropbox records one row per member, and won't let a student
+    /// be on two different teams. Blank and repeated ids are ignored.
+    ///
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.set_team(Some("Team Rocket"), vec!["s1", "s2", "s2", ""]);
+    /// assert_eq!(sub.members, vec!["s1", "s2"]);
+    /// ```
+    pub fn set_team<S: AsRef<str>>(&mut self, name: Option<&str>, members: Vec<S>) {
+        self.team = name.map(String::from);
+        self.members.clear();
+        for m in members {
+            let m = m.as_ref().trim();
+            if !m.is_empty() && !self.members.iter().any(|existing| existing == m) {
+                self.members.push(String::from(m));
+            }
+        }
+    }
+
+    /// What identifies the team: its name, or its members if it doesn't have one.
+    /// `None` if this isn't a team submission.
+    pub fn team_key(&self) -> Option<String> {
+        if self.members.is_empty() {
+            return None;
+        }
+        Some(self.team.clone().unwrap_or_else(|| {
+            let mut members = self.members.clone();
+            members.sort();
+            members.join(";")
+        }))
+    }
+
+    /// Adds data about a team member, like their name or email. Their row
+    /// in the dropbox gets it instead of the submitter's.
+    ///
+    /// ```rust
+    /// # use rubric::{data, Submission};
+    /// let mut sub = Submission::from_data(data! { "id" => "s1", "name" => "Jo" });
+    /// sub.set_team(None, vec!["s1", "s2"]);
+    /// sub.set_member_data("s2", data! { "name" => "Al" });
+    ///
+    /// let rows = sub.per_member("id");
+    /// assert_eq!(rows[1].data["name"], "Al");
+    /// ```
+    pub fn set_member_data(&mut self, member: &str, data: TestData) {
+        self.member_data.insert(String::from(member), data);
+    }
+
+    /// One submission per team member, with `identity_key` in the data set
+    /// to the member's id. A submission without members is returned as is.
+    ///
+    /// Only the submitter's row keeps the [identifying keys](IDENTIFYING_KEYS) from
+    /// the data. Everyone else's come from their [member data](crate::dropbox::submission::Submission::set_member_data),
+    /// so one student's name or email never ends up on another's row.
+    ///
+    /// The dropbox uses this to record a row for each member.
+    pub fn per_member(&self, identity_key: &str) -> Vec<Submission> {
+        if self.members.is_empty() {
+            return vec![self.clone()];
+        }
+        let submitter = self.data.get(identity_key);
+        self.members.iter()
+            .map(|member| {
+                let mut sub = self.clone();
+                if submitter != Some(member) {
+                    for key in IDENTIFYING_KEYS {
+                        sub.data.remove(*key);
+                    }
+                }
+                if let Some(data) = self.member_data.get(member) {
+                    sub.data.extend(data.clone());
+                }
+                sub.data.insert(String::from(identity_key), member.clone());
+                sub
+            })
+            .collect()
+    }
+
 
     /// Creates a fingerprint based on the provided secret key.
     ///
     /// The fingerprint will contain the secret key and some automatically
-    /// collected platform information.
+    /// collected platform information. The secret key is only used to sign
+    /// the submission, it isn't sent to the dropbox.
     ///
     /// ```no_compile
     /// let mut sub = Submission::new();
@@ -146,15 +332,196 @@ impl Submission {
         self.fingerprint = Some(Fingerprint::from_secret(secret));
     }
 
+    /// Like [`set_fingerprint`](crate::dropbox::submission::Submission::set_fingerprint),
+    /// but you choose which machine information is collected.
+    ///
+    /// ```no_compile
+    /// let options = FingerprintOptions::default().salt("cs101-fall");
+    /// sub.set_fingerprint_with("My secret key", &options);
+    /// ```
+    pub fn set_fingerprint_with(&mut self, secret: &str, options: &FingerprintOptions) {
+        self.fingerprint = Some(Fingerprint::with_options(secret, options));
+    }
+
     /// Returns the submissions fingerprint. It may not be set.
     pub fn fingerprint(&self) -> &Option<Fingerprint> {
         &self.fingerprint
     }
 
+    /// Signs the submission with the fingerprint's secret.
+    ///
+    /// This does nothing if there isn't a fingerprint. [`submit`](crate::dropbox::submission::Submission::submit)
+    /// signs the submission for you, so you only need this if you're sending it some other way.
+    /// Any change to the submission after signing will invalidate the signature.
+    ///
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.set_fingerprint("secret key");
+    /// sub.sign();
+    /// assert!(sub.verify_signature("secret key"));
+    /// assert!(!sub.verify_signature("wrong key"));
+    /// ```
+    pub fn sign(&mut self) {
+        let secret = match &self.fingerprint {
+            Some(fp) => fp.secret.clone(),
+            None => return,
+        };
+        let payload = signature::canonical_payload(&self.as_json_value());
+        self.signature = Some(signature::sign(&secret, &payload));
+    }
+
+    /// Returns true if the submission was signed with the given secret
+    pub fn verify_signature(&self, secret: &str) -> bool {
+        signature::verify_value(secret, &self.as_json_value())
+    }
+
+    // The submission as JSON, which is what actually gets signed and sent.
+    // Artifact paths are local to the student's machine, so they're left out,
+    // and the dropbox doesn't need its own public key. The API token goes in
+    // a header. The history keeps this too, so neither is left lying around.
+    pub(crate) fn as_json_value(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("Submission is always valid JSON");
+        if let Some(obj) = value.as_object_mut() {
+            obj.remove("public_key");
+            obj.remove("api_token");
+        }
+        if let Some(artifacts) = value.get_mut("artifacts").and_then(|a| a.as_array_mut()) {
+            for a in artifacts {
+                if let Some(obj) = a.as_object_mut() {
+                    obj.remove("path");
+                }
+            }
+        }
+        value
+    }
+
+    /// Attaches a file to the submission, to be sent to the dropbox for review.
+    ///
+    /// This returns an error if the file can't be read, if it's larger than the
+    /// limit for a single file, or if it would put the submission over its total limit.
+    /// The limits are 1 MiB per file and 10 MiB total, unless you change them with
+    /// [`set_artifact_limits`](crate::dropbox::submission::Submission::set_artifact_limits).
+    ///
+    /// The file is read again when the submission is sent, so don't delete it before then.
+    ///
+    /// ```no_run
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.attach_file("Dockerfile").unwrap();
+    /// ```
+    pub fn attach_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&Artifact> {
+        let new = Artifact::from_path(path, self.artifact_limits.max_file_size)?;
+
+        if self.artifacts.iter().any(|a| a.name == new.name) {
+            bail!("An artifact named {} is already attached", new.name);
+        }
+        let total: u64 = self.artifacts.iter().map(|a| a.size).sum::<u64>() + new.size;
+        if total > self.artifact_limits.max_total_size {
+            bail!(
+                "Couldn't attach {}, the submission's artifacts would be {} bytes and the limit is {}",
+                new.name, total, self.artifact_limits.max_total_size
+            );
+        }
+
+        self.artifacts.push(new);
+        Ok(self.artifacts.last().unwrap())
+    }
+
+    /// Attaches every file matching a glob pattern, like `logs/*.txt`.
+    /// Directories are skipped.
+    ///
+    /// Returns how many files were attached. Stops at the first file that
+    /// can't be attached, see [`attach_file`](crate::dropbox::submission::Submission::attach_file).
+    pub fn attach_glob(&mut self, pattern: &str) -> Result<usize> {
+        let paths = glob::glob(pattern).context(format!("Invalid pattern {}", pattern))?;
+        let mut attached = 0;
+        for path in paths {
+            let path = path?;
+            if path.is_file() {
+                self.attach_file(&path)?;
+                attached += 1;
+            }
+        }
+        Ok(attached)
+    }
+
+    /// Sets the largest file that can be attached, and the most that
+    /// can be attached in total, in bytes.
+    pub fn set_artifact_limits(&mut self, max_file_size: u64, max_total_size: u64) {
+        self.artifact_limits = ArtifactLimits { max_file_size, max_total_size };
+    }
+
+    /// Encrypts the submission with the dropbox's public key when it's sent.
+    /// See [`encryption`](crate::dropbox::encryption).
+    ///
+    /// The key is PEM encoded. You'll probably want to embed it in the grader.
+    /// This returns an error if it isn't a valid RSA public key.
+    ///
+    /// ```no_compile
+    /// sub.set_public_key(include_str!("dropbox_public.pem"))?;
+    /// ```
+    pub fn set_public_key(&mut self, public_key: &str) -> Result<()> {
+        encryption::check_public_key(public_key)?;
+        self.public_key = Some(String::from(public_key));
+        Ok(())
+    }
+
+    /// Sends this token to the dropbox with the submission, so it's accepted
+    /// by a dropbox that needs one. See [`auth`](crate::dropbox::auth).
+    ///
+    /// Like the public key, you'll probably want to embed it in the grader.
+    ///
+    /// ```no_compile
+    /// sub.set_api_token(env!("DROPBOX_TOKEN"));
+    /// ```
+    pub fn set_api_token(&mut self, token: &str) {
+        self.api_token = Some(String::from(token));
+    }
+
+    // Encrypts something to send, if there's a public key.
+    // The key was checked when it was set, so this won't fail.
+    fn seal(&self, contents: &[u8]) -> Option<encryption::EncryptedPayload> {
+        self.public_key.as_ref().map(|key| {
+            encryption::encrypt(key, contents).expect("Couldn't encrypt with a valid public key")
+        })
+    }
+
+    // Posts the submission. It's sent as JSON, or as a multipart
+    // form if there are artifacts. If there's a public key, the
+    // submission and each artifact are encrypted.
+    pub(crate) fn post(&self, url: &str) -> std::result::Result<reqwest::blocking::Response, reqwest::Error> {
+        let mut body = self.as_json_value();
+        if let Some(sealed) = self.seal(body.to_string().as_bytes()) {
+            body = sealed.into_value();
+        }
+
+        if self.artifacts.is_empty() {
+            return web::post_json_with_token(url, body, self.api_token.as_deref());
+        }
+
+        // If a file can't be read anymore it's left out, and the dropbox will refuse the submission
+        let files = self.artifacts.iter()
+            .filter_map(|a| match a.read() {
+                Ok(contents) => Some((a.name.clone(), contents)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            })
+            .map(|(name, contents)| match self.seal(&contents) {
+                Some(sealed) => (name, serde_json::to_vec(&sealed).expect("Payload is always valid JSON")),
+                None => (name, contents),
+            })
+            .collect();
+        web::post_multipart_with_token(url, artifact::SUBMISSION_FIELD, body, artifact::ARTIFACT_FIELD, files, self.api_token.as_deref())
+    }
+
     /// Adds to the grade, with a message why
     fn addition(&mut self, to_add: isize, message: &str) {
         self.grade += to_add;
-        self.passed.push(format!("{} (+{})", message, to_add));
+        // `+` formats the sign either way, so negative worth doesn't print as "+-5"
+        self.passed.push(format!("{} ({:+})", message, to_add));
     }
 
     /// Subtracts from the grade, with a message why
@@ -163,64 +530,184 @@ impl Submission {
         self.failed.push(format!("{} (-{})", message, to_penalize));
     }
 
+    /// Adds a flag, something suspicious about the submission
+    pub fn flag(&mut self, reason: &str) {
+        self.flags.push(String::from(reason));
+    }
+
+    /// Sets the assignment this submission is for. This is what the dropbox
+    /// checks against its allowed assignments, unless it's sent to the
+    /// assignment's route with [`submit_to`](crate::dropbox::submission::Submission::submit_to).
+    pub fn set_assignment(&mut self, name: &str) {
+        self.assignment = Some(String::from(name));
+    }
+
     /// Tests a submission against a list of criterion
     pub fn grade_against(&mut self, rubric: &mut Rubric) {
-        // Penalties
-        if rubric.past_final_deadline() {
+        if self.assignment.is_none() {
+            self.assignment = Some(rubric.name.clone());
+        }
+        if rubric.version.is_some() {
+            self.rubric_version = rubric.version.clone();
+        }
+        let policy = rubric.late_policy();
+        let now = Local::now();
+
+        if policy.past_final_deadline_at(now) {
             eprintln!("Final deadline ({}) has passed.", rubric.final_deadline.unwrap());
             eprintln!("Your instructor has chosen to not allow late submission");
             eprintln!("This submission will be recorded, but with a grade of 0");
-            self.penalty(self.grade, "Past final deadline");
-            return;
+        } else if policy.late_at(now) && !rubric.allow_late {
+            eprintln!("Deadline ({}) has passed.", rubric.deadline.unwrap());
+            eprintln!("Your instructor has chosen to not allow late submission");
+            eprintln!("This submission will be recorded, but with a grade of 0");
         }
 
-        if rubric.past_due() {
-            // Submission is late, mark it as such
-            self.late = true;
-
-            // And subtract the late penalty
-            self.penalty(rubric.late_penalty, "Late submission");
-            // Related, subtract the late penalty per day
-            // This returns the amount of whole days since the deadline + 1.
-            // One second after the deadline counts as 1 day,
-            // exactly 24 hours + 1 second after the deadline is 2 days.
-            let how_late = rubric.deadline
-                .unwrap()
-                .signed_duration_since(Local::now())
-                .num_days()
-                .abs() + 1;
-            let daily_penalty = rubric.daily_penalty * how_late as isize;
-            self.penalty(daily_penalty, &format!("{} days late", how_late));
-
-            // If they disallow late submission
-            if !rubric.allow_late {
-                // Inform the student and return early without grading
-                eprintln!("Deadline ({}) has passed.", rubric.deadline.unwrap());
-                eprintln!("Your instructor has chosen to not allow late submission");
-                eprintln!("This submission will be recorded, but with a grade of 0");
-                // Penalize 100% of the points and return
-                self.penalty(self.grade, "Past deadline");
-                return;
-            }
+        // Additions
+        for crit in &mut rubric.sorted().into_iter() {
+            let start = Instant::now();
+            let status = crit.test_with_data(&self.data);
+            self.results.push(CriterionResult::from_criterion(crit, start.elapsed()));
 
+            if crit.deduction {
+                // Deductions only matter if they're triggered
+                if status {
+                    self.penalty(crit.worth.abs(), &crit.name);
+                }
+                continue;
+            }
 
+            if status {
+                // Criteria with children may earn less than their worth
+                self.addition(crit.earned(), &crit.name);
+            } else {
+                // Failing a criteria just means +0 points, unless
+                // a deduction inside it was triggered
+                self.penalty(-crit.earned(), &crit.name);
+            }
         }
 
-        // Additions
-        for crit in &mut rubric.sorted().into_iter() {
-            if crit.test_with_data(&self.data) {
-                self.addition(crit.worth, &crit.name);
-            } else {
-                // Failing a criteria just means +0 points
-                self.penalty(0, &crit.name);
+        // Penalties. It's still graded when it's too late, so the dropbox
+        // has a grade to give if its clock says it wasn't.
+        self.apply_penalties(&policy, now);
+    }
+
+    /// Works out lateness and penalties again, as if the submission was made at `time`.
+    ///
+    /// The dropbox uses this with its own clock, so changing the clock on the student's
+    /// machine doesn't get around the deadline. The [`late_penalties`](crate::dropbox::submission::Submission::late_penalties)
+    /// the grader applied are given back before the policy's are taken off.
+    pub fn apply_late_policy(&mut self, policy: &LatePolicy, time: DateTime<Local>) {
+        // Give back the penalties worked out from the student's clock
+        for penalty in std::mem::take(&mut self.late_penalties) {
+            self.grade += penalty.amount;
+            if let Some(i) = self.failed.iter().position(|f| *f == penalty.message()) {
+                self.failed.remove(i);
             }
         }
+        if !self.results.is_empty() {
+            self.grade = self.results.iter().map(|r| r.earned).sum();
+        }
+        self.apply_penalties(policy, time);
+    }
+
+    // Decides if it's late at this time, and takes off the penalties
+    fn apply_penalties(&mut self, policy: &LatePolicy, time: DateTime<Local>) {
+        self.late = policy.late_at(time);
+        for penalty in policy.penalties_at(time, self.grade) {
+            self.grade -= penalty.amount;
+            self.failed.push(penalty.message());
+            self.late_penalties.push(penalty);
+        }
+    }
+
+    /// How far the submission's time is from when the dropbox received it.
+    /// `None` if it hasn't been received.
+    pub fn clock_skew(&self) -> Option<chrono::Duration> {
+        self.received_at.map(|received| received.signed_duration_since(self.time))
+    }
+
+    /// Returns the result for the criterion with the given func, if
+    /// this submission was graded against it. Children's results are found too.
+    pub fn result(&self, func: &str) -> Option<&CriterionResult> {
+        self.results.iter().find_map(|r| r.find(func))
     }
 
     /// Posts the submission to the URL in JSON format. Meant to be sent
-    /// to a dropbox. Really just calls [`helpers::web::post_json`](rubric::helpers::web::post_json).
-    pub fn submit(&self, url: &str) -> Result<Response, reqwest::Error> {
-        web::post_json(url, self)
+    /// to a dropbox. Uses [`helpers::web::post_json`](rubric::helpers::web::post_json),
+    /// or [`helpers::web::post_multipart`](rubric::helpers::web::post_multipart) if files are attached.
+    ///
+    /// Returns the [`Receipt`](crate::dropbox::receipt::Receipt) the dropbox sends back,
+    /// which is also saved locally.
+    ///
+    /// If the dropbox can't be reached, or has an error, the submission is saved
+    /// to the [`Outbox`](crate::dropbox::outbox::Outbox) and retried the next time
+    /// a submission is sent. Anything already in the outbox is sent first.
+    ///
+    /// ```no_run
+    /// # use rubric::Submission;
+    /// let sub = Submission::new();
+    /// match sub.submit("http://localhost:8080/submit") {
+    ///     Ok(receipt) => receipt.print(),
+    ///     Err(e) => println!("Couldn't submit: {}", e),
+    /// }
+    /// ```
+    ///
+    /// If the submission has a fingerprint, it's signed before it's sent.
+    /// It's added to the local [`History`](crate::dropbox::history::History)
+    /// before it's sent, whether or not the dropbox takes it.
+    pub fn submit(&self, url: &str) -> Result<Receipt> {
+        self.save_history();
+        let resp = Outbox::default().send(url, &self.signed())?;
+        Self::keep_receipt(resp)
+    }
+
+    /// Posts the submission without saving it to the outbox if it fails.
+    pub fn submit_once(&self, url: &str) -> Result<Receipt> {
+        self.save_history();
+        let resp = self.signed().post(url)?;
+        Self::keep_receipt(resp)
+    }
+
+    /// Submits to one assignment on a dropbox that serves several.
+    /// The assignment is added to the URL, and set on the submission.
+    /// Otherwise this is just like [`submit`](crate::dropbox::submission::Submission::submit).
+    ///
+    /// ```no_run
+    /// # use rubric::Submission;
+    /// let sub = Submission::new();
+    /// // Sent to http://localhost:8080/submit/lab1
+    /// let receipt = sub.submit_to("http://localhost:8080/submit", "lab1");
+    /// ```
+    pub fn submit_to(&self, url: &str, assignment: &str) -> Result<Receipt> {
+        let url = assignment::submit_url(url, assignment)?;
+        let mut sub = self.clone();
+        sub.set_assignment(assignment);
+        sub.submit(&url)
+    }
+
+    // Adds the submission to the local history. Not being able to
+    // shouldn't stop it from being sent.
+    fn save_history(&self) {
+        if let Err(e) = History::default().append(self) {
+            eprintln!("Couldn't save submission history: {}", e);
+        }
+    }
+
+    // A signed copy of the submission, ready to send
+    fn signed(&self) -> Submission {
+        let mut sub = self.clone();
+        sub.sign();
+        sub
+    }
+
+    // Reads the receipt from the dropbox and saves it
+    fn keep_receipt(resp: reqwest::blocking::Response) -> Result<Receipt> {
+        let receipt = Receipt::from_response(resp)?;
+        if let Err(e) = receipt.save() {
+            eprintln!("Couldn't save receipt: {}", e);
+        }
+        Ok(receipt)
     }
 
     /// Overrides the default timestamp format.
@@ -240,7 +727,7 @@ impl AsCsv for TestData {
     fn as_csv(&self) -> String {
         let mut v: Vec<_> = self.into_iter().collect();
         v.sort_by(|x,y| x.0.cmp(&y.0));
-        v.iter().map(|v| v.1.replace(",", ";") ).collect::<Vec<_>>().join(",")
+        csv::write_row(&v.iter().map(|v| v.1).collect::<Vec<_>>(), ',')
     }
 
     /// Returns the filename that the [`ResultsFile`](crate::results_file::ResultsFile)
@@ -260,28 +747,75 @@ impl AsCsv for TestData {
         // return format!("{}", owned_keys.join(","));
         let mut v: Vec<_> = self.into_iter().collect();
         v.sort_by(|x,y| x.0.cmp(&y.0));
-        v.iter().map(|v| v.0.to_owned() ).collect::<Vec<_>>().join(",")
+        csv::write_row(&v.iter().map(|v| v.0).collect::<Vec<_>>(), ',')
+    }
+}
+
+impl AsCsv for Vec<CriterionResult> {
+    /// Returns the points earned by each criterion, in the order they were graded
+    fn as_csv(&self) -> String {
+        self.iter().map(|r| r.earned.to_string()).collect::<Vec<_>>().join(",")
+    }
+
+    /// This probably shouldn't get used for results, as they will be written as part
+    /// of a submission, not on their own.
+    fn filename(&self) -> String {
+        String::from("criterion_results.csv")
+    }
+
+    /// One column per criterion, named by its func
+    fn header(&self) -> String {
+        csv::write_row(&self.iter().map(|r| &r.func).collect::<Vec<_>>(), ',')
     }
 }
 
 impl AsCsv for Submission {
     /// Returns the submission's values in csv format. The `TestData` atttached will be
     /// sorted alphabetically by key.
+    ///
+    /// If the submission has been graded, there is a column with the points
+    /// earned for each criterion before the `TestData`.
+    ///
+    /// If the dropbox has received it, there are `received` and `flags` columns after the time.
+    ///
+    /// Team submissions have a `team` column with the team's name (or members) near the end.
+    /// If files are attached, the last column lists where they are, separated by `;`.
     fn as_csv(&self) -> String {
-        let mut csv = format!(
-            "{},{},{},{},{},{}",
-            self.time.format(&self.timestamp_format),
-            self.late,
-            self.grade,
-            self.passed.join(";"),
-            self.failed.join(";"),
-            self.data.as_csv()
-        );
+        let mut fields = vec![self.time.format(&self.timestamp_format).to_string()];
+
+        if let Some(received) = self.received_at {
+            fields.push(received.format(&self.timestamp_format).to_string());
+            fields.push(self.flags.join(";"));
+        }
+
+        fields.push(self.late.to_string());
+        fields.push(self.grade.to_string());
+        fields.push(self.passed.join(";"));
+        fields.push(self.failed.join(";"));
+        let mut csv = csv::write_row(&fields, ',');
+
+        if !self.results.is_empty() {
+            csv = format!("{},{}", csv, self.results.as_csv());
+        }
+
+        csv = format!("{},{}", csv, self.data.as_csv());
 
         if let Some(fp) = &self.fingerprint {
             csv = format!("{},{}", csv, fp.as_csv());
         }
 
+        if let Some(team) = self.team_key() {
+            csv = format!("{},{}", csv, csv::escape_field(&team, ','));
+        }
+
+        if !self.artifacts.is_empty() {
+            let paths: Vec<String> = self.artifacts.iter()
+                .map(|a| a.path.as_ref().map_or(a.name.clone(), |p| p.display().to_string()))
+                .map(|p| p.replace(";", "_"))
+                .collect();
+            csv = format!("{},{}", csv, csv::escape_field(&paths.join(";"), ','));
+        }
+
         csv
     }
 
@@ -292,10 +826,25 @@ impl AsCsv for Submission {
 
     /// Returns a header of all the fields, matching the data in `as_csv`
     fn header(&self) -> String {
-        let mut header = format!("time,late,grade,passed,failed,{}", self.data.header());
+        // None of these need escaping
+        let mut header = String::from("time");
+        if self.received_at.is_some() {
+            header.push_str(",received,flags");
+        }
+        header.push_str(",late,grade,passed,failed");
+        if !self.results.is_empty() {
+            header = format!("{},{}", header, self.results.header());
+        }
+        header = format!("{},{}", header, self.data.header());
         if let Some(fp) = &self.fingerprint {
             header = format!("{},{}", header, fp.header());
         }
+        if self.team_key().is_some() {
+            header.push_str(",team");
+        }
+        if !self.artifacts.is_empty() {
+            header.push_str(",artifacts");
+        }
         header
     }
 }
@@ -304,12 +853,15 @@ impl AsCsv for Submission {
 mod tests {
     use super::*;
     use crate::{data, yaml, attach};
+    use crate::dropbox::ResultStatus;
 
 
     #[test]
     fn test_new_submission() {
         let sub = Submission::new();
         assert!(sub.data.len() == 0);
+        assert_eq!(sub.id.len(), 32);
+        assert_ne!(sub.id, Submission::new().id);
     }
 
     #[test]
@@ -368,6 +920,190 @@ mod tests {
 
         sub.grade_against(&mut rubric);
         assert_eq!(sub.grade, 50);
+        assert_eq!(sub.assignment.as_deref(), Some(rubric.name.as_str()));
+
+        // An assignment that's already set is kept
+        let mut sub = Submission::new();
+        sub.set_assignment("lab1");
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.assignment.as_deref(), Some("lab1"));
+        assert!(sub.rubric_version.is_none());
+
+        rubric.version = Some(String::from("2"));
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.rubric_version.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_structured_results() {
+        let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        let test = |_: &TestData| true;
+        attach! {
+            rubric,
+            "first_crit" => test
+        };
+
+        let mut sub = Submission::from_data(data! { "name" => "luke" });
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.results.len(), 2);
+        let first = sub.result("first_crit").unwrap();
+        assert_eq!(first.earned, 50);
+        assert_eq!(first.status, ResultStatus::Passed);
+        assert_eq!(first.message, "success");
+        assert_eq!(sub.result("second_crit").unwrap().status, ResultStatus::Failed);
+
+        // One column per criterion, between the legacy lists and the data
+        assert!(sub.header().contains("failed,first_crit,second_crit,name"));
+        assert!(sub.as_csv().contains(",50,0,luke"));
+
+        // Results survive the trip to the dropbox
+        let json = serde_json::to_string(&sub).unwrap();
+        let built: Submission = serde_json::from_str(&json).unwrap();
+        assert_eq!(built.results, sub.results);
+    }
+
+    #[test]
+    fn test_nested_results() {
+        let yaml = r#"
+            name: Nested
+            criteria:
+                Docker works:
+                    combine: sum
+                    criteria:
+                        Dockerfile builds:
+                            worth: 10
+                        Serves port 80:
+                            worth: 10
+        "#;
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        let test = |_: &TestData| true;
+        attach! {
+            rubric,
+            "dockerfile_builds" => test
+        };
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        // Only the parent is at the top, so the grade isn't counted twice
+        assert_eq!(sub.results.len(), 1);
+        assert_eq!(sub.grade, 10);
+        assert_eq!(sub.results[0].children.len(), 2);
+        assert_eq!(sub.result("dockerfile_builds").unwrap().status, ResultStatus::Passed);
+        assert_eq!(sub.result("serves_port_80").unwrap().status, ResultStatus::Failed);
+    }
+
+    fn late_rubric() -> Rubric {
+        let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        let test = |_: &TestData| true;
+        attach! {
+            rubric,
+            "first_crit" => test
+        };
+        rubric.late_penalty = 5;
+        rubric.daily_penalty = 2;
+        rubric
+    }
+
+    #[test]
+    fn test_apply_late_policy() {
+        // Graded on time by the student's clock
+        let mut rubric = late_rubric();
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 50);
+        assert!(!sub.late);
+
+        // But the dropbox received it a day and a bit after the deadline
+        let mut policy = rubric.late_policy();
+        let deadline = Local::now() - chrono::Duration::hours(25);
+        policy.deadline = Some(deadline);
+        sub.apply_late_policy(&policy, Local::now());
+        assert!(sub.late);
+        assert_eq!(sub.grade, 50 - 5 - 2 * 2);
+
+        // Applying it again replaces the penalties rather than stacking them
+        sub.apply_late_policy(&policy, Local::now());
+        assert_eq!(sub.grade, 41);
+        assert_eq!(sub.failed.iter().filter(|f| f.starts_with("Late submission")).count(), 1);
+
+        // On time by the dropbox's clock
+        sub.apply_late_policy(&policy, deadline - chrono::Duration::hours(1));
+        assert!(!sub.late);
+        assert_eq!(sub.grade, 50);
+
+        // Not allowed late at all
+        policy.allow_late = false;
+        sub.apply_late_policy(&policy, Local::now());
+        assert_eq!(sub.grade, 0);
+    }
+
+    #[test]
+    fn test_late_by_the_students_clock() {
+        // The student's clock says it's too late
+        let mut rubric = late_rubric();
+        let deadline = Local::now() - chrono::Duration::hours(1);
+        rubric.deadline = Some(deadline);
+        rubric.allow_late = false;
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert!(sub.late);
+        assert_eq!(sub.grade, 0);
+        assert_eq!(sub.late_penalties.len(), 3);
+
+        // The dropbox got it in time, so it gets the grade it earned
+        let mut sub: Submission = serde_json::from_str(&serde_json::to_string(&sub).unwrap()).unwrap();
+        sub.apply_late_policy(&rubric.late_policy(), deadline - chrono::Duration::minutes(5));
+        assert!(!sub.late);
+        assert_eq!(sub.grade, 50);
+        assert!(sub.late_penalties.is_empty());
+        assert!(!sub.failed.iter().any(|f| f.starts_with("Past deadline")));
+    }
+
+    #[test]
+    fn test_late_policy_keeps_the_grade() {
+        // Not graded against a rubric, so there aren't any results
+        let mut sub = Submission::new();
+        sub.grade = 12;
+        let policy = LatePolicy {
+            deadline: Some(Local::now() - chrono::Duration::hours(1)),
+            late_penalty: 5,
+            ..LatePolicy::default()
+        };
+        sub.apply_late_policy(&policy, Local::now() - chrono::Duration::hours(2));
+        assert_eq!(sub.grade, 12);
+        sub.apply_late_policy(&policy, Local::now());
+        assert_eq!(sub.grade, 7);
+        sub.apply_late_policy(&policy, Local::now());
+        assert_eq!(sub.grade, 7);
+    }
+
+    #[test]
+    fn test_criteria_named_like_penalties() {
+        let mut rubric = late_rubric();
+        rubric.deadline = Some(Local::now() - chrono::Duration::hours(1));
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        // A criterion that failed with a name like a penalty's
+        sub.failed.push(String::from("Late submission (-0)"));
+        sub.apply_late_policy(&LatePolicy::default(), Local::now());
+        assert_eq!(sub.grade, 50);
+        assert!(sub.failed.contains(&String::from("Late submission (-0)")));
+    }
+
+    #[test]
+    fn test_received_columns() {
+        let mut sub = Submission::new();
+        assert!(!sub.header().contains("received"));
+
+        sub.received_at = Some(sub.time + chrono::Duration::minutes(10));
+        sub.flag("Clock is off, by a lot");
+        assert!(sub.header().starts_with("time,received,flags,late"));
+        assert!(sub.as_csv().contains(r#""Clock is off, by a lot",false"#));
+        assert_eq!(sub.clock_skew(), Some(chrono::Duration::minutes(10)));
     }
 
     #[test]
@@ -387,12 +1123,34 @@ mod tests {
     }
 
     #[test]
-    fn test_as_csv_replaces_commas() {
+    fn test_as_csv_quotes_commas() {
         let sub = Submission::from_data(data! {
             "key" => "value with, comma"
         });
 
-        assert!(sub.as_csv().contains("value with; comma"));
+        assert!(sub.as_csv().contains(r#""value with, comma""#));
+    }
+
+    #[test]
+    fn test_as_csv_reads_back() {
+        let mut sub = Submission::from_data(data! {
+            "name" => r#"Jo "JJ" Smith, Jr."#,
+            "notes" => "line one\nline two"
+        });
+        sub.set_team(Some("Team, \"A\""), vec!["s1", "s2"]);
+
+        let text = format!("{}\n{}", sub.header(), sub.as_csv());
+        let records = csv::read_str(&text, ',').unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get("name"), Some(r#"Jo "JJ" Smith, Jr."#));
+        assert_eq!(records[0].get("notes"), Some("line one\nline two"));
+        assert_eq!(records[0].get("team"), Some("Team, \"A\""));
+        assert_eq!(records[0].values.len(), records[0].header.len());
+
+        // And with another delimiter
+        let text = format!("{}\n{}", sub.header_with(';'), sub.as_csv_with(';'));
+        let records = csv::read_str(&text, ';').unwrap();
+        assert_eq!(records[0].get("name"), Some(r#"Jo "JJ" Smith, Jr."#));
     }
 
     #[test]
@@ -429,6 +1187,113 @@ mod tests {
         assert_eq!(sub.grade, -5);
     }
 
+    #[test]
+    fn test_attach_file() {
+        let dir = tempdir::TempDir::new("attach").unwrap();
+        let small = dir.path().join("small.txt");
+        let big = dir.path().join("big.txt");
+        std::fs::write(&small, "12345").unwrap();
+        std::fs::write(&big, "1234567890").unwrap();
+
+        let mut sub = Submission::new();
+        sub.set_artifact_limits(8, 12);
+        assert!(sub.attach_file(&big).is_err());
+        assert_eq!(sub.attach_file(&small).unwrap().size, 5);
+        // Already attached
+        assert!(sub.attach_file(&small).is_err());
+        assert_eq!(sub.artifacts.len(), 1);
+    }
+
+    #[test]
+    fn test_attach_glob() {
+        let dir = tempdir::TempDir::new("attach").unwrap();
+        for name in &["a.log", "b.log", "c.txt"] {
+            std::fs::write(dir.path().join(name), "log").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("d.log")).unwrap();
+
+        let mut sub = Submission::new();
+        let pattern = format!("{}/*.log", dir.path().display());
+        assert_eq!(sub.attach_glob(&pattern).unwrap(), 2);
+        assert!(sub.attach_glob("[").is_err());
+    }
+
+    #[test]
+    fn test_artifact_paths_not_sent() {
+        let dir = tempdir::TempDir::new("attach").unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "notes").unwrap();
+
+        let mut sub = Submission::new();
+        sub.attach_file(&file).unwrap();
+        let sent = sub.as_json_value().to_string();
+        assert!(sent.contains(&artifact::digest_bytes(b"notes")));
+        assert!(!sent.contains(&dir.path().display().to_string()));
+
+        // But they're kept when the submission is saved to the outbox
+        let saved = serde_json::to_string(&sub).unwrap();
+        let back: Submission = serde_json::from_str(&saved).unwrap();
+        assert_eq!(back.artifacts[0].path.as_ref(), Some(&file));
+
+        assert!(sub.header().ends_with(",artifacts"));
+    }
+
+    #[test]
+    fn test_public_key_not_sent() {
+        let (_, public_key) = encryption::generate_keys().unwrap();
+        let mut sub = Submission::new();
+        assert!(sub.set_public_key("not a key").is_err());
+        sub.set_public_key(&public_key).unwrap();
+
+        assert!(sub.as_json_value().get("public_key").is_none());
+        // Saved to the outbox, the key is kept so retries are encrypted too
+        let back: Submission = serde_json::from_str(&serde_json::to_string(&sub).unwrap()).unwrap();
+        assert_eq!(back.public_key, Some(public_key));
+    }
+
+    #[test]
+    fn test_api_token_not_sent() {
+        let mut sub = Submission::new();
+        sub.set_api_token("course token");
+        sub.set_fingerprint("secret");
+        sub.sign();
+
+        // It's a header, so it isn't in the JSON or the signature
+        assert!(sub.as_json_value().get("api_token").is_none());
+        assert!(sub.verify_signature("secret"));
+        let back: Submission = serde_json::from_str(&serde_json::to_string(&sub).unwrap()).unwrap();
+        assert_eq!(back.api_token.as_deref(), Some("course token"));
+    }
+
+    #[test]
+    fn test_team_submission() {
+        let mut sub = Submission::from_data(data! { "id" => "s1", "name" => "luke" });
+        assert!(sub.team_key().is_none());
+        assert_eq!(sub.per_member("id").len(), 1);
+
+        sub.set_team(None, vec!["s2", "s1"]);
+        assert_eq!(sub.team_key().unwrap(), "s1;s2");
+        assert!(sub.header().ends_with(",team"));
+        assert!(sub.as_csv().ends_with(",s1;s2"));
+
+        let rows = sub.per_member("id");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].data["id"], "s2");
+        assert_eq!(rows[1].data["id"], "s1");
+        // Only the submitter's row has their name
+        assert!(rows[0].data.get("name").is_none());
+        assert_eq!(rows[1].data["name"], "luke");
+
+        sub.set_member_data("s2", data! { "name" => "leia", "email" => "leia@example.edu" });
+        let rows = sub.per_member("id");
+        assert_eq!(rows[0].data["name"], "leia");
+        assert_eq!(rows[0].data["email"], "leia@example.edu");
+        assert!(rows[1].data.get("email").is_none());
+
+        sub.set_team(Some("Team Rocket"), vec!["s1", "s2"]);
+        assert_eq!(sub.team_key().unwrap(), "Team Rocket");
+    }
+
     #[test]
     fn test_add_fingerprint() {
         let mut sub = Submission::new();
@@ -441,6 +1306,36 @@ mod tests {
     fn test_submission_as_csv_with_fingerprint() {
         let mut sub = Submission::new();
         sub.set_fingerprint("secret key");
-        assert!(sub.header().contains("secret,platform"));
+        assert!(sub.header().contains("platform"));
+        assert!(!sub.header().contains("secret"));
+        assert!(!sub.as_csv().contains("secret key"));
+    }
+
+    #[test]
+    fn test_signature_survives_json() {
+        let mut sub = Submission::from_data(data! { "b" => "2", "a" => "1", "c" => "3" });
+        sub.set_fingerprint("secret key");
+        sub.grade = 80;
+        sub.sign();
+
+        let json = serde_json::to_string(&sub).unwrap();
+        assert!(!json.contains("secret key"));
+
+        // The dropbox verifies the JSON it receives
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(signature::verify_value("secret key", &value));
+
+        // Changing the grade breaks the signature
+        let forged = json.replace(r#""grade":80"#, r#""grade":100"#);
+        let value: serde_json::Value = serde_json::from_str(&forged).unwrap();
+        assert!(!signature::verify_value("secret key", &value));
+    }
+
+    #[test]
+    fn test_unsigned_without_fingerprint() {
+        let mut sub = Submission::new();
+        sub.sign();
+        assert!(sub.signature.is_none());
+        assert!(!sub.verify_signature("anything"));
     }
 }
@@ -93,6 +93,42 @@ pub fn prompt(msg: &str) -> String {
 }
 
 
+/// Prompts for a list, like team members' ids.
+///
+/// Entries can be separated by commas or spaces. Repeated entries are removed,
+/// and it asks again until at least one is entered.
+///
+/// ```no_run
+/// use rubric::helpers::cli::prompt_list;
+///
+/// let members = prompt_list("Team member ids: ");
+/// ```
+/// They see:
+/// ```text
+/// Team member ids: s123, s456 s789
+/// ```
+pub fn prompt_list(msg: &str) -> Vec<String> {
+    loop {
+        let list = split_list(&prompt(msg));
+        if !list.is_empty() {
+            return list;
+        }
+        eprintln!("Enter at least one. Try again.");
+    }
+}
+
+// Splits a list on commas and whitespace, removing repeats
+fn split_list(input: &str) -> Vec<String> {
+    let mut list: Vec<String> = Vec::new();
+    for item in input.split(|c: char| c == ',' || c.is_whitespace()).filter(|i| !i.is_empty()) {
+        if !list.iter().any(|existing| existing == item) {
+            list.push(String::from(item));
+        }
+    }
+    list
+}
+
+
 /// Prints the message, then returns true if the user enters y/yes, false otherwise.
 ///
 /// This method will append `[y/N]` to the message.
@@ -161,6 +197,11 @@ pub fn cmd(command: &str) -> std::result::Result<std::process::Output, std::io::
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list("s1, s2 s3,,s1"), vec!["s1", "s2", "s3"]);
+        assert!(split_list(" , ").is_empty());
+    }
 
     #[test]
     #[cfg(target_family = "windows")]
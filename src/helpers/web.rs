@@ -10,7 +10,8 @@ use std::time::Duration;
 // external uses
 use serde::Serialize;
 use reqwest::blocking::{Client, Response};
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
 
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
@@ -118,15 +119,73 @@ pub fn site_responds(url: &str) -> bool {
 /// }
 /// ```
 pub fn post_json<B: Serialize>(url: &str, body: B) -> Result<Response, reqwest::Error> {
+    post_json_with_token(url, body, None)
+}
+
+/// Like [`post_json`](crate::helpers::web::post_json), but if there's a token
+/// it's sent as a bearer token in the `Authorization` header.
+///
+/// ```no_run
+/// use rubric::helpers::web::post_json_with_token;
+///
+/// let result = post_json_with_token("http://localhost:8080/submit", "{}", Some("my token"));
+/// ```
+pub fn post_json_with_token<B: Serialize>(url: &str, body: B, token: Option<&str>) -> Result<Response, reqwest::Error> {
     let client = Client::builder()
         .user_agent(APP_USER_AGENT)
         .build()
         .expect("Couldn't build reqwest client. This shouldn't happen.");
 
-    client.post(url)
+    let mut req = client.post(url)
         .headers(construct_headers())
-        .json(&body)
-        .send()
+        .json(&body);
+    if let Some(token) = token {
+        req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+    }
+    req.send()
+}
+
+
+/// Posts a multipart form, with `body` as JSON in a field named `json_field`
+/// and one `file_field` part for each file.
+///
+/// Files are given as `(filename, contents)`.
+///
+/// ## Example
+/// ```no_run
+/// use rubric::helpers::web::post_multipart;
+/// use std::collections::HashMap;
+///
+/// let mut data = HashMap::new();
+/// data.insert("key", "value");
+/// let files = vec![(String::from("notes.txt"), b"some notes".to_vec())];
+///
+/// let result = post_multipart("https://postman-echo.com/post", "data", data, "file", files);
+/// ```
+pub fn post_multipart<B: Serialize>(url: &str, json_field: &str, body: B, file_field: &str, files: Vec<(String, Vec<u8>)>) -> Result<Response, reqwest::Error> {
+    post_multipart_with_token(url, json_field, body, file_field, files, None)
+}
+
+/// Like [`post_multipart`](crate::helpers::web::post_multipart), but with a
+/// bearer token if there is one. See [`post_json_with_token`](crate::helpers::web::post_json_with_token).
+pub fn post_multipart_with_token<B: Serialize>(url: &str, json_field: &str, body: B, file_field: &str, files: Vec<(String, Vec<u8>)>, token: Option<&str>) -> Result<Response, reqwest::Error> {
+    let client = Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .build()
+        .expect("Couldn't build reqwest client. This shouldn't happen.");
+
+    let json = serde_json::to_string(&body).expect("Couldn't serialize body to JSON");
+    let mut form = Form::new()
+        .part(json_field.to_string(), Part::text(json).mime_str("application/json")?);
+    for (name, contents) in files {
+        form = form.part(file_field.to_string(), Part::bytes(contents).file_name(name));
+    }
+
+    let mut req = client.post(url).multipart(form);
+    if let Some(token) = token {
+        req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+    }
+    req.send()
 }
 
 
@@ -9,12 +9,21 @@
 #[macro_use] extern crate rocket;
 extern crate rocket_contrib;
 extern crate serde_yaml;
+extern crate toml;
 extern crate serde_json;
 extern crate reqwest;
 extern crate chrono;
 extern crate anyhow;
 extern crate serde;
 extern crate regex;
+extern crate rand;
+extern crate sha2;
+extern crate hmac;
+extern crate glob;
+extern crate openssl;
+extern crate base64;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 extern crate paris;
 
 // External testing crates
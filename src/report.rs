@@ -30,6 +30,7 @@ pub fn short(mut rubric: &mut Rubric) {
     log.newline(1);
     components::short_criteria(&mut rubric);
     log.newline(1);
+    components::short_deductions(&rubric);
 
     components::hidden(&rubric);
     components::grade(&rubric);
@@ -49,6 +50,7 @@ pub fn long(mut rubric: &mut Rubric) {
     log.newline(1);
 
     components::long_criteria(&mut rubric);
+    components::long_deductions(&mut rubric);
 
     components::hidden(&rubric);
     components::grade(&rubric);
@@ -106,13 +108,43 @@ mod components {
     }
 
     pub fn short_criteria(rubric: &mut Rubric) {
-        for crit in rubric.sorted() {
+        for crit in rubric.sorted().iter().filter(|c| !c.deduction) {
             crit.print_short();
         }
     }
 
     pub fn long_criteria(rubric: &mut Rubric) {
-        for crit in rubric.sorted() {
+        for crit in rubric.sorted().iter().filter(|c| !c.deduction) {
+            crit.print_long();
+            println!();
+        }
+    }
+
+    // Only triggered deductions are worth showing in the short report
+    pub fn short_deductions(rubric: &Rubric) {
+        let mut log = Logger::new();
+        let triggered: Vec<_> = rubric.deductions()
+            .into_iter()
+            .filter(|c| c.status == Some(true))
+            .collect();
+
+        if triggered.is_empty() {
+            return;
+        }
+
+        for crit in triggered {
+            crit.print_short();
+        }
+        log.newline(1);
+    }
+
+    pub fn long_deductions(rubric: &mut Rubric) {
+        if rubric.deductions().is_empty() {
+            return;
+        }
+
+        Logger::new().info("<bold>Deductions</>");
+        for crit in rubric.sorted().iter().filter(|c| c.deduction) {
             crit.print_long();
             println!();
         }
@@ -120,10 +152,14 @@ mod components {
 
     pub fn grade(rubric: &Rubric) {
         let mut log = Logger::new();
-        if rubric.points() as isize >= rubric.total_points() {
-            log.success(format!("<bold>Grade: <green>{}/{}</>", rubric.points(), rubric.total_points()));
+        if rubric.deducted() > 0 {
+            log.info(format!("Deducted: <red>-{}</>", rubric.deducted()));
+        }
+
+        if rubric.net_points() >= rubric.total_points() {
+            log.success(format!("<bold>Grade: <green>{}/{}</>", rubric.net_points(), rubric.total_points()));
         } else {
-            log.info(format!("<bold>Grade: {}/{}</>", rubric.points(), rubric.total_points()));
+            log.info(format!("<bold>Grade: {}/{}</>", rubric.net_points(), rubric.total_points()));
         }
     }
 
@@ -0,0 +1,104 @@
+//! How many times a student may submit
+//!
+//! The dropbox counts attempts per student, identified by one of the keys in
+//! the submission's data. Give it the rubric's policy when opening it.
+
+// external uses
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use crate::Rubric;
+use crate::dropbox::Submission;
+
+
+/// The data key that identifies a student, unless the rubric says otherwise
+pub const DEFAULT_IDENTITY_KEY: &str = "id";
+
+
+/// A limit on attempts per student.
+///
+/// You probably want to get this from a rubric with
+/// [`Rubric::attempt_policy`](crate::rubric::Rubric::attempt_policy).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttemptPolicy {
+    /// How many submissions each student gets. `None` means no limit,
+    /// but attempts are still counted.
+    pub max_attempts: Option<u32>,
+    /// The key in the submission's data that identifies a student
+    pub identity_key: String,
+    /// Reject attempts over the limit. If false, they're recorded with a flag.
+    pub reject_extra: bool,
+}
+
+impl Default for AttemptPolicy {
+    /// No limit, students identified by `id`, extra attempts rejected
+    fn default() -> Self {
+        AttemptPolicy {
+            max_attempts: None,
+            identity_key: String::from(DEFAULT_IDENTITY_KEY),
+            reject_extra: true,
+        }
+    }
+}
+
+impl AttemptPolicy {
+    /// Copies the attempt policy out of a rubric
+    pub fn from_rubric(rubric: &Rubric) -> Self {
+        AttemptPolicy {
+            max_attempts: rubric.max_attempts,
+            identity_key: rubric.identity_key.clone(),
+            reject_extra: rubric.reject_extra_attempts,
+        }
+    }
+
+    /// The student who made a submission, if the identity key is in its data
+    pub fn student(&self, sub: &Submission) -> Option<String> {
+        sub.data.get(&self.identity_key)
+            .filter(|s| !s.is_empty())
+            .cloned()
+    }
+
+    /// Returns true if this attempt number is over the limit
+    pub fn exceeded(&self, attempt: u32) -> bool {
+        match self.max_attempts {
+            Some(max) => attempt > max,
+            None => false,
+        }
+    }
+}
+
+/// Reads an identity key from a rubric, where it may be written as `data.id`
+pub(crate) fn parse_identity_key(key: &str) -> String {
+    key.trim_start_matches("data.").to_string()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    #[test]
+    fn test_student() {
+        let policy = AttemptPolicy::default();
+        let sub = Submission::from_data(data! { "id" => "s123" });
+        assert_eq!(policy.student(&sub), Some(String::from("s123")));
+        assert_eq!(policy.student(&Submission::new()), None);
+    }
+
+    #[test]
+    fn test_exceeded() {
+        let mut policy = AttemptPolicy::default();
+        assert!(!policy.exceeded(100));
+        policy.max_attempts = Some(3);
+        assert!(!policy.exceeded(3));
+        assert!(policy.exceeded(4));
+    }
+
+    #[test]
+    fn test_parse_identity_key() {
+        assert_eq!(parse_identity_key("data.id"), "id");
+        assert_eq!(parse_identity_key("student_id"), "student_id");
+    }
+}
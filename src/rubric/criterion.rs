@@ -9,15 +9,69 @@
 //! You **probably shouldn't** create criteria individually through this module,
 //! but you can if you want. Instead, you should define your criteria in `YAML` then
 //! build that into a [`Rubric`](crate::rubric::Rubric).
+//!
+//! A criterion can also contain child criteria. These are combined with a
+//! [`Combinator`](crate::rubric::criterion::Combinator) to decide if the parent
+//! passes, and how many points it's worth.
+
+// std uses
+use std::str::FromStr;
 
 // external uses
 use paris::{Logger, formatter::Formatter};
+use anyhow::anyhow;
 
 // internal uses
 use crate::TestData;
 use crate::rubric::CriterionBuilder;
 
 
+/// How a criterion with children decides if it passed.
+///
+/// In `yaml` this is the `combine` field, and can be `all`, `any`,
+/// `sum`, or `at_least N`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combinator {
+    /// Passes if every child passes. All or nothing.
+    All,
+    /// Passes if any one of the children passes.
+    Any,
+    /// Passes if at least this many children pass.
+    AtLeast(usize),
+    /// Earns the worth of every child that passed, up to the
+    /// parent's worth. Passes if any child passed.
+    Sum,
+}
+
+impl Default for Combinator {
+    fn default() -> Self {
+        Combinator::All
+    }
+}
+
+impl FromStr for Combinator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts: Vec<String> = s.split_whitespace()
+            .map(|p| p.to_lowercase())
+            .collect();
+
+        match parts.iter().map(|p| p.as_str()).collect::<Vec<_>>().as_slice() {
+            ["all"] => Ok(Combinator::All),
+            ["any"] => Ok(Combinator::Any),
+            ["sum"] => Ok(Combinator::Sum),
+            ["at_least", n] | ["at", "least", n] => {
+                let n = n.parse::<usize>()
+                    .map_err(|_| anyhow!("Bad number in combinator `{}`", s))?;
+                Ok(Combinator::AtLeast(n))
+            },
+            _ => Err(anyhow!("Unknown combinator `{}`. Use all, any, sum, or at_least N", s)),
+        }
+    }
+}
+
+
 /// A single Criterion
 pub struct Criterion {
     /// The name of the function that serves as this criterions test
@@ -29,8 +83,8 @@ pub struct Criterion {
     /// Point value of this criterion. If it passes, this value
     /// will be added to the [`Submission`](crate::submission::Submission) grade.
     ///
-    /// Can be negative if you wish to subtract points. Be sure to get your logic right.
-    /// This value is added to the submission grade *if the test returns true*.
+    /// If you want to subtract points, make this criterion a
+    /// [`deduction`](crate::rubric::criterion::Criterion::deduction) instead.
     pub worth: isize,
     /// An index to sort by when running.
     ///
@@ -55,6 +109,16 @@ pub struct Criterion {
     pub status: Option<bool>,
     /// Renders the criterion unable to be printed
     pub hide: bool,
+    /// Child criteria. If this is empty, the criterion's own
+    /// test is run, otherwise the children are run and combined.
+    pub children: Vec<Criterion>,
+    /// How the children are combined. Ignored if there are no children.
+    pub combinator: Combinator,
+    /// Makes this criterion a deduction.
+    ///
+    /// A deduction is *triggered* when its test returns true, and subtracts its
+    /// worth from the grade. Deductions don't count towards the total points possible.
+    pub deduction: bool,
 }
 
 impl Criterion {
@@ -102,8 +166,94 @@ impl Criterion {
     /// You shouldn't call this method directly, instead grade an entire
     /// [`Rubric`](crate::rubric::Rubric).
     pub fn test_with_data(&mut self, data: &TestData) -> bool {
-        self.status = Some((self.test)(data));
-        self.status.unwrap()
+        if self.children.is_empty() {
+            self.status = Some((self.test)(data));
+            return self.status.unwrap();
+        }
+
+        // Children are sorted and tested recursively, just like a rubric
+        self.children.sort_by(|a, b| a.index.cmp(&b.index));
+        let mut passed = 0;
+        for child in &mut self.children {
+            // Deductions are tested, but they subtract points on their
+            // own instead of deciding if the parent passed
+            if child.test_with_data(data) && !child.deduction {
+                passed += 1;
+            }
+        }
+        let graded = self.children.iter().filter(|c| !c.deduction).count();
+
+        let status = match self.combinator {
+            Combinator::All => passed == graded,
+            Combinator::Any | Combinator::Sum => passed > 0,
+            Combinator::AtLeast(n) => passed >= n,
+        };
+        self.status = Some(status);
+        status
+    }
+
+    /// Returns true if this criterion has child criteria
+    pub fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    /// The points this criterion earned after testing.
+    ///
+    /// This is the worth if it passed, or 0 otherwise. A criterion
+    /// that sums its children earns the points of each child that passed,
+    /// but never more than its own worth.
+    ///
+    /// A triggered deduction earns its worth as negative points. Deductions
+    /// among the children are subtracted whether or not the parent passed.
+    pub fn earned(&self) -> isize {
+        if self.deduction {
+            return match self.status {
+                Some(true) => -self.worth.abs(),
+                _ => 0,
+            };
+        }
+        let deducted: isize = self.deductions().iter().map(|d| d.earned()).sum();
+        self.earned_without_deductions() + deducted
+    }
+
+    // The points earned, leaving out any deductions
+    fn earned_without_deductions(&self) -> isize {
+        if self.status != Some(true) {
+            return 0;
+        }
+
+        match self.combinator {
+            Combinator::Sum if self.has_children() => {
+                let earned: isize = self.children.iter()
+                    .filter(|c| !c.deduction)
+                    .map(|c| c.earned_without_deductions())
+                    .sum();
+                earned.min(self.worth)
+            },
+            _ => self.worth,
+        }
+    }
+
+    /// The deductions among this criterion's descendants
+    pub fn deductions(&self) -> Vec<&Criterion> {
+        let mut deductions = Vec::new();
+        for child in &self.children {
+            if child.deduction {
+                deductions.push(child);
+            } else {
+                deductions.extend(child.deductions());
+            }
+        }
+        deductions
+    }
+
+    /// Searches this criterion and all of its descendants for
+    /// a criterion with the given func.
+    pub fn find_mut(&mut self, func: &str) -> Option<&mut Criterion> {
+        if self.func == func {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|c| c.find_mut(func))
     }
 
     /// Runs the criterions test and assigns the result to `criterion.status`.
@@ -126,7 +276,7 @@ impl Criterion {
 
         let mut log = Logger::new();
         
-        if let Some(s) = self.status {
+        if let Some(s) = self.good() {
             // Already tested, diff color based on status
             if s {
                 log.same().success(&self.name).log(
@@ -143,7 +293,15 @@ impl Criterion {
         }
     }
 
+    /// Prints all the criterion information, and the information of
+    /// any children, indented underneath it.
+    /// Will do nothing if the `hide` field is true
     pub fn print_long(&self) {
+        self.print_long_at(0);
+    }
+
+    // Prints the criterion indented by `depth` tabs
+    fn print_long_at(&self, depth: usize) {
         // Never print if it's hidden
         if self.hide {
             return;
@@ -151,7 +309,8 @@ impl Criterion {
 
         let mut log = Logger::new();
         // Name and status
-        if let Some(s) = self.status {
+        if let Some(s) = self.good() {
+            log.indent(depth);
             if s {
                 log.same().success(&self.name);
             } else {
@@ -161,16 +320,38 @@ impl Criterion {
             log.same().log("  ").log(self.colored_status_message());
         } else {
             // Hasn't been tested
-            log.warn(format!("{}  <bold>Not Tested</>", self.name));
+            log.indent(depth).warn(format!("{}  <bold>Not Tested</>", self.name));
         }
 
         // Description
         if let Some(desc) = &self.desc {
-            log.info(desc);
+            log.indent(depth).info(desc);
         }
         
         // Worth
-        log.info(format!("Worth: <bold>{}</>", self.worth));
+        if self.deduction {
+            log.indent(depth).info(format!("Deduction: <bold>-{}</>", self.worth.abs()));
+        } else if self.has_children() {
+            log.indent(depth).info(format!(
+                "Worth: <bold>{}</> ({})", self.worth, self.combinator_description()
+            ));
+        } else {
+            log.indent(depth).info(format!("Worth: <bold>{}</>", self.worth));
+        }
+
+        for child in &self.children {
+            child.print_long_at(depth + 1);
+        }
+    }
+
+    // A short description of how the children are combined
+    fn combinator_description(&self) -> String {
+        match self.combinator {
+            Combinator::All => String::from("all must pass"),
+            Combinator::Any => String::from("any one must pass"),
+            Combinator::AtLeast(n) => format!("at least {} of {} must pass", n, self.children.len()),
+            Combinator::Sum => format!("earned {}", self.earned()),
+        }
     }
 
 
@@ -187,13 +368,19 @@ impl Criterion {
 
     /// Same as [`status_message`](crate::rubric::criterion::Criterion::status_message), but
     /// the success message will be colored green and the failure message red.
+    ///
+    /// For deductions this is reversed, as a triggered deduction is bad news.
     pub fn colored_status_message(&self) -> String {
         let fmt = Formatter::new();
-        if self.status == Some(true) {
-            fmt.colorize(&format!("<green>{}</>", self.success_message()))
-        } else {
-            fmt.colorize(&format!("<red>{}</>", self.failure_message()))
-        }
+        let color = if self.good() == Some(true) { "green" } else { "red" };
+        fmt.colorize(&format!("<{}>{}</>", color, self.status_message()))
+    }
+
+    // If the result of this criterion is good for the student, ie. it
+    // passed or it's a deduction that wasn't triggered.
+    // None if it hasn't been tested.
+    fn good(&self) -> Option<bool> {
+        self.status.map(|s| s != self.deduction)
     }
 
 
@@ -231,6 +418,107 @@ mod tests {
         assert_eq!(c.failure_message(), "failure");
     }
 
+    fn composite(combinator: Combinator) -> Criterion {
+        Criterion::new("parent")
+            .worth(20)
+            .combinator(combinator)
+            .child(Criterion::new("pass").worth(10).test(Box::new(|_: &TestData| true)).build())
+            .child(Criterion::new("fail").worth(10).build())
+            .build()
+    }
+
+    #[test]
+    fn test_combinators() {
+        let mut all = composite(Combinator::All);
+        assert!(!all.test());
+        assert_eq!(all.earned(), 0);
+        assert_eq!(all.children[0].status, Some(true));
+
+        let mut any = composite(Combinator::Any);
+        assert!(any.test());
+        assert_eq!(any.earned(), 20);
+
+        let mut at_least = composite(Combinator::AtLeast(2));
+        assert!(!at_least.test());
+
+        let mut sum = composite(Combinator::Sum);
+        assert!(sum.test());
+        assert_eq!(sum.earned(), 10);
+
+        // Never more than the parent is worth
+        let mut capped = composite(Combinator::Sum);
+        capped.worth = 5;
+        capped.test();
+        assert_eq!(capped.earned(), 5);
+    }
+
+    #[test]
+    fn test_parse_combinator() {
+        assert_eq!("all".parse::<Combinator>().unwrap(), Combinator::All);
+        assert_eq!("Any".parse::<Combinator>().unwrap(), Combinator::Any);
+        assert_eq!("at_least 2".parse::<Combinator>().unwrap(), Combinator::AtLeast(2));
+        assert_eq!("at least 3".parse::<Combinator>().unwrap(), Combinator::AtLeast(3));
+        assert!("most".parse::<Combinator>().is_err());
+        assert!("at_least two".parse::<Combinator>().is_err());
+    }
+
+    #[test]
+    fn test_deduction() {
+        let mut crit = Criterion::new("uses unsafe")
+            .worth(5)
+            .deduction(true)
+            .test(Box::new(|_: &TestData| true))
+            .build();
+        assert!(crit.test());
+        assert_eq!(crit.earned(), -5);
+        assert_eq!(crit.good(), Some(false));
+
+        let mut untriggered = Criterion::new("committed artifacts")
+            .worth(5)
+            .deduction(true)
+            .build();
+        assert!(!untriggered.test());
+        assert_eq!(untriggered.earned(), 0);
+        assert_eq!(untriggered.good(), Some(true));
+    }
+
+    #[test]
+    fn test_nested_deduction() {
+        let deduction = |triggered: bool| Criterion::new("uses unsafe")
+            .worth(5)
+            .deduction(true)
+            .test(Box::new(move |_: &TestData| triggered))
+            .build();
+        let parent = |triggered: bool| Criterion::new("parent")
+            .worth(10)
+            .child(Criterion::new("pass").worth(10).test(Box::new(|_: &TestData| true)).build())
+            .child(deduction(triggered))
+            .build();
+
+        // An untriggered deduction doesn't fail the group
+        let mut clean = parent(false);
+        assert!(clean.test());
+        assert_eq!(clean.earned(), 10);
+
+        // A triggered one doesn't pass it, it just subtracts
+        let mut unsafe_code = parent(true);
+        assert!(unsafe_code.test());
+        assert_eq!(unsafe_code.earned(), 5);
+        assert_eq!(unsafe_code.deductions().len(), 1);
+
+        let mut sum = parent(true);
+        sum.combinator = Combinator::Sum;
+        sum.test();
+        assert_eq!(sum.earned(), 5);
+    }
+
+    #[test]
+    fn test_find_nested() {
+        let mut crit = composite(Combinator::All);
+        assert!(crit.find_mut("fail").is_some());
+        assert!(crit.find_mut("nope").is_none());
+    }
+
     #[test]
     fn test_data_macro() {
         // The long way
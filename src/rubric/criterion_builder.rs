@@ -1,5 +1,5 @@
 // internal uses
-use crate::{TestData, rubric::Criterion};
+use crate::{TestData, rubric::Criterion, rubric::criterion::Combinator};
 
 
 /// A builder struct that builds a Criterion. You should create one
@@ -13,7 +13,10 @@ pub struct CriterionBuilder {
     desc: Option<String>,
     test: Option<Box<dyn Fn(&TestData) -> bool>>,
     index: i64,
-    hide: bool
+    hide: bool,
+    children: Vec<Criterion>,
+    combinator: Combinator,
+    deduction: bool
 }
 
 impl CriterionBuilder {
@@ -32,7 +35,10 @@ impl CriterionBuilder {
             desc: None,
             test: None,
             index: 100,
-            hide: false
+            hide: false,
+            children: Vec::new(),
+            combinator: Combinator::default(),
+            deduction: false
         }
     }
 
@@ -131,6 +137,45 @@ impl CriterionBuilder {
         self
     }
 
+    /// Adds a child criterion. A criterion with children doesn't
+    /// run its own test, instead it combines the results of its children.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::{CriterionBuilder, Criterion};
+    /// let crit = CriterionBuilder::new("Docker works")
+    ///     .child(Criterion::new("Dockerfile builds").worth(10).build())
+    ///     .child(Criterion::new("Serves port 80").worth(10).build())
+    ///     .build();
+    /// assert_eq!(crit.children.len(), 2);
+    /// ```
+    pub fn child(mut self, child: Criterion) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Sets how the children are combined. Defaults to
+    /// [`Combinator::All`](crate::rubric::criterion::Combinator::All).
+    pub fn combinator(mut self, combinator: Combinator) -> Self {
+        self.combinator = combinator;
+        self
+    }
+
+    /// Makes the criterion a deduction. A deduction subtracts its worth
+    /// when its test returns true.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Uses unsafe")
+    ///     .worth(10)
+    ///     .deduction(true)
+    ///     .build();
+    /// assert!(crit.deduction);
+    /// ```
+    pub fn deduction(mut self, deduction: bool) -> Self {
+        self.deduction = deduction;
+        self
+    }
+
     /// Finalizes the criterion.
     ///
     /// If a function name wasn't manually set, it will create one based on the
@@ -161,7 +206,10 @@ impl CriterionBuilder {
             test: self.test.unwrap_or(Box::new(|_: &TestData| false)),
             index: self.index,
             status: None,
-            hide: self.hide
+            hide: self.hide,
+            children: self.children,
+            combinator: self.combinator,
+            deduction: self.deduction
         }
     }
 }
@@ -0,0 +1,231 @@
+//! A rubric's deadlines and late penalties, on their own
+//!
+//! The grader uses this to work out penalties from the student's clock. The dropbox
+//! can be given the same policy, so it can work them out again from its own clock.
+
+// external uses
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use crate::Rubric;
+
+
+/// Deadlines and penalties for late submission.
+///
+/// You probably want to get this from a rubric with
+/// [`Rubric::late_policy`](crate::rubric::Rubric::late_policy).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatePolicy {
+    pub deadline: Option<DateTime<Local>>,
+    pub final_deadline: Option<DateTime<Local>>,
+    pub allow_late: bool,
+    pub late_penalty: isize,
+    pub daily_penalty: isize,
+}
+
+impl Default for LatePolicy {
+    /// No deadlines, no penalties
+    fn default() -> Self {
+        LatePolicy {
+            deadline: None,
+            final_deadline: None,
+            allow_late: true,
+            late_penalty: 0,
+            daily_penalty: 0,
+        }
+    }
+}
+
+impl LatePolicy {
+    /// Copies the late policy out of a rubric
+    pub fn from_rubric(rubric: &Rubric) -> Self {
+        LatePolicy {
+            deadline: rubric.deadline,
+            final_deadline: rubric.final_deadline,
+            allow_late: rubric.allow_late,
+            late_penalty: rubric.late_penalty,
+            daily_penalty: rubric.daily_penalty,
+        }
+    }
+
+    /// Returns true if a submission at this time is past the deadline
+    pub fn late_at(&self, time: DateTime<Local>) -> bool {
+        match self.deadline {
+            Some(deadline) => deadline.timestamp() < time.timestamp(),
+            None => false,
+        }
+    }
+
+    /// Returns true if a submission at this time is past the final deadline
+    pub fn past_final_deadline_at(&self, time: DateTime<Local>) -> bool {
+        match self.final_deadline {
+            Some(final_deadline) => final_deadline.timestamp() < time.timestamp(),
+            None => false,
+        }
+    }
+
+    /// The penalties for a submission at this time with this grade, in the order
+    /// they're taken off. Not being allowed late, or being past the final deadline,
+    /// takes off everything.
+    pub fn penalties_at(&self, time: DateTime<Local>, grade: isize) -> Vec<LatePenalty> {
+        if self.past_final_deadline_at(time) {
+            return vec![LatePenalty::new(LatePenaltyKind::PastFinalDeadline, grade)];
+        }
+        if !self.late_at(time) {
+            return Vec::new();
+        }
+
+        let days = self.days_late_at(time);
+        let mut penalties = vec![
+            LatePenalty::new(LatePenaltyKind::Late, self.late_penalty),
+            LatePenalty::new(LatePenaltyKind::DaysLate(days), self.daily_penalty * days as isize),
+        ];
+        if !self.allow_late {
+            let left = grade - penalties.iter().map(|p| p.amount).sum::<isize>();
+            penalties.push(LatePenalty::new(LatePenaltyKind::PastDeadline, left));
+        }
+        penalties
+    }
+
+    /// The number of days late a submission at this time is.
+    ///
+    /// This is the amount of whole days since the deadline + 1.
+    /// One second after the deadline counts as 1 day,
+    /// exactly 24 hours + 1 second after the deadline is 2 days.
+    /// A submission that isn't late is 0 days late.
+    pub fn days_late_at(&self, time: DateTime<Local>) -> i64 {
+        if !self.late_at(time) {
+            return 0;
+        }
+        self.deadline
+            .unwrap()
+            .signed_duration_since(time)
+            .num_days()
+            .abs() + 1
+    }
+}
+
+
+/// Why a submission lost points for when it was made
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatePenaltyKind {
+    /// The penalty for being late at all
+    Late,
+    /// The penalty for each day late, and how many days
+    DaysLate(i64),
+    /// Late, when late submission isn't allowed
+    PastDeadline,
+    /// Past the final deadline
+    PastFinalDeadline,
+}
+
+/// Points a submission lost for being late
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatePenalty {
+    /// Why it was taken off
+    pub kind: LatePenaltyKind,
+    /// The points taken off
+    pub amount: isize,
+}
+
+impl LatePenalty {
+    /// A penalty of this many points
+    pub fn new(kind: LatePenaltyKind, amount: isize) -> Self {
+        LatePenalty { kind, amount }
+    }
+
+    /// How the penalty reads in a submission's failed list, like `Late submission (-5)`
+    pub fn message(&self) -> String {
+        let reason = match self.kind {
+            LatePenaltyKind::Late => String::from("Late submission"),
+            LatePenaltyKind::DaysLate(days) => format!("{} days late", days),
+            LatePenaltyKind::PastDeadline => String::from("Past deadline"),
+            LatePenaltyKind::PastFinalDeadline => String::from("Past final deadline"),
+        };
+        format!("{} (-{})", reason, self.amount)
+    }
+
+    /// Reads a penalty back from its [`message`](LatePenalty::message), for
+    /// submissions that only kept their failed list. `None` if it isn't one.
+    pub fn from_message(message: &str) -> Option<Self> {
+        let open = message.rfind(" (-")?;
+        let amount = message[open + 3..].strip_suffix(')')?.parse().ok()?;
+        let kind = match &message[..open] {
+            "Late submission" => LatePenaltyKind::Late,
+            "Past deadline" => LatePenaltyKind::PastDeadline,
+            "Past final deadline" => LatePenaltyKind::PastFinalDeadline,
+            reason => LatePenaltyKind::DaysLate(reason.strip_suffix(" days late")?.parse().ok()?),
+        };
+        Some(LatePenalty { kind, amount })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn policy() -> LatePolicy {
+        LatePolicy {
+            deadline: Some(Local::now()),
+            late_penalty: 5,
+            daily_penalty: 2,
+            ..LatePolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_late_at() {
+        let p = policy();
+        let deadline = p.deadline.unwrap();
+        assert!(!p.late_at(deadline - Duration::seconds(10)));
+        assert!(p.late_at(deadline + Duration::seconds(10)));
+        assert!(!LatePolicy::default().late_at(Local::now()));
+    }
+
+    #[test]
+    fn test_days_late() {
+        let p = policy();
+        let deadline = p.deadline.unwrap();
+        assert_eq!(p.days_late_at(deadline - Duration::hours(1)), 0);
+        assert_eq!(p.days_late_at(deadline + Duration::seconds(1)), 1);
+        assert_eq!(p.days_late_at(deadline + Duration::hours(25)), 2);
+    }
+
+    #[test]
+    fn test_penalties() {
+        let mut p = policy();
+        let deadline = p.deadline.unwrap();
+        assert!(p.penalties_at(deadline - Duration::hours(1), 50).is_empty());
+
+        let penalties = p.penalties_at(deadline + Duration::hours(25), 50);
+        assert_eq!(penalties, vec![
+            LatePenalty::new(LatePenaltyKind::Late, 5),
+            LatePenalty::new(LatePenaltyKind::DaysLate(2), 4),
+        ]);
+        assert_eq!(penalties[1].message(), "2 days late (-4)");
+
+        // Everything that's left
+        p.allow_late = false;
+        let penalties = p.penalties_at(deadline + Duration::hours(1), 50);
+        assert_eq!(penalties[2], LatePenalty::new(LatePenaltyKind::PastDeadline, 43));
+
+        p.final_deadline = Some(deadline);
+        let penalties = p.penalties_at(deadline + Duration::hours(1), 50);
+        assert_eq!(penalties, vec![LatePenalty::new(LatePenaltyKind::PastFinalDeadline, 50)]);
+    }
+
+    #[test]
+    fn test_from_message() {
+        for kind in &[LatePenaltyKind::Late, LatePenaltyKind::DaysLate(3), LatePenaltyKind::PastDeadline, LatePenaltyKind::PastFinalDeadline] {
+            let penalty = LatePenalty::new(*kind, 5);
+            assert_eq!(LatePenalty::from_message(&penalty.message()), Some(penalty));
+        }
+        assert_eq!(LatePenalty::from_message("Compiles (-0)"), None);
+        assert_eq!(LatePenalty::from_message("Late submission (+5)"), None);
+        assert_eq!(LatePenalty::from_message("many days late (-5)"), None);
+    }
+}
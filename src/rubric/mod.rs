@@ -28,12 +28,19 @@
 // Re exports to be available from this module
 pub mod criterion;
 pub mod criterion_builder;
+pub mod late_policy;
+pub mod attempt_policy;
+pub mod score_policy;
 
-pub use criterion::Criterion;
+pub use criterion::{Criterion, Combinator};
 pub use criterion_builder::CriterionBuilder;
+pub use late_policy::{LatePenalty, LatePenaltyKind, LatePolicy};
+pub use attempt_policy::AttemptPolicy;
+pub use score_policy::ScorePolicy;
 
 
 // std uses
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::default::Default;
 
@@ -63,13 +70,24 @@ use crate::{Result, yaml::RubricYaml};
 pub struct Rubric {
     pub name: String,
     pub desc: Option<String>,
+    /// Which version of the rubric this is, if it's versioned.
+    /// A dropbox can refuse submissions graded against old versions.
+    pub version: Option<String>,
     pub criteria: Vec<Criterion>,
     pub total: isize,
     pub deadline: Option<DateTime<Local>>,
     pub final_deadline: Option<DateTime<Local>>,
     pub allow_late: bool,
     pub late_penalty: isize,
-    pub daily_penalty: isize
+    pub daily_penalty: isize,
+    /// How many times each student may submit, if there's a limit
+    pub max_attempts: Option<u32>,
+    /// The data key that identifies a student
+    pub identity_key: String,
+    /// Reject attempts over the limit, instead of flagging them
+    pub reject_extra_attempts: bool,
+    /// Which attempt counts in the gradebook
+    pub score_policy: ScorePolicy,
 }
 
 impl Default for Rubric {
@@ -80,13 +98,18 @@ impl Default for Rubric {
         Rubric {
             name: String::new(),
             desc: None,
+            version: None,
             criteria: Vec::new(),
             total: 0,
             deadline: None,
             final_deadline: None,
             allow_late: true,
             late_penalty: 0,
-            daily_penalty: 0
+            daily_penalty: 0,
+            max_attempts: None,
+            identity_key: String::from(attempt_policy::DEFAULT_IDENTITY_KEY),
+            reject_extra_attempts: true,
+            score_policy: ScorePolicy::default(),
         }
     }
 }
@@ -126,8 +149,11 @@ impl Rubric {
     /// let not_criterion = rubric.get("doesnt-exist");
     /// assert!(not_criterion.is_none());
     /// ```
+    ///
+    /// Child criteria are searched as well, so you can attach
+    /// tests to nested criteria.
     pub fn get(&mut self, func: &str) -> Option<&mut Criterion> {
-        self.criteria.iter_mut().find(|c| c.func == func)
+        self.criteria.iter_mut().find_map(|c| c.find_mut(func))
     }
 
     /// Adds a criterion to the rubric.
@@ -156,30 +182,56 @@ impl Rubric {
     ///
     /// If you run this before grading, it should return 0. If it
     /// doesn't, call me lmao.
+    ///
+    /// Triggered deductions are subtracted, but this won't go below 0.
+    /// Use [`net_points`](crate::rubric::Rubric::net_points) to see how far below.
     pub fn points(&self) -> usize {
-        let mut total: usize = 0;
+        self.net_points().max(0) as usize
+    }
+
+    /// The total points earned after grading, less triggered
+    /// deductions. This can be negative.
+    pub fn net_points(&self) -> isize {
+        let mut total: isize = 0;
         for crit in &self.criteria {
-            if let Some(status) = crit.status {
-                if status {
-                    // Only add to the total if they've graded
-                    // and this criterion passed
-                    total += crit.worth as usize;
-                }
-            }
+            // Only passed criteria have earned anything
+            total += crit.earned();
         }
         total
     }
 
     /// Returns the total worth of all criteria, ie. the
     /// maximum number of points possible.
+    ///
+    /// Deductions aren't included.
     pub fn total_points(&self) -> isize {
         let mut total: isize = 0;
-        for crit in &self.criteria {
+        for crit in self.criteria.iter().filter(|c| !c.deduction) {
             total += crit.worth as isize;
         }
         total
     }
 
+    /// Returns the deduction criteria in the rubric, including
+    /// ones inside other criteria.
+    pub fn deductions(&self) -> Vec<&Criterion> {
+        let mut deductions = Vec::new();
+        for crit in &self.criteria {
+            if crit.deduction {
+                deductions.push(crit);
+            } else {
+                deductions.extend(crit.deductions());
+            }
+        }
+        deductions
+    }
+
+    /// The total points subtracted by triggered deductions,
+    /// as a positive number.
+    pub fn deducted(&self) -> isize {
+        self.deductions().iter().map(|c| -c.earned()).sum()
+    }
+
     /// Returns a reference to a `Vec` of the criteria. This
     /// is like [`sorted`](crate::rubric::Rubric::sorted), but
     /// they aren't sorted.
@@ -193,21 +245,45 @@ impl Rubric {
     }
 
     pub fn past_due(&self) -> bool {
-        if let Some(deadline) = self.deadline {
-            return deadline.timestamp() < Local::now().timestamp();
-        }
-        false
+        self.late_policy().late_at(Local::now())
     }
 
     pub fn past_final_deadline(&self) -> bool {
-        if let Some(final_deadline) = self.final_deadline {
-            return final_deadline.timestamp() < Local::now().timestamp();
-        }
-        false
+        self.late_policy().past_final_deadline_at(Local::now())
+    }
+
+    /// Returns the rubric's deadlines and late penalties. You can
+    /// give this to the dropbox so it can decide lateness itself.
+    pub fn late_policy(&self) -> LatePolicy {
+        LatePolicy::from_rubric(self)
+    }
+
+    /// Returns the rubric's limit on attempts. Give this to the
+    /// dropbox so it can count attempts per student.
+    pub fn attempt_policy(&self) -> AttemptPolicy {
+        AttemptPolicy::from_rubric(self)
     }
 
 }
 
+// Makes sure no two criteria anywhere in the tree share a func. Children
+// named the same under different parents would, and `attach!` only ever
+// finds the first, so the other would fail without a test.
+fn check_funcs(criteria: &[Criterion]) -> Result<()> {
+    fn walk<'a>(criteria: &'a [Criterion], seen: &mut HashSet<&'a str>) -> Result<()> {
+        for crit in criteria {
+            if !seen.insert(&crit.func) {
+                return Err(anyhow::anyhow!(
+                    "More than one criterion has the func '{}', give '{}' its own func",
+                    crit.func, crit.name
+                ));
+            }
+            walk(&crit.children, seen)?;
+        }
+        Ok(())
+    }
+    walk(criteria, &mut HashSet::new())
+}
 
 impl FromStr for Rubric {
     type Err = anyhow::Error;
@@ -222,11 +298,15 @@ impl FromStr for Rubric {
         let mut criteria_total: isize = 0;
         let mut criteria = vec![];
         for (name, crit_yaml) in rubric_yaml.criteria {
-            let crit = crit_yaml.into_criterion(name);
-            criteria_total += crit.worth as isize;
+            let crit = crit_yaml.into_criterion(name)?;
+            // Deductions don't count towards the total
+            if !crit.deduction {
+                criteria_total += crit.worth as isize;
+            }
             criteria.push(crit);
         }
 
+        check_funcs(&criteria)?;
 
         if let Some(t) = rubric_yaml.total {
             if criteria_total != t {
@@ -263,17 +343,37 @@ impl FromStr for Rubric {
             final_deadline = Some(DateTime::from(parsed_deadline));
         }
 
+        // Versions can be numbers or strings, but they're kept as strings
+        let version = match rubric_yaml.version {
+            None => None,
+            Some(serde_yaml::Value::String(v)) => Some(v),
+            Some(serde_yaml::Value::Number(n)) => Some(n.to_string()),
+            Some(_) => anyhow::bail!("Rubric version should be a string or number"),
+        };
+
+        let score_policy = match rubric_yaml.score_policy {
+            Some(policy) => policy.parse()?,
+            None => ScorePolicy::default(),
+        };
+
         // Construct a rubric
         Ok(Rubric {
             name: rubric_yaml.name,
             desc: rubric_yaml.desc,
+            version: version,
             criteria: criteria,
             total: criteria_total,
             deadline: deadline,
             final_deadline: final_deadline,
             allow_late: rubric_yaml.allow_late.unwrap_or(true),
             late_penalty: rubric_yaml.late_penalty.unwrap_or(0),
-            daily_penalty: rubric_yaml.late_penalty_per_day.unwrap_or(0)
+            daily_penalty: rubric_yaml.late_penalty_per_day.unwrap_or(0),
+            max_attempts: rubric_yaml.max_attempts,
+            identity_key: rubric_yaml.identity
+                .map(|k| attempt_policy::parse_identity_key(&k))
+                .unwrap_or_else(|| String::from(attempt_policy::DEFAULT_IDENTITY_KEY)),
+            reject_extra_attempts: rubric_yaml.reject_extra_attempts.unwrap_or(true),
+            score_policy: score_policy,
         })
     }
 }
@@ -293,6 +393,19 @@ mod tests {
         let rubric = Rubric::from_yaml(yaml_data()).expect("Bad yaml");
         assert_eq!(rubric.name, "Test Rubric");
         assert!(rubric.desc.is_some());
+        assert!(rubric.version.is_none());
+    }
+
+    #[test]
+    fn test_rubric_version() {
+        let yaml = "name: Versioned\nversion: 2\ncriteria: {}";
+        assert_eq!(Rubric::from_yaml(yaml).unwrap().version.as_deref(), Some("2"));
+
+        let yaml = "name: Versioned\nversion: fall-2020\ncriteria: {}";
+        assert_eq!(Rubric::from_yaml(yaml).unwrap().version.as_deref(), Some("fall-2020"));
+
+        let yaml = "name: Versioned\nversion: [1, 2]\ncriteria: {}";
+        assert!(Rubric::from_yaml(yaml).is_err());
     }
 
     #[test]
@@ -332,6 +445,242 @@ mod tests {
         assert!(raw.parse::<Rubric>().is_ok());
     }
 
+    #[test]
+    fn test_attempt_policy() {
+        let raw = r#"
+            name: Limited rubric
+            max_attempts: 3
+            identity: data.student_id
+            criteria:
+                Only:
+                    worth: 10
+        "#;
+        let policy = raw.parse::<Rubric>().unwrap().attempt_policy();
+        assert_eq!(policy.max_attempts, Some(3));
+        assert_eq!(policy.identity_key, "student_id");
+        assert!(policy.reject_extra);
+
+        let defaults = Rubric::from_yaml(yaml_data()).unwrap().attempt_policy();
+        assert_eq!(defaults, AttemptPolicy::default());
+    }
+
+    #[test]
+    fn test_score_policy() {
+        let raw = r#"
+            name: Best counts
+            score_policy: best
+            criteria:
+                Only:
+                    worth: 10
+        "#;
+        assert_eq!(raw.parse::<Rubric>().unwrap().score_policy, ScorePolicy::Best);
+        assert!(raw.replace("best", "worst").parse::<Rubric>().is_err());
+
+        let defaults = Rubric::from_yaml(yaml_data()).unwrap();
+        assert_eq!(defaults.score_policy, ScorePolicy::Latest);
+    }
+
+    #[test]
+    fn test_nested_criteria() {
+        let raw = r#"
+            name: Nested rubric
+            criteria:
+                Docker works:
+                    worth: 20
+                    combine: all
+                    criteria:
+                        Dockerfile builds:
+                            func: builds
+                            worth: 10
+                        Serves port 80:
+                            func: serves
+                            worth: 10
+                Any approach:
+                    worth: 5
+                    combine: any
+                    criteria:
+                        First approach:
+                            worth: 5
+                        Second approach:
+                            func: second
+                            worth: 5
+                Partial credit:
+                    combine: sum
+                    criteria:
+                        Part one:
+                            func: part_one
+                            worth: 3
+                        Part two:
+                            worth: 4
+        "#;
+        fn pass(_: &TestData) -> bool { true }
+
+        let mut rubric = Rubric::from_yaml(raw).expect("Bad yaml");
+        assert_eq!(rubric.total_points(), 32);
+
+        attach! {
+            rubric,
+            "builds" => pass,
+            "second" => pass,
+            "part_one" => pass
+        };
+
+        let mut sub = crate::Submission::new();
+        sub.grade_against(&mut rubric);
+        // 0 for docker, 5 for any, 3 for the sum
+        assert_eq!(rubric.points(), 8);
+        assert_eq!(sub.grade, 8);
+    }
+
+    #[test]
+    fn test_deductions() {
+        let raw = r#"
+            name: Deduction rubric
+            criteria:
+                Compiles:
+                    func: compiles
+                    worth: 10
+                Uses unsafe:
+                    func: uses_unsafe
+                    worth: 5
+                    deduction: true
+                Committed build artifacts:
+                    func: artifacts
+                    worth: 3
+                    deduction: true
+        "#;
+        fn yes(_: &TestData) -> bool { true }
+
+        let mut rubric = Rubric::from_yaml(raw).expect("Bad yaml");
+        assert_eq!(rubric.total, 10);
+        assert_eq!(rubric.total_points(), 10);
+        assert_eq!(rubric.deductions().len(), 2);
+
+        attach! {
+            rubric,
+            "compiles" => yes,
+            "uses_unsafe" => yes
+        };
+
+        let mut sub = crate::Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(rubric.points(), 5);
+        assert_eq!(rubric.deducted(), 5);
+        assert_eq!(sub.grade, 5);
+        assert!(sub.failed.contains(&String::from("Uses unsafe (-5)")));
+        assert!(!sub.failed.iter().any(|f| f.contains("Committed build artifacts")));
+    }
+
+    #[test]
+    fn test_nested_deductions() {
+        let raw = r#"
+            name: Nested deductions
+            criteria:
+                Tests:
+                    combine: all
+                    criteria:
+                        Unit tests:
+                            func: unit
+                            worth: 10
+                        Skipped tests:
+                            func: skipped
+                            worth: 4
+                            deduction: true
+        "#;
+        fn yes(_: &TestData) -> bool { true }
+
+        let mut rubric = Rubric::from_yaml(raw).expect("Bad yaml");
+        assert_eq!(rubric.total_points(), 10);
+        assert_eq!(rubric.deductions().len(), 1);
+
+        attach! {
+            rubric,
+            "unit" => yes,
+            "skipped" => yes
+        };
+
+        let mut sub = crate::Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(rubric.deducted(), 4);
+        assert_eq!(rubric.points(), 6);
+        assert_eq!(sub.grade, 6);
+    }
+
+    #[test]
+    fn test_bad_combinator() {
+        let raw = r#"
+            name: Bad rubric
+            criteria:
+                Parent:
+                    worth: 5
+                    combine: most
+                    criteria:
+                        Child:
+                            worth: 5
+        "#;
+        assert!(Rubric::from_yaml(raw).is_err());
+    }
+
+    #[test]
+    fn test_composite_worth() {
+        let rubric = |combine: &str, worth: &str| format!(r#"
+            name: Composite
+            criteria:
+                Parent:
+                    {}
+                    combine: {}
+                    criteria:
+                        One way:
+                            worth: 5
+                        Another way:
+                            worth: 3
+        "#, worth, combine);
+
+        // Only one has to pass, so it's worth the best one
+        let any = Rubric::from_yaml(&rubric("any", "")).unwrap();
+        assert_eq!(any.total_points(), 5);
+        let sum = Rubric::from_yaml(&rubric("sum", "")).unwrap();
+        assert_eq!(sum.total_points(), 8);
+
+        // at_least has to say what it's worth, and can't ask for too few or too many
+        assert!(Rubric::from_yaml(&rubric("at_least 1", "")).is_err());
+        assert_eq!(Rubric::from_yaml(&rubric("at_least 2", "worth: 6")).unwrap().total_points(), 6);
+        assert!(Rubric::from_yaml(&rubric("at_least 0", "worth: 6")).is_err());
+        assert!(Rubric::from_yaml(&rubric("at_least 3", "worth: 6")).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_funcs() {
+        let raw = r#"
+            name: Duplicate funcs
+            criteria:
+                Lab one:
+                    criteria:
+                        Compiles:
+                            worth: 5
+                Lab two:
+                    criteria:
+                        Compiles:
+                            worth: 5
+        "#;
+        let err = Rubric::from_yaml(raw).err().expect("Both children are 'compiles'");
+        assert!(format!("{:#}", err).contains("compiles"));
+    }
+
+    #[test]
+    fn test_leaf_needs_worth() {
+        let raw = r#"
+            name: Missing worth
+            criteria:
+                Parent:
+                    criteria:
+                        Child:
+                            func: child
+        "#;
+        let err = Rubric::from_yaml(raw).err().expect("A leaf needs a worth");
+        assert!(format!("{:#}", err).contains("Child"));
+    }
+
     #[test]
     fn test_rubric_past_due() {
         let ok_rubric = Rubric::from_yaml(yaml_data()).unwrap();
@@ -0,0 +1,85 @@
+//! Which attempt counts
+//!
+//! When students can submit more than once, the gradebook needs one grade for
+//! each of them. The rubric says how to get it with `score_policy`.
+//!
+//! ```yaml
+//! max_attempts: 3
+//! # best, latest, or average. Defaults to latest.
+//! score_policy: best
+//! ```
+
+// std uses
+use std::fmt;
+use std::str::FromStr;
+
+// external uses
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+
+/// How a student's attempts become one grade
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScorePolicy {
+    /// The highest grade
+    Best,
+    /// The grade of the last attempt
+    Latest,
+    /// The average of every attempt
+    Average,
+}
+
+impl Default for ScorePolicy {
+    /// The last attempt counts
+    fn default() -> Self {
+        ScorePolicy::Latest
+    }
+}
+
+impl FromStr for ScorePolicy {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "best" | "highest" => Ok(ScorePolicy::Best),
+            "latest" | "last" => Ok(ScorePolicy::Latest),
+            "average" | "mean" => Ok(ScorePolicy::Average),
+            _ => Err(anyhow!("Unknown score policy '{}', expected best, latest, or average", s)),
+        }
+    }
+}
+
+impl fmt::Display for ScorePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScorePolicy::Best => "best",
+            ScorePolicy::Latest => "latest",
+            ScorePolicy::Average => "average",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("best".parse::<ScorePolicy>().unwrap(), ScorePolicy::Best);
+        assert_eq!(" Latest".parse::<ScorePolicy>().unwrap(), ScorePolicy::Latest);
+        assert_eq!("mean".parse::<ScorePolicy>().unwrap(), ScorePolicy::Average);
+        assert!("worst".parse::<ScorePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for policy in &[ScorePolicy::Best, ScorePolicy::Latest, ScorePolicy::Average] {
+            assert_eq!(policy.to_string().parse::<ScorePolicy>().unwrap(), *policy);
+        }
+        assert_eq!(ScorePolicy::default(), ScorePolicy::Latest);
+    }
+}
@@ -16,10 +16,12 @@
 use std::collections::HashMap;
 
 // external uses
+use anyhow::anyhow;
 use serde::Deserialize;
 
 // internal uses
-use crate::rubric::Criterion;
+use crate::Result;
+use crate::rubric::{Criterion, Combinator};
 
 
 /// A yaml representation of a [`Rubric`](crate::rubric::Rubric).
@@ -30,6 +32,7 @@ use crate::rubric::Criterion;
 pub struct RubricYaml {
     pub name: String,
     pub desc: Option<String>,
+    pub version: Option<serde_yaml::Value>,
     pub criteria: HashMap<String, CriterionYaml>,
     pub total: Option<isize>,
     pub deadline: Option<String>,
@@ -37,28 +40,76 @@ pub struct RubricYaml {
     pub allow_late: Option<bool>,
     pub late_penalty: Option<isize>,
     pub late_penalty_per_day: Option<isize>,
+    pub max_attempts: Option<u32>,
+    pub identity: Option<String>,
+    pub reject_extra_attempts: Option<bool>,
+    pub score_policy: Option<String>,
 }
 
 /// A yaml representation of [`Criterion`](crate::criterion::Criterion)
 ///
 /// This can be deserialized from valid yaml, then converted into a
 /// Criterion with [`into_criterion`](crate::yaml::CriterionYaml::into_criterion).
+///
+/// A criterion can have its own `criteria`, which are combined according
+/// to `combine`. If a criterion with children has no `worth`, it's worth
+/// the total of its children, or the most any one child is worth with `any`.
+/// Criteria without children, or combining `at_least` some, need a `worth`.
 #[derive(Deserialize)]
 pub struct CriterionYaml {
     func: Option<String>,
     index: Option<i64>,
     desc: Option<String>,
-    worth: isize,
+    worth: Option<isize>,
     messages: Option<(String, String)>,
     hide: Option<bool>,
+    combine: Option<String>,
+    criteria: Option<HashMap<String, CriterionYaml>>,
+    deduction: Option<bool>,
 }
 
 impl CriterionYaml {
     // Normally I would implement FromStr but I can't because I can't attach the `name`,
     // just because of the yaml format. Kinda fucky, I know.
-    pub fn into_criterion(self, name: String) -> Criterion {
-        // The two required fields
-        let mut builder = Criterion::new(&name).worth(self.worth);
+    pub fn into_criterion(self, name: String) -> Result<Criterion> {
+        // Build the children first, they decide the default worth
+        let mut children = Vec::new();
+        if let Some(criteria) = self.criteria {
+            for (child_name, child_yaml) in criteria {
+                children.push(child_yaml.into_criterion(child_name)?);
+            }
+        }
+        let combinator = match self.combine {
+            Some(combine) => combine.parse::<Combinator>()?,
+            None => Combinator::default(),
+        };
+
+        // Deductions don't decide if the parent passes, or count towards its worth
+        let graded: Vec<&Criterion> = children.iter().filter(|c| !c.deduction).collect();
+        if let Combinator::AtLeast(n) = combinator {
+            if !children.is_empty() && (n == 0 || n > graded.len()) {
+                return Err(anyhow!(
+                    "Criterion '{}' needs at_least between 1 and {}, its number of children",
+                    name, graded.len()
+                ));
+            }
+        }
+
+        let worth = match (self.worth, combinator) {
+            (Some(worth), _) => worth,
+            (None, _) if children.is_empty() => return Err(anyhow!("Criterion '{}' needs a worth", name)),
+            // Only one child has to pass, so it's worth as much as the best one
+            (None, Combinator::Any) => graded.iter().map(|c| c.worth).max().unwrap_or(0),
+            (None, Combinator::AtLeast(_)) => {
+                return Err(anyhow!("Criterion '{}' combines at_least some children, so it needs a worth", name))
+            },
+            (None, _) => graded.iter().map(|c| c.worth).sum(),
+        };
+
+        let mut builder = Criterion::new(&name).worth(worth).combinator(combinator);
+        for child in children {
+            builder = builder.child(child);
+        }
 
         if let Some(msg) = self.messages {
             builder = builder.messages(&msg.0, &msg.1)
@@ -75,7 +126,10 @@ impl CriterionYaml {
         if let Some(index) = self.index {
             builder = builder.index(index);
         }
+        if let Some(d) = self.deduction {
+            builder = builder.deduction(d);
+        }
 
-        builder.build()
+        Ok(builder.build())
     }
 }